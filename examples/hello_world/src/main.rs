@@ -17,14 +17,9 @@ fn main() {
                                    "hello",
                                    false);
     let block = fun.new_block("main_block");
-    let function_ptr = context.new_function_pointer_type(None,
-                                                         void_ty,
-                                                         &[],
-                                                         false);
-    let ptr = unsafe {
-        context.new_rvalue_from_ptr(function_ptr, say_hello as *mut ())
-    };
-    let call = context.new_call_through_ptr(None, ptr, &[]);
+    let (_, say_hello_ptr) = context.import_function("say_hello",
+                                                     say_hello as extern "C" fn());
+    let call = context.new_call_through_ptr(None, say_hello_ptr, &[]);
     block.add_eval(None, call);
     block.end_with_void_return(None);
     let result = context.compile();
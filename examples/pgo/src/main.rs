@@ -0,0 +1,90 @@
+extern crate gccjit;
+
+use gccjit::Context;
+use gccjit::FunctionType;
+use gccjit::OptimizationLevel;
+use gccjit::OutputKind;
+use gccjit::ToRValue;
+use gccjit::{BinaryOp, ComparisonOp};
+
+use std::default::Default;
+use std::env;
+use std::fs;
+use std::process::Command;
+use std::time::Instant;
+
+/// Builds a program whose main sums the Collatz sequence lengths of the
+/// numbers up to a million, and exits with the sum modulo 100.
+fn build(context: &Context) {
+    let int_ty = context.new_type::<i32>();
+    let long_ty = context.new_type::<i64>();
+    let long = |n| context.new_rvalue_from_long(long_ty, n);
+
+    let n = context.new_parameter(None, long_ty, "n");
+    let steps_fn = context.new_function(None, FunctionType::Internal, int_ty, &[n], "steps", false);
+    let entry = steps_fn.new_block("entry");
+    let steps = steps_fn.new_local(None, int_ty, "steps");
+    entry.add_assignment(None, steps, context.new_rvalue_zero(int_ty));
+    let seq = steps_fn.new_while_loop(None, entry, "seq");
+    seq.test(None, context.new_comparison(None, ComparisonOp::NotEquals, n, long(1)));
+    let even = steps_fn.new_block("even");
+    let odd = steps_fn.new_block("odd");
+    let next = steps_fn.new_block("next");
+    let parity = context.new_binary_op(None, BinaryOp::BitwiseAnd, long_ty, n, long(1));
+    seq.body.end_with_conditional(None, context.new_comparison(None, ComparisonOp::Equals, parity, long(0)),
+                                  even, odd);
+    even.add_assignment_op(None, n, BinaryOp::Divide, long(2));
+    even.end_with_jump(None, next);
+    let tripled = context.new_binary_op(None, BinaryOp::Mult, long_ty, n, long(3));
+    odd.add_assignment(None, n, context.new_binary_op(None, BinaryOp::Plus, long_ty, tripled, long(1)));
+    odd.end_with_jump(None, next);
+    next.add_assignment_op(None, steps, BinaryOp::Plus, context.new_rvalue_one(int_ty));
+    seq.close(None, next).end_with_return(None, steps);
+
+    let main = context.new_function(None, FunctionType::Exported, int_ty, &[], "main", false);
+    let entry = main.new_block("entry");
+    let total = main.new_local(None, long_ty, "total");
+    let i = main.new_local(None, long_ty, "i");
+    entry.add_assignment(None, total, long(0));
+    entry.add_assignment(None, i, long(1));
+    let sum = main.new_while_loop(None, entry, "sum");
+    sum.test(None, context.new_comparison(None, ComparisonOp::LessThanEquals, i, long(1_000_000)));
+    let count = context.new_call(None, steps_fn, &[i.to_rvalue()]);
+    sum.body.add_assignment_op(None, total, BinaryOp::Plus, context.new_cast(None, count, long_ty));
+    sum.body.add_assignment_op(None, i, BinaryOp::Plus, long(1));
+    let exit = sum.close(None, sum.body);
+    let code = context.new_binary_op(None, BinaryOp::Modulo, long_ty, total, long(100));
+    exit.end_with_return(None, context.new_cast(None, code, int_ty));
+}
+
+/// Compiles a context to the given executable, runs it, and prints how
+/// long it took.
+fn run(context: &Context, exe: &str, label: &str) {
+    context.set_optimization_level(OptimizationLevel::Aggressive);
+    build(context);
+    context.compile_to_file(OutputKind::Executable, exe);
+    let start = Instant::now();
+    let status = Command::new(exe).status().expect("failed to run the compiled program");
+    println!("{}: exited with {:?} after {:?}", label, status.code(), start.elapsed());
+}
+
+fn main() {
+    // the instrumented and optimized builds must be compiled to the same
+    // path, since profiles are found by the name of the object file.
+    let dir = env::temp_dir().join("gccjit-pgo-example");
+    let profile_dir = dir.join("profile");
+    fs::create_dir_all(&profile_dir).unwrap();
+    let exe = dir.join("collatz");
+    let exe = exe.to_str().unwrap();
+
+    let baseline = Context::default();
+    run(&baseline, exe, "without a profile");
+
+    let instrumented = Context::default();
+    instrumented.set_profile_generate(&profile_dir);
+    run(&instrumented, exe, "collecting a profile");
+
+    let optimized = Context::default();
+    optimized.set_profile_use(&profile_dir);
+    run(&optimized, exe, "with the profile");
+}
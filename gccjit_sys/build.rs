@@ -0,0 +1,11 @@
+// libgccjit itself is linked through the #[link] attribute in lib.rs.
+// Cargo requires a build script for packages that declare `links`, so this
+// one only lets the library be found outside of the default search path.
+use std::env;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GCCJIT_LIB_DIR");
+    if let Ok(dir) = env::var("GCCJIT_LIB_DIR") {
+        println!("cargo:rustc-link-search=native={}", dir);
+    }
+}
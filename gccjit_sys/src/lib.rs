@@ -2,7 +2,13 @@
 
 extern crate libc;
 
-use libc::{c_char, c_int, FILE, c_void, c_long, c_double};
+use libc::{c_char, c_int, FILE, c_void, c_long, c_double, size_t};
+#[cfg(feature = "master")]
+use libc::ssize_t;
+
+// buffers that libgccjit allocates for the caller, such as the ones filled
+// in by gcc_jit_context_enable_dump, are released with free.
+pub use libc::free;
 
 // opaque pointers
 pub enum gcc_jit_context {}
@@ -17,6 +23,7 @@ pub enum gcc_jit_block {}
 pub enum gcc_jit_rvalue {}
 pub enum gcc_jit_lvalue {}
 pub enum gcc_jit_param {}
+pub enum gcc_jit_case {}
 
 #[repr(C)]
 pub enum gcc_jit_str_option {
@@ -226,8 +233,36 @@ pub enum gcc_jit_comparison
     GCC_JIT_COMPARISON_GE
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum gcc_jit_fn_attribute
+{
+    GCC_JIT_FN_ATTRIBUTE_ALIAS,
+    GCC_JIT_FN_ATTRIBUTE_ALWAYS_INLINE,
+    GCC_JIT_FN_ATTRIBUTE_INLINE,
+    GCC_JIT_FN_ATTRIBUTE_NOINLINE,
+    GCC_JIT_FN_ATTRIBUTE_TARGET,
+    GCC_JIT_FN_ATTRIBUTE_USED,
+    GCC_JIT_FN_ATTRIBUTE_VISIBILITY,
+    GCC_JIT_FN_ATTRIBUTE_COLD,
+    GCC_JIT_FN_ATTRIBUTE_RETURNS_TWICE,
+    GCC_JIT_FN_ATTRIBUTE_PURE,
+    GCC_JIT_FN_ATTRIBUTE_CONST,
+    GCC_JIT_FN_ATTRIBUTE_WEAK,
+    GCC_JIT_FN_ATTRIBUTE_NONNULL,
+    GCC_JIT_FN_ATTRIBUTE_MAX
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum gcc_jit_variable_attribute
+{
+    GCC_JIT_VARIABLE_ATTRIBUTE_VISIBILITY,
+    GCC_JIT_VARIABLE_ATTRIBUTE_MAX
+}
+
 #[link(name = "gccjit")]
-extern {
+extern "C" {
     // context operations
     pub fn gcc_jit_context_acquire() -> *mut gcc_jit_context;
     pub fn gcc_jit_context_release(ctx: *mut gcc_jit_context);
@@ -244,6 +279,9 @@ extern {
     pub fn gcc_jit_context_compile_to_file(ctx: *mut gcc_jit_context,
                                            kind: gcc_jit_output_kind,
                                            path: *const c_char);
+    pub fn gcc_jit_context_enable_dump(ctx: *mut gcc_jit_context,
+                                       dumpname: *const c_char,
+                                       out_ptr: *mut *mut c_char);
     pub fn gcc_jit_context_dump_to_file(ctx: *mut gcc_jit_context,
                                         path: *const c_char,
                                         update_locations: c_int);
@@ -252,6 +290,7 @@ extern {
                                        flags: c_int,
                                        verbosity: c_int);
     pub fn gcc_jit_context_get_first_error(ctx: *mut gcc_jit_context) -> *const c_char;
+    pub fn gcc_jit_context_get_last_error(ctx: *mut gcc_jit_context) -> *const c_char;
 
     // result operations
     pub fn gcc_jit_result_get_code(result: *mut gcc_jit_result,
@@ -468,4 +507,134 @@ extern {
     pub fn gcc_jit_context_new_child_context(parent: *mut gcc_jit_context) -> *mut gcc_jit_context;
     pub fn gcc_jit_context_dump_reproducer_to_file(parent: *mut gcc_jit_context,
                                                    path: *const c_char);
+
+    // LIBGCCJIT_ABI_1
+    pub fn gcc_jit_context_add_command_line_option(ctx: *mut gcc_jit_context,
+                                                   optname: *const c_char);
+
+    // LIBGCCJIT_ABI_2
+    pub fn gcc_jit_context_set_bool_allow_unreachable_blocks(ctx: *mut gcc_jit_context,
+                                                             value: c_int);
+
+    // LIBGCCJIT_ABI_3
+    pub fn gcc_jit_block_end_with_switch(block: *mut gcc_jit_block,
+                                         loc: *mut gcc_jit_location,
+                                         expr: *mut gcc_jit_rvalue,
+                                         default_block: *mut gcc_jit_block,
+                                         num_cases: c_int,
+                                         cases: *mut *mut gcc_jit_case);
+    pub fn gcc_jit_context_new_case(ctx: *mut gcc_jit_context,
+                                    min_value: *mut gcc_jit_rvalue,
+                                    max_value: *mut gcc_jit_rvalue,
+                                    dest_block: *mut gcc_jit_block) -> *mut gcc_jit_case;
+    pub fn gcc_jit_case_as_object(case_: *mut gcc_jit_case) -> *mut gcc_jit_object;
+
+    // LIBGCCJIT_ABI_5
+    pub fn gcc_jit_context_set_bool_use_external_driver(ctx: *mut gcc_jit_context,
+                                                        value: c_int);
+
+    // LIBGCCJIT_ABI_8
+    pub fn gcc_jit_type_get_vector(ty: *mut gcc_jit_type,
+                                   num_units: size_t) -> *mut gcc_jit_type;
+
+    // LIBGCCJIT_ABI_9
+    pub fn gcc_jit_function_get_address(func: *mut gcc_jit_function,
+                                        loc: *mut gcc_jit_location) -> *mut gcc_jit_rvalue;
+
+    // LIBGCCJIT_ABI_10
+    pub fn gcc_jit_context_new_rvalue_from_vector(ctx: *mut gcc_jit_context,
+                                                  loc: *mut gcc_jit_location,
+                                                  vec_type: *mut gcc_jit_type,
+                                                  num_elements: size_t,
+                                                  elements: *mut *mut gcc_jit_rvalue) -> *mut gcc_jit_rvalue;
+
+    // LIBGCCJIT_ABI_11
+    pub fn gcc_jit_context_add_driver_option(ctx: *mut gcc_jit_context,
+                                             optname: *const c_char);
+
+    // LIBGCCJIT_ABI_12
+    pub fn gcc_jit_context_new_bitfield(ctx: *mut gcc_jit_context,
+                                        loc: *mut gcc_jit_location,
+                                        ty: *mut gcc_jit_type,
+                                        width: c_int,
+                                        name: *const c_char) -> *mut gcc_jit_field;
+
+    // LIBGCCJIT_ABI_14
+    pub fn gcc_jit_global_set_initializer(global: *mut gcc_jit_lvalue,
+                                          blob: *const c_void,
+                                          num_bytes: size_t) -> *mut gcc_jit_lvalue;
+
+    // LIBGCCJIT_ABI_16
+    pub fn gcc_jit_struct_get_field(struct_type: *mut gcc_jit_struct,
+                                    index: size_t) -> *mut gcc_jit_field;
+    pub fn gcc_jit_struct_get_field_count(struct_type: *mut gcc_jit_struct) -> size_t;
+    pub fn gcc_jit_type_is_pointer(ty: *mut gcc_jit_type) -> *mut gcc_jit_type;
+    pub fn gcc_jit_type_unqualified(ty: *mut gcc_jit_type) -> *mut gcc_jit_type;
+
+    // LIBGCCJIT_ABI_18
+    pub fn gcc_jit_lvalue_set_link_section(lvalue: *mut gcc_jit_lvalue,
+                                           section_name: *const c_char);
+
+    // LIBGCCJIT_ABI_19
+    pub fn gcc_jit_global_set_initializer_rvalue(global: *mut gcc_jit_lvalue,
+                                                 init_value: *mut gcc_jit_rvalue) -> *mut gcc_jit_lvalue;
+
+    // LIBGCCJIT_ABI_21
+    pub fn gcc_jit_context_new_bitcast(ctx: *mut gcc_jit_context,
+                                       loc: *mut gcc_jit_location,
+                                       rvalue: *mut gcc_jit_rvalue,
+                                       ty: *mut gcc_jit_type) -> *mut gcc_jit_rvalue;
+
+    // LIBGCCJIT_ABI_26
+    pub fn gcc_jit_function_add_attribute(func: *mut gcc_jit_function,
+                                          attribute: gcc_jit_fn_attribute);
+    pub fn gcc_jit_function_add_string_attribute(func: *mut gcc_jit_function,
+                                                 attribute: gcc_jit_fn_attribute,
+                                                 value: *const c_char);
+    pub fn gcc_jit_function_add_integer_array_attribute(func: *mut gcc_jit_function,
+                                                        attribute: gcc_jit_fn_attribute,
+                                                        value: *const c_int,
+                                                        length: size_t);
+    pub fn gcc_jit_lvalue_add_string_attribute(variable: *mut gcc_jit_lvalue,
+                                               attribute: gcc_jit_variable_attribute,
+                                               value: *const c_char);
+
+    // LIBGCCJIT_ABI_27
+    pub fn gcc_jit_context_new_sizeof(ctx: *mut gcc_jit_context,
+                                      ty: *mut gcc_jit_type) -> *mut gcc_jit_rvalue;
+
+    // LIBGCCJIT_ABI_28
+    pub fn gcc_jit_context_new_alignof(ctx: *mut gcc_jit_context,
+                                       ty: *mut gcc_jit_type) -> *mut gcc_jit_rvalue;
+
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_function_set_personality_function(func: *mut gcc_jit_function,
+                                                     personality_func: *mut gcc_jit_function);
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_block_add_try_catch(block: *mut gcc_jit_block,
+                                       loc: *mut gcc_jit_location,
+                                       try_block: *mut gcc_jit_block,
+                                       catch_block: *mut gcc_jit_block);
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_block_add_try_finally(block: *mut gcc_jit_block,
+                                         loc: *mut gcc_jit_location,
+                                         try_block: *mut gcc_jit_block,
+                                         finally_block: *mut gcc_jit_block);
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_context_convert_vector(ctx: *mut gcc_jit_context,
+                                          loc: *mut gcc_jit_location,
+                                          vector: *mut gcc_jit_rvalue,
+                                          ty: *mut gcc_jit_type) -> *mut gcc_jit_rvalue;
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_context_new_vector_access(ctx: *mut gcc_jit_context,
+                                             loc: *mut gcc_jit_location,
+                                             vector: *mut gcc_jit_rvalue,
+                                             index: *mut gcc_jit_rvalue) -> *mut gcc_jit_lvalue;
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_context_set_output_ident(ctx: *mut gcc_jit_context,
+                                            output_ident: *const c_char);
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_type_is_floating_point(ty: *mut gcc_jit_type) -> c_int;
+    #[cfg(feature = "master")]
+    pub fn gcc_jit_type_get_alignment(ty: *mut gcc_jit_type) -> ssize_t;
 }
@@ -0,0 +1,481 @@
+//! Describes how the C calling convention of the host passes struct and
+//! union values to and from functions.
+//!
+//! Jitted functions use the C calling convention of the target, which
+//! differs between platforms in how aggregates are passed: SysV x86-64
+//! splits small structs across integer and SSE registers, Windows x64 only
+//! passes structs of 1, 2, 4, or 8 bytes in registers, and AArch64 has its
+//! own rules for homogeneous floating point aggregates. Getting this wrong
+//! when calling a jitted function from Rust (or the other way around)
+//! silently corrupts arguments, so this module classifies types according
+//! to the host convention and can generate thunks whose signatures only
+//! use pointers, which are passed the same way everywhere.
+
+use std::mem;
+use std::os::raw::{c_char, c_int, c_long, c_longlong, c_short};
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types::*;
+
+use context::Context;
+use function::{self, Function, FunctionType};
+use lvalue::ToLValue;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, Registry, TypeKind};
+use types::{self, Type};
+
+/// The calling conventions that this module knows how to classify.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Convention {
+    /// The System V AMD64 ABI, used by Linux, macOS, and the BSDs on x86-64.
+    SysV64,
+    /// The Microsoft x64 calling convention.
+    Win64,
+    /// The ARM 64-bit Procedure Call Standard.
+    Aapcs64
+}
+
+impl Convention {
+    /// Gets the convention that the host uses for extern "C" functions,
+    /// or None if the host isn't one of the supported targets.
+    pub fn host() -> Option<Convention> {
+        if cfg!(all(target_arch = "x86_64", windows)) {
+            Some(Convention::Win64)
+        } else if cfg!(target_arch = "x86_64") {
+            Some(Convention::SysV64)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Convention::Aapcs64)
+        } else {
+            None
+        }
+    }
+}
+
+/// The kind of register that (part of) a value is passed in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RegisterClass {
+    /// A general purpose register.
+    Integer,
+    /// A floating point or vector register.
+    Float
+}
+
+/// How a value is passed to or returned from a function.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PassMode {
+    /// The value is passed directly in registers of the given classes, one
+    /// register per entry. Values of size zero use no registers at all.
+    Registers(Vec<RegisterClass>),
+    /// The value is copied into the argument area on the stack.
+    Memory,
+    /// The caller copies the value to memory and passes a pointer to it.
+    /// For return values, this is the hidden pointer to the storage that
+    /// the callee writes the result to.
+    Indirect
+}
+
+/// Classifies how a value of the given type is passed as an argument
+/// under the host calling convention. Returns None if the host convention
+/// isn't supported or if the layout of the type isn't known, which is the
+/// case for types that weren't constructed through this library.
+pub fn classify_argument(ty: Type) -> Option<PassMode> {
+    Convention::host().and_then(|conv| classify_argument_for(conv, ty))
+}
+
+/// Classifies how a value of the given type is returned under the host
+/// calling convention. See classify_argument for when None is returned.
+pub fn classify_return(ty: Type) -> Option<PassMode> {
+    Convention::host().and_then(|conv| classify_return_for(conv, ty))
+}
+
+/// Classifies how a value of the given type is passed as an argument
+/// under the given calling convention.
+pub fn classify_argument_for(conv: Convention, ty: Type) -> Option<PassMode> {
+    classify(conv, ty, false)
+}
+
+/// Classifies how a value of the given type is returned under the given
+/// calling convention.
+pub fn classify_return_for(conv: Convention, ty: Type) -> Option<PassMode> {
+    classify(conv, ty, true)
+}
+
+/// Creates an exported wrapper around a function that takes every struct
+/// or union argument by pointer instead of by value, and that writes a
+/// struct or union result through a pointer passed as an additional first
+/// parameter instead of returning it. The wrapper has the same signature
+/// under every calling convention, so it can be called from the host
+/// without depending on how the target classifies aggregates.
+///
+/// Returns None if the signature of the wrapped function isn't known,
+/// which is the case for functions that weren't created through
+/// Context::new_function.
+pub fn new_pointer_thunk<'a, 'ctx, S: AsRef<str>>(ctx: &'a Context<'ctx>,
+                                                   func: Function<'a>,
+                                                   name: S) -> Option<Function<'a>> {
+    let func_ptr = unsafe { function::get_ptr(&func) };
+    let signature = tracking::with_registry(|reg| {
+        reg.function(func_ptr).map(|info| {
+            let param_types : Vec<_> = info.params.iter()
+                .map(|&param| unsafe {
+                    let rvalue = gccjit_sys::gcc_jit_param_as_rvalue(param);
+                    gccjit_sys::gcc_jit_rvalue_get_type(rvalue)
+                })
+                .map(|ty| (ty, is_aggregate(reg, ty)))
+                .collect();
+            let ret = info.return_type;
+            (param_types, ret, is_aggregate(reg, ret))
+        })
+    });
+    let (param_types, return_type, returns_aggregate) = signature?;
+    let return_type = unsafe { types::from_ptr(return_type) };
+    let mut params = vec![];
+    if returns_aggregate {
+        params.push(ctx.new_parameter(None, return_type.make_pointer(), "ret"));
+    }
+    for (idx, &(ty, aggregate)) in param_types.iter().enumerate() {
+        let ty = unsafe { types::from_ptr(ty) };
+        let ty = if aggregate { ty.make_pointer() } else { ty };
+        params.push(ctx.new_parameter(None, ty, format!("arg{}", idx)));
+    }
+    let thunk_return = if returns_aggregate {
+        ctx.new_type::<()>()
+    } else {
+        return_type
+    };
+    let thunk = ctx.new_function(None,
+                                 FunctionType::Exported,
+                                 thunk_return,
+                                 &params,
+                                 name,
+                                 false);
+    let offset = if returns_aggregate { 1 } else { 0 };
+    let args : Vec<RValue> = param_types.iter()
+        .enumerate()
+        .map(|(idx, &(_, aggregate))| {
+            let param = thunk.get_param((idx + offset) as i32).to_rvalue();
+            if aggregate {
+                param.dereference(None).to_rvalue()
+            } else {
+                param
+            }
+        })
+        .collect();
+    let block = thunk.new_block("entry");
+    let call = ctx.new_call(None, func, &args);
+    let returns_void = tracking::with_registry(|reg| {
+        is_void(reg, unsafe { types::get_ptr(&return_type) })
+    });
+    if returns_aggregate {
+        let ret = thunk.get_param(0).to_rvalue().dereference(None);
+        block.add_assignment(None, ret.to_lvalue(), call);
+        block.end_with_void_return(None);
+    } else if returns_void {
+        block.add_eval(None, call);
+        block.end_with_void_return(None);
+    } else {
+        block.end_with_return(None, call);
+    }
+    Some(thunk)
+}
+
+/// The scalar categories that matter for argument classification.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Scalar {
+    Integer,
+    Float,
+    Double,
+    /// An x87 long double or a complex type, which this module doesn't
+    /// classify.
+    Other
+}
+
+/// A scalar component of a value, at a byte offset from its start.
+struct Leaf {
+    offset: usize,
+    size: usize,
+    align: usize,
+    scalar: Scalar
+}
+
+fn classify(conv: Convention, ty: Type, is_return: bool) -> Option<PassMode> {
+    let ty = unsafe { types::get_ptr(&ty) };
+    tracking::with_registry(|reg| {
+        let (size, _) = size_and_align(reg, ty)?;
+        let mut leaves = vec![];
+        if !flatten(reg, ty, 0, &mut leaves) {
+            return None;
+        }
+        if leaves.iter().any(|l| l.scalar == Scalar::Other) {
+            return None;
+        }
+        let aggregate = is_aggregate(reg, ty);
+        match conv {
+            Convention::SysV64 => Some(classify_sysv(size, &leaves, is_return)),
+            Convention::Win64 => Some(classify_win64(size, &leaves, aggregate)),
+            Convention::Aapcs64 => Some(classify_aapcs64(size, &leaves, aggregate))
+        }
+    })
+}
+
+fn classify_sysv(size: usize, leaves: &[Leaf], is_return: bool) -> PassMode {
+    let memory = if is_return { PassMode::Indirect } else { PassMode::Memory };
+    if size > 16 || leaves.iter().any(|l| l.offset % l.align != 0) {
+        return memory;
+    }
+    let mut classes = vec![None; size.div_ceil(8)];
+    for leaf in leaves {
+        let class = match leaf.scalar {
+            Scalar::Integer => RegisterClass::Integer,
+            _ => RegisterClass::Float
+        };
+        let first = leaf.offset / 8;
+        let last = (leaf.offset + leaf.size.max(1) - 1) / 8;
+        for slot in classes[first..=last].iter_mut() {
+            *slot = match (*slot, class) {
+                (Some(RegisterClass::Integer), _) | (_, RegisterClass::Integer) => Some(RegisterClass::Integer),
+                _ => Some(RegisterClass::Float)
+            };
+        }
+    }
+    // eightbytes that only contain padding don't need a register, but gcc
+    // still assigns them one of the neighboring class, so treat them as SSE.
+    PassMode::Registers(classes.into_iter()
+                        .map(|c| c.unwrap_or(RegisterClass::Float))
+                        .collect())
+}
+
+fn classify_win64(size: usize, leaves: &[Leaf], aggregate: bool) -> PassMode {
+    if !aggregate {
+        return match leaves.first() {
+            Some(leaf) if leaf.scalar != Scalar::Integer => PassMode::Registers(vec![RegisterClass::Float]),
+            Some(_) => PassMode::Registers(vec![RegisterClass::Integer]),
+            None => PassMode::Registers(vec![])
+        };
+    }
+    match size {
+        1 | 2 | 4 | 8 => PassMode::Registers(vec![RegisterClass::Integer]),
+        _ => PassMode::Indirect
+    }
+}
+
+fn classify_aapcs64(size: usize, leaves: &[Leaf], aggregate: bool) -> PassMode {
+    if !aggregate {
+        return match leaves.first() {
+            Some(leaf) if leaf.scalar != Scalar::Integer => PassMode::Registers(vec![RegisterClass::Float]),
+            Some(_) => PassMode::Registers(vec![RegisterClass::Integer; size.div_ceil(8)]),
+            None => PassMode::Registers(vec![])
+        };
+    }
+    // homogeneous floating point aggregates of up to four members are passed
+    // in consecutive floating point registers.
+    let homogeneous = match leaves.first() {
+        Some(first) => first.scalar != Scalar::Integer &&
+            leaves.iter().all(|l| l.scalar == first.scalar),
+        None => false
+    };
+    if homogeneous && leaves.len() <= 4 {
+        return PassMode::Registers(vec![RegisterClass::Float; leaves.len()]);
+    }
+    if size > 16 {
+        PassMode::Indirect
+    } else {
+        PassMode::Registers(vec![RegisterClass::Integer; size.div_ceil(8)])
+    }
+}
+
+/// Collects the scalar leaves of a type. Returns false if the type
+/// contains anything whose layout isn't known.
+fn flatten(reg: &Registry,
+           ty: *mut gccjit_sys::gcc_jit_type,
+           offset: usize,
+           leaves: &mut Vec<Leaf>) -> bool {
+    let ty = reg.unqualified(ty);
+    match reg.type_kind(ty) {
+        Some(TypeKind::Basic(kind)) => {
+            match basic_layout(kind) {
+                Some((0, _, _)) => true,
+                Some((size, align, scalar)) => {
+                    leaves.push(Leaf { offset, size, align, scalar });
+                    true
+                }
+                None => false
+            }
+        }
+        Some(&TypeKind::Pointer(_)) => {
+            leaves.push(Leaf {
+                offset,
+                size: mem::size_of::<usize>(),
+                align: mem::align_of::<usize>(),
+                scalar: Scalar::Integer
+            });
+            true
+        }
+        Some(&TypeKind::Array(elem, count)) => {
+            let (elem_size, _) = match size_and_align(reg, elem) {
+                Some(layout) => layout,
+                None => return false
+            };
+            (0..count.max(0) as usize).all(|i| flatten(reg, elem, offset + i * elem_size, leaves))
+        }
+        Some(TypeKind::Struct(fields)) => {
+            let offsets = match field_offsets(reg, fields) {
+                Some(offsets) => offsets,
+                None => return false
+            };
+            fields.iter().zip(offsets.iter()).all(|(&field, &field_offset)| {
+                match reg.field_type(field) {
+                    Some(field_ty) => flatten(reg, field_ty, offset + field_offset, leaves),
+                    None => false
+                }
+            })
+        }
+        Some(TypeKind::Union(fields)) => {
+            fields.iter().all(|&field| {
+                match reg.field_type(field) {
+                    Some(field_ty) => flatten(reg, field_ty, offset, leaves),
+                    None => false
+                }
+            })
+        }
+        _ => false
+    }
+}
+
+/// Computes the offset of each field of a struct with the given fields,
+/// following the usual C layout rules. Bitfields are packed in ways that
+/// differ between targets, so structs containing them have no known
+/// layout.
+pub(crate) fn field_offsets(reg: &Registry, fields: &[*mut gccjit_sys::gcc_jit_field]) -> Option<Vec<usize>> {
+    let mut offset = 0;
+    let mut offsets = vec![];
+    for &field in fields {
+        if reg.field_width(field).is_some() {
+            return None;
+        }
+        let (size, align) = size_and_align(reg, reg.field_type(field)?)?;
+        offset = round_up(offset, align);
+        offsets.push(offset);
+        offset += size;
+    }
+    Some(offsets)
+}
+
+/// Computes the size and alignment of a type on the host, if its layout
+/// is known.
+pub(crate) fn size_and_align(reg: &Registry, ty: *mut gccjit_sys::gcc_jit_type) -> Option<(usize, usize)> {
+    let ty = reg.unqualified(ty);
+    match *reg.type_kind(ty)? {
+        TypeKind::Basic(ref kind) => basic_layout(kind).map(|(size, align, _)| (size, align)),
+        TypeKind::Pointer(_) => Some((mem::size_of::<usize>(), mem::align_of::<usize>())),
+        TypeKind::Array(elem, count) => {
+            let (size, align) = size_and_align(reg, elem)?;
+            Some((size * count.max(0) as usize, align))
+        }
+        // vectors are aligned to their size.
+        TypeKind::Vector(elem, count) => {
+            let (size, _) = size_and_align(reg, elem)?;
+            Some((size * count, size * count))
+        }
+        TypeKind::Struct(ref fields) => {
+            let offsets = field_offsets(reg, fields)?;
+            let mut end = 0;
+            let mut max_align = 1;
+            for (&field, &offset) in fields.iter().zip(offsets.iter()) {
+                let (size, align) = size_and_align(reg, reg.field_type(field)?)?;
+                end = offset + size;
+                max_align = max_align.max(align);
+            }
+            Some((round_up(end, max_align), max_align))
+        }
+        TypeKind::Union(ref fields) => {
+            let mut max_size = 0;
+            let mut max_align = 1;
+            for &field in fields {
+                if reg.field_width(field).is_some() {
+                    return None;
+                }
+                let (size, align) = size_and_align(reg, reg.field_type(field)?)?;
+                max_size = max_size.max(size);
+                max_align = max_align.max(align);
+            }
+            Some((round_up(max_size, max_align), max_align))
+        }
+        _ => None
+    }
+}
+
+/// The size, alignment, and scalar category of a basic gccjit type on the
+/// host.
+pub(crate) fn basic_layout(kind: &gccjit_sys::gcc_jit_types) -> Option<(usize, usize, Scalar)> {
+    macro_rules! layout {
+        ($ty:ty, $scalar:expr) => {
+            Some((mem::size_of::<$ty>(), mem::align_of::<$ty>(), $scalar))
+        }
+    }
+    match *kind {
+        GCC_JIT_TYPE_VOID => Some((0, 1, Scalar::Integer)),
+        GCC_JIT_TYPE_VOID_PTR |
+        GCC_JIT_TYPE_CONST_CHAR_PTR |
+        GCC_JIT_TYPE_FILE_PTR |
+        GCC_JIT_TYPE_SIZE_T => layout!(usize, Scalar::Integer),
+        GCC_JIT_TYPE_BOOL => layout!(bool, Scalar::Integer),
+        GCC_JIT_TYPE_CHAR |
+        GCC_JIT_TYPE_SIGNED_CHAR |
+        GCC_JIT_TYPE_UNSIGNED_CHAR => layout!(c_char, Scalar::Integer),
+        GCC_JIT_TYPE_SHORT |
+        GCC_JIT_TYPE_UNSIGNED_SHORT => layout!(c_short, Scalar::Integer),
+        GCC_JIT_TYPE_INT |
+        GCC_JIT_TYPE_UNSIGNED_INT => layout!(c_int, Scalar::Integer),
+        GCC_JIT_TYPE_LONG |
+        GCC_JIT_TYPE_UNSIGNED_LONG => layout!(c_long, Scalar::Integer),
+        GCC_JIT_TYPE_LONG_LONG |
+        GCC_JIT_TYPE_UNSIGNED_LONG_LONG => layout!(c_longlong, Scalar::Integer),
+        GCC_JIT_TYPE_FLOAT => layout!(f32, Scalar::Float),
+        GCC_JIT_TYPE_DOUBLE => layout!(f64, Scalar::Double),
+        GCC_JIT_TYPE_COMPLEX_FLOAT => Some((8, 4, Scalar::Other)),
+        GCC_JIT_TYPE_COMPLEX_DOUBLE => Some((16, 8, Scalar::Other)),
+        GCC_JIT_TYPE_LONG_DOUBLE |
+        GCC_JIT_TYPE_COMPLEX_LONG_DOUBLE => None
+    }
+}
+
+pub(crate) fn round_up(value: usize, align: usize) -> usize {
+    value.div_ceil(align) * align
+}
+
+fn is_aggregate(reg: &Registry, ty: *mut gccjit_sys::gcc_jit_type) -> bool {
+    matches!(reg.type_kind(reg.unqualified(ty)),
+             Some(&TypeKind::Struct(_)) | Some(&TypeKind::Union(_)))
+}
+
+pub(crate) fn is_void(reg: &Registry, ty: *mut gccjit_sys::gcc_jit_type) -> bool {
+    matches!(reg.type_kind(reg.unqualified(ty)),
+             Some(&TypeKind::Basic(GCC_JIT_TYPE_VOID)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::default::Default;
+
+    #[test]
+    fn classify_small_and_large_structs() {
+        let ctx = Context::default();
+        let double_ty = ctx.new_type::<f64>();
+        let int_ty = ctx.new_type::<i32>();
+        let x = ctx.new_field(None, double_ty, "x");
+        let y = ctx.new_field(None, double_ty, "y");
+        let point = ctx.new_struct_type(None, "point", &[x, y]).as_type();
+        assert_eq!(classify_argument_for(Convention::SysV64, point),
+                   Some(PassMode::Registers(vec![RegisterClass::Float, RegisterClass::Float])));
+        assert_eq!(classify_argument_for(Convention::Win64, point), Some(PassMode::Indirect));
+        assert_eq!(classify_argument_for(Convention::Aapcs64, point),
+                   Some(PassMode::Registers(vec![RegisterClass::Float, RegisterClass::Float])));
+
+        let fields : Vec<_> = (0..5).map(|i| ctx.new_field(None, int_ty, format!("f{}", i))).collect();
+        let big = ctx.new_struct_type(None, "big", &fields).as_type();
+        assert_eq!(classify_argument_for(Convention::SysV64, big), Some(PassMode::Memory));
+        assert_eq!(classify_return_for(Convention::SysV64, big), Some(PassMode::Indirect));
+    }
+}
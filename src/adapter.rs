@@ -0,0 +1,103 @@
+//! Adapter functions, which let a function be called through a signature
+//! other than its own, such as a C callback type that passes an extra
+//! environment pointer or narrower integers than the function takes.
+
+use abi;
+use context::Context;
+use function::{self, Function, FunctionType};
+use location::Location;
+use rvalue::{RValue, ToRValue};
+use tracking;
+use types::{self, Type};
+
+/// Where an argument of the adapted function comes from.
+#[derive(Copy, Clone, Debug)]
+pub enum AdapterArg<'ctx> {
+    /// The adapter's parameter at the given index.
+    Param(usize),
+    /// A fixed value.
+    Value(RValue<'ctx>)
+}
+
+/// How the parameters of an adapter are passed on to the function that it
+/// adapts. Arguments are cast to the type of the parameter they're passed
+/// as where the types differ, which widens or narrows integers and
+/// converts between integers, floating point values, and pointers.
+#[derive(Copy, Clone, Debug)]
+pub enum AdapterStrategy<'a, 'ctx: 'a> {
+    /// Every parameter is passed on in the same position.
+    InOrder,
+    /// The given number of leading parameters, such as an environment
+    /// pointer, are ignored, and the rest are passed on in order.
+    SkipLeading(usize),
+    /// Each argument is given explicitly. Parameters that aren't referred
+    /// to are ignored, and parameters can be passed more than once.
+    Map(&'a [AdapterArg<'ctx>])
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn new<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                     loc: Option<Location<'a>>,
+                     kind: FunctionType,
+                     return_ty: Type<'a>,
+                     params: &[(Type<'a>, &str)],
+                     name: &str,
+                     target: Function<'a>,
+                     strategy: AdapterStrategy<'_, 'a>) -> Function<'a> {
+    let target_ptr = unsafe { function::get_ptr(&target) };
+    let (target_params, target_returns_void, returns_void) = tracking::with_registry(|reg| {
+        let info = reg.function(target_ptr)
+            .unwrap_or_else(|| panic!("can't adapt {:?}, which wasn't created through this crate", target));
+        (info.params.len(),
+         abi::is_void(reg, info.return_type),
+         abi::is_void(reg, unsafe { types::get_ptr(&return_ty) }))
+    });
+    if target_returns_void && !returns_void {
+        panic!("adapter {} returns a value, but {:?} doesn't", name, target);
+    }
+    let adapter_params : Vec<_> = params.iter()
+        .map(|&(ty, name)| ctx.new_parameter(loc, ty, name))
+        .collect();
+    let adapter = ctx.new_function(loc, kind, return_ty, &adapter_params, name, false);
+
+    let sources : Vec<AdapterArg> = match strategy {
+        AdapterStrategy::InOrder => (0..params.len()).map(AdapterArg::Param).collect(),
+        AdapterStrategy::SkipLeading(skip) => (skip..params.len()).map(AdapterArg::Param).collect(),
+        AdapterStrategy::Map(args) => args.to_vec()
+    };
+    if sources.len() != target_params {
+        panic!("adapter {} passes {} arguments to {:?}, which takes {}",
+               name, sources.len(), target, target_params);
+    }
+    let args : Vec<RValue<'a>> = sources.iter().enumerate().map(|(idx, source)| {
+        let value = match *source {
+            AdapterArg::Param(param) => match adapter_params.get(param) {
+                Some(param) => param.to_rvalue(),
+                None => panic!("adapter {} has no parameter {}", name, param)
+            },
+            AdapterArg::Value(value) => value
+        };
+        convert(ctx, loc, value, target.get_param(idx as i32).to_rvalue().get_type())
+    }).collect();
+
+    let block = adapter.new_block("entry");
+    let call = ctx.new_call(loc, target, &args);
+    if returns_void {
+        block.add_eval(loc, call);
+        block.end_with_void_return(loc);
+    } else {
+        block.end_with_return(loc, convert(ctx, loc, call, return_ty));
+    }
+    adapter
+}
+
+fn convert<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                     loc: Option<Location<'a>>,
+                     value: RValue<'a>,
+                     ty: Type<'a>) -> RValue<'a> {
+    if unsafe { types::get_ptr(&value.get_type()) == types::get_ptr(&ty) } {
+        value
+    } else {
+        ctx.new_cast(loc, value, ty)
+    }
+}
@@ -0,0 +1,222 @@
+//! Typed wrappers around GCC's __atomic builtins.
+//!
+//! GCC provides the __atomic builtins in sized variants, such as
+//! __atomic_load_4 for four byte values, whose parameters use unsigned
+//! integer types of that size. The functions in this module pick the
+//! variant that matches the type being operated on and insert the casts
+//! that the builtins need, so that atomic operations can be written in
+//! terms of the types in the program.
+//!
+//! Atomic operations are supported on integer and bool types of one, two,
+//! four, or eight bytes, accessed through pointers built with
+//! Type::make_pointer. Every function returns None if the type of value
+//! that the pointer points to isn't one of those or if its layout isn't
+//! known, and when libgccjit doesn't provide the builtin.
+
+use gccjit_sys::gcc_jit_types::*;
+
+use abi::{self, Scalar};
+use context::Context;
+use function::{self, Function};
+use location::Location;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// The memory orderings that atomic operations can use, with the same
+/// meaning as the orderings of C11 and std::sync::atomic. The values match
+/// the __ATOMIC_* constants that GCC expects.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Ordering {
+    Relaxed,
+    Consume,
+    Acquire,
+    Release,
+    AcqRel,
+    SeqCst
+}
+
+/// The read-modify-write operations provided by the __atomic_fetch_*
+/// builtins.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FetchOp {
+    Add,
+    Sub,
+    And,
+    Xor,
+    Or,
+    Nand
+}
+
+impl FetchOp {
+    fn name(self) -> &'static str {
+        match self {
+            FetchOp::Add => "add",
+            FetchOp::Sub => "sub",
+            FetchOp::And => "and",
+            FetchOp::Xor => "xor",
+            FetchOp::Or => "or",
+            FetchOp::Nand => "nand"
+        }
+    }
+}
+
+/// Atomically loads the value that ptr points to, like __atomic_load_n.
+pub fn load<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                      loc: Option<Location<'a>>,
+                      ptr: RValue<'a>,
+                      ordering: Ordering) -> Option<RValue<'a>> {
+    let (ty, builtin) = resolve(ctx, ptr, "load")?;
+    let args = [cast_param(ctx, loc, builtin, 0, ptr),
+                order(ctx, ordering)];
+    Some(ctx.new_cast(loc, ctx.new_call(loc, builtin, &args), ty))
+}
+
+/// Atomically stores a value to the location that ptr points to, like
+/// __atomic_store_n. The returned call has type void and should be added
+/// to a block with Block::add_eval.
+pub fn store<'a, 'ctx, T: ToRValue<'a>>(ctx: &'a Context<'ctx>,
+                                        loc: Option<Location<'a>>,
+                                        ptr: RValue<'a>,
+                                        value: T,
+                                        ordering: Ordering) -> Option<RValue<'a>> {
+    let (_, builtin) = resolve(ctx, ptr, "store")?;
+    let args = [cast_param(ctx, loc, builtin, 0, ptr),
+                cast_param(ctx, loc, builtin, 1, value.to_rvalue()),
+                order(ctx, ordering)];
+    Some(ctx.new_call(loc, builtin, &args))
+}
+
+/// Atomically replaces the value that ptr points to, like __atomic_exchange_n.
+/// The result is the value that was replaced.
+pub fn exchange<'a, 'ctx, T: ToRValue<'a>>(ctx: &'a Context<'ctx>,
+                                           loc: Option<Location<'a>>,
+                                           ptr: RValue<'a>,
+                                           value: T,
+                                           ordering: Ordering) -> Option<RValue<'a>> {
+    let (ty, builtin) = resolve(ctx, ptr, "exchange")?;
+    let args = [cast_param(ctx, loc, builtin, 0, ptr),
+                cast_param(ctx, loc, builtin, 1, value.to_rvalue()),
+                order(ctx, ordering)];
+    Some(ctx.new_cast(loc, ctx.new_call(loc, builtin, &args), ty))
+}
+
+/// Atomically replaces the value that ptr points to with desired if it
+/// is equal to the value that expected points to, like
+/// __atomic_compare_exchange_n. If they aren't equal, the current value is
+/// written to expected instead. A weak compare and exchange may fail
+/// spuriously. The result is a bool that is true if the value was replaced.
+#[allow(clippy::too_many_arguments)]
+pub fn compare_exchange<'a, 'ctx, T: ToRValue<'a>>(ctx: &'a Context<'ctx>,
+                                                   loc: Option<Location<'a>>,
+                                                   ptr: RValue<'a>,
+                                                   expected: RValue<'a>,
+                                                   desired: T,
+                                                   weak: bool,
+                                                   success: Ordering,
+                                                   failure: Ordering) -> Option<RValue<'a>> {
+    let (_, builtin) = resolve(ctx, ptr, "compare_exchange")?;
+    let bool_ty = ctx.new_type::<bool>();
+    let weak = if weak {
+        ctx.new_rvalue_one(bool_ty)
+    } else {
+        ctx.new_rvalue_zero(bool_ty)
+    };
+    let args = [cast_param(ctx, loc, builtin, 0, ptr),
+                cast_param(ctx, loc, builtin, 1, expected),
+                cast_param(ctx, loc, builtin, 2, desired.to_rvalue()),
+                cast_param(ctx, loc, builtin, 3, weak),
+                order(ctx, success),
+                order(ctx, failure)];
+    Some(ctx.new_call(loc, builtin, &args))
+}
+
+/// Atomically applies an operation to the value that ptr points to, like
+/// the __atomic_fetch_* builtins. The result is the value from before the
+/// operation was applied.
+pub fn fetch<'a, 'ctx, T: ToRValue<'a>>(ctx: &'a Context<'ctx>,
+                                        loc: Option<Location<'a>>,
+                                        op: FetchOp,
+                                        ptr: RValue<'a>,
+                                        value: T,
+                                        ordering: Ordering) -> Option<RValue<'a>> {
+    let (ty, builtin) = resolve(ctx, ptr, &format!("fetch_{}", op.name()))?;
+    let args = [cast_param(ctx, loc, builtin, 0, ptr),
+                cast_param(ctx, loc, builtin, 1, value.to_rvalue()),
+                order(ctx, ordering)];
+    Some(ctx.new_cast(loc, ctx.new_call(loc, builtin, &args), ty))
+}
+
+/// Finds the type that ptr points to and the sized variant of the named
+/// builtin that operates on it.
+fn resolve<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                     ptr: RValue<'a>,
+                     name: &str) -> Option<(Type<'a>, Function<'a>)> {
+    let ptr_ty = unsafe { types::get_ptr(&ptr.get_type()) };
+    let (pointee, size) = tracking::with_registry(|reg| {
+        let pointee = match *reg.type_kind(reg.unqualified(ptr_ty))? {
+            TypeKind::Pointer(pointee) => reg.unqualified(pointee),
+            _ => return None
+        };
+        match *reg.type_kind(pointee)? {
+            // gccjit can't cast between integers and pointers, so atomic
+            // pointers aren't supported.
+            TypeKind::Basic(GCC_JIT_TYPE_VOID_PTR) |
+            TypeKind::Basic(GCC_JIT_TYPE_CONST_CHAR_PTR) |
+            TypeKind::Basic(GCC_JIT_TYPE_FILE_PTR) => None,
+            TypeKind::Basic(ref kind) => match abi::basic_layout(kind)? {
+                (size, _, Scalar::Integer) if matches!(size, 1 | 2 | 4 | 8) => Some((pointee, size)),
+                _ => None
+            },
+            _ => None
+        }
+    })?;
+    let builtin = ctx.get_builtin_function(format!("__atomic_{}_{}", name, size));
+    if unsafe { function::get_ptr(&builtin) }.is_null() {
+        return None;
+    }
+    Some((unsafe { types::from_ptr(pointee) }, builtin))
+}
+
+/// Casts an argument to the type of the given parameter of a builtin.
+fn cast_param<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                        loc: Option<Location<'a>>,
+                        builtin: Function<'a>,
+                        idx: i32,
+                        value: RValue<'a>) -> RValue<'a> {
+    let param_ty = builtin.get_param(idx).to_rvalue().get_type();
+    ctx.new_cast(loc, value, param_ty)
+}
+
+fn order<'a, 'ctx>(ctx: &'a Context<'ctx>, ordering: Ordering) -> RValue<'a> {
+    ctx.new_rvalue_from_int(ctx.new_type::<i32>(), ordering as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::default::Default;
+    use std::mem;
+    use {FunctionType, GlobalKind};
+
+    #[test]
+    fn fetch_add_and_load() {
+        let ctx = Context::default();
+        let int_ty = ctx.new_type::<i32>();
+        let counter = ctx.new_global(None, GlobalKind::Exported, int_ty, "counter");
+        let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[], "bump", false);
+        let block = fun.new_block("entry");
+        let ptr = counter.get_address(None);
+        let one = ctx.new_rvalue_one(int_ty);
+        block.add_eval(None, fetch(&ctx, None, FetchOp::Add, ptr, one, Ordering::SeqCst).unwrap());
+        block.end_with_return(None, load(&ctx, None, ptr, Ordering::Acquire).unwrap());
+
+        let result = ctx.compile();
+        unsafe {
+            let bump : extern "C" fn() -> i32 = mem::transmute(result.get_function("bump"));
+            assert_eq!(bump(), 1);
+            assert_eq!(bump(), 2);
+        }
+    }
+}
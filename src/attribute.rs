@@ -0,0 +1,113 @@
+use std::ffi::CString;
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_fn_attribute::*;
+use gccjit_sys::gcc_jit_variable_attribute::*;
+
+/// The ELF symbol visibilities that can be given to functions and
+/// variables with the visibility attribute.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Visibility {
+    Default,
+    Hidden,
+    Protected,
+    Internal
+}
+
+impl Visibility {
+    fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Default => "default",
+            Visibility::Hidden => "hidden",
+            Visibility::Protected => "protected",
+            Visibility::Internal => "internal"
+        }
+    }
+}
+
+/// Attributes that can be added to a function with Function::add_attribute.
+/// They mirror the GNU C function attributes of the same names. There is no
+/// section attribute, since libgccjit can only place variables in custom
+/// sections (see VarAttribute::Section).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FnAttribute<'a> {
+    /// The function is an alias for the function with the given name.
+    Alias(&'a str),
+    AlwaysInline,
+    Inline,
+    NoInline,
+    /// Compiles the function for a different target, given as in
+    /// "arch=haswell" or "avx2".
+    Target(&'a str),
+    /// The function is emitted even if nothing refers to it.
+    Used,
+    Visibility(Visibility),
+    /// The function is unlikely to be called.
+    Cold,
+    /// The function can return more than once, like setjmp.
+    ReturnsTwice,
+    /// The function has no effects other than its return value, which
+    /// only depends on its arguments and global memory.
+    Pure,
+    /// Like Pure, but the return value only depends on the arguments.
+    Const,
+    Weak,
+    /// The arguments at the given (1-based) positions are never null.
+    NonNull(&'a [i32])
+}
+
+/// Attributes that can be added to a global or local variable with
+/// LValue::add_attribute.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VarAttribute<'a> {
+    Visibility(Visibility),
+    /// Places the variable in the object file section with the given
+    /// name, such as ".data.hot". Only meaningful for globals.
+    Section(&'a str)
+}
+
+pub unsafe fn add_var_attribute(lvalue: *mut gccjit_sys::gcc_jit_lvalue, attribute: VarAttribute) {
+    match attribute {
+        VarAttribute::Visibility(visibility) => {
+            let cstr = CString::new(visibility.as_str()).unwrap();
+            gccjit_sys::gcc_jit_lvalue_add_string_attribute(lvalue,
+                                                            GCC_JIT_VARIABLE_ATTRIBUTE_VISIBILITY,
+                                                            cstr.as_ptr());
+        }
+        VarAttribute::Section(name) => {
+            let cstr = CString::new(name).unwrap();
+            gccjit_sys::gcc_jit_lvalue_set_link_section(lvalue, cstr.as_ptr());
+        }
+    }
+}
+
+pub unsafe fn add_fn_attribute(func: *mut gccjit_sys::gcc_jit_function, attribute: FnAttribute) {
+    let (kind, value) = match attribute {
+        FnAttribute::Alias(name) => (GCC_JIT_FN_ATTRIBUTE_ALIAS, Some(name)),
+        FnAttribute::AlwaysInline => (GCC_JIT_FN_ATTRIBUTE_ALWAYS_INLINE, None),
+        FnAttribute::Inline => (GCC_JIT_FN_ATTRIBUTE_INLINE, None),
+        FnAttribute::NoInline => (GCC_JIT_FN_ATTRIBUTE_NOINLINE, None),
+        FnAttribute::Target(target) => (GCC_JIT_FN_ATTRIBUTE_TARGET, Some(target)),
+        FnAttribute::Used => (GCC_JIT_FN_ATTRIBUTE_USED, None),
+        FnAttribute::Visibility(visibility) => (GCC_JIT_FN_ATTRIBUTE_VISIBILITY, Some(visibility.as_str())),
+        FnAttribute::Cold => (GCC_JIT_FN_ATTRIBUTE_COLD, None),
+        FnAttribute::ReturnsTwice => (GCC_JIT_FN_ATTRIBUTE_RETURNS_TWICE, None),
+        FnAttribute::Pure => (GCC_JIT_FN_ATTRIBUTE_PURE, None),
+        FnAttribute::Const => (GCC_JIT_FN_ATTRIBUTE_CONST, None),
+        FnAttribute::Weak => (GCC_JIT_FN_ATTRIBUTE_WEAK, None),
+        FnAttribute::NonNull(indices) => {
+            gccjit_sys::gcc_jit_function_add_integer_array_attribute(func,
+                                                                     GCC_JIT_FN_ATTRIBUTE_NONNULL,
+                                                                     indices.as_ptr(),
+                                                                     indices.len() as _);
+            return;
+        }
+    };
+    match value {
+        Some(value) => {
+            let cstr = CString::new(value).unwrap();
+            gccjit_sys::gcc_jit_function_add_string_attribute(func, kind, cstr.as_ptr());
+        }
+        None => gccjit_sys::gcc_jit_function_add_attribute(func, kind)
+    }
+}
@@ -0,0 +1,210 @@
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_int, c_long, c_longlong};
+use std::ptr;
+
+use gccjit_sys;
+
+use abi::{self, Scalar};
+use block::BinaryOp;
+use context::Context;
+use function::{self, Function};
+use location::{self, Location};
+use object::{self, ToObject};
+use rvalue::{self, RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// BuiltinBits counts the bits of integers with GCC's __builtin_popcount,
+/// __builtin_clz, and __builtin_ctz families, picking the variant that
+/// operates on the operand's type, such as __builtin_popcountll for 64 bit
+/// integers. It is created by Context::builtin_bits.
+///
+/// The operand can be of any integer type of at most eight bytes, and is
+/// counted as an unsigned value of its own width. Every count is an int,
+/// and is None if the operand isn't of such a type or if libgccjit doesn't
+/// provide the builtin.
+#[derive(Copy, Clone)]
+pub struct BuiltinBits<'ctx> {
+    ctx: &'ctx Context<'ctx>
+}
+
+impl<'ctx> BuiltinBits<'ctx> {
+    /// The number of bits that are set in a value.
+    pub fn popcount<T: ToRValue<'ctx>>(&self, loc: Option<Location<'ctx>>, value: T) -> Option<RValue<'ctx>> {
+        let (value, builtin, _) = self.resolve(loc, "popcount", value.to_rvalue())?;
+        Some(self.ctx.new_call(loc, builtin, &[value]))
+    }
+
+    /// The number of leading zero bits of a value, counting from the most
+    /// significant bit of the operand's type. Like __builtin_clz, the
+    /// result is undefined if the value is zero, so zero should be checked
+    /// for first if it can occur.
+    pub fn clz<T: ToRValue<'ctx>>(&self, loc: Option<Location<'ctx>>, value: T) -> Option<RValue<'ctx>> {
+        let (value, builtin, padding) = self.resolve(loc, "clz", value.to_rvalue())?;
+        let count = self.ctx.new_call(loc, builtin, &[value]);
+        if padding == 0 {
+            return Some(count);
+        }
+        // the operand was widened to the builtin's parameter type, which
+        // adds that many leading zeros.
+        let int_ty = self.ctx.new_type::<i32>();
+        let padding = self.ctx.new_rvalue_from_int(int_ty, padding as i32);
+        Some(self.ctx.new_binary_op(loc, BinaryOp::Minus, int_ty, count, padding))
+    }
+
+    /// The number of trailing zero bits of a value. Like __builtin_ctz, the
+    /// result is undefined if the value is zero.
+    pub fn ctz<T: ToRValue<'ctx>>(&self, loc: Option<Location<'ctx>>, value: T) -> Option<RValue<'ctx>> {
+        let (value, builtin, _) = self.resolve(loc, "ctz", value.to_rvalue())?;
+        Some(self.ctx.new_call(loc, builtin, &[value]))
+    }
+
+    /// Finds the variant of the named builtin for the type of value, and
+    /// converts value to its parameter type. Also returns the number of
+    /// bits that the conversion added.
+    fn resolve(&self,
+               loc: Option<Location<'ctx>>,
+               name: &str,
+               value: RValue<'ctx>) -> Option<(RValue<'ctx>, Function<'ctx>, usize)> {
+        let size = integer_size(value.get_type())?;
+        let suffix = if size <= mem::size_of::<c_int>() {
+            ""
+        } else if size == mem::size_of::<c_long>() {
+            "l"
+        } else if size == mem::size_of::<c_longlong>() {
+            "ll"
+        } else {
+            return None;
+        };
+        let builtin = self.ctx.get_builtin_function(format!("__builtin_{}{}", name, suffix));
+        if unsafe { function::get_ptr(&builtin) }.is_null() {
+            return None;
+        }
+        let param_ty = builtin.get_param(0).to_rvalue().get_type();
+        let param_size = integer_size(param_ty)?;
+        // going through the unsigned type of the operand's width keeps
+        // negative values from being sign extended.
+        let unsigned = self.ctx.new_cast(loc, value, self.unsigned_type(size)?);
+        Some((self.ctx.new_cast(loc, unsigned, param_ty), builtin, 8 * (param_size - size)))
+    }
+
+    fn unsigned_type(&self, size: usize) -> Option<Type<'ctx>> {
+        match size {
+            1 => Some(self.ctx.new_type::<u8>()),
+            2 => Some(self.ctx.new_type::<u16>()),
+            4 => Some(self.ctx.new_type::<u32>()),
+            8 => Some(self.ctx.new_type::<u64>()),
+            _ => None
+        }
+    }
+}
+
+/// Gets the size of an integer type, or None if ty isn't one or its layout
+/// isn't known.
+fn integer_size(ty: Type) -> Option<usize> {
+    let ty = unsafe { types::get_ptr(&ty) };
+    tracking::with_registry(|reg| {
+        match *reg.type_kind(reg.unqualified(ty))? {
+            TypeKind::Basic(ref kind) => match abi::basic_layout(kind)? {
+                (size, _, Scalar::Integer) => Some(size),
+                _ => None
+            },
+            _ => None
+        }
+    })
+}
+
+/// Reverses the bytes of an integer with __builtin_bswap16, 32, or 64, for
+/// RValue::byte_swap.
+pub fn byte_swap<'ctx>(loc: Option<Location<'ctx>>, value: RValue<'ctx>) -> Option<RValue<'ctx>> {
+    let ty = value.get_type();
+    match integer_size(ty)? {
+        1 => Some(value),
+        size @ 2 | size @ 4 | size @ 8 => {
+            let loc_ptr = match loc {
+                Some(loc) => unsafe { location::get_ptr(&loc) },
+                None => ptr::null_mut()
+            };
+            unsafe {
+                let ctx = tracking::context_of(object::get_ptr(&value.to_object()));
+                let name = CString::new(format!("__builtin_bswap{}", 8 * size)).unwrap();
+                let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, name.as_ptr());
+                if builtin.is_null() {
+                    return None;
+                }
+                // the parameter is unsigned and of the same size, so the
+                // cast keeps every bit of signed values.
+                let param = gccjit_sys::gcc_jit_param_as_rvalue(gccjit_sys::gcc_jit_function_get_param(builtin, 0));
+                let mut arg = gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(&value),
+                                                                   gccjit_sys::gcc_jit_rvalue_get_type(param));
+                let call = gccjit_sys::gcc_jit_context_new_call(ctx, loc_ptr, builtin, 1, &mut arg);
+                Some(rvalue::from_ptr(gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, call, types::get_ptr(&ty))))
+            }
+        },
+        _ => None
+    }
+}
+
+pub fn new<'ctx>(ctx: &'ctx Context<'ctx>) -> BuiltinBits<'ctx> {
+    BuiltinBits { ctx }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::default::Default;
+
+    use context::Context;
+    use function::FunctionType;
+    use rvalue::ToRValue;
+
+    #[test]
+    fn counts_match_rust() {
+        let ctx = Context::default();
+        let bits = ctx.builtin_bits();
+        let int_ty = ctx.new_type::<i32>();
+        let byte_ty = ctx.new_type::<i8>();
+        let long_ty = ctx.new_type::<u64>();
+        let x = ctx.new_parameter(None, byte_ty, "x");
+        let z = ctx.new_parameter(None, byte_ty, "z");
+        let y = ctx.new_parameter(None, long_ty, "y");
+        let clz8 = ctx.new_function(None, FunctionType::Exported, int_ty, &[x], "clz8", false);
+        clz8.new_block("entry").end_with_return(None, bits.clz(None, x).unwrap());
+        let popcount8 = ctx.new_function(None, FunctionType::Exported, int_ty, &[z], "popcount8", false);
+        popcount8.new_block("entry").end_with_return(None, bits.popcount(None, z).unwrap());
+        let ctz64 = ctx.new_function(None, FunctionType::Exported, int_ty, &[y], "ctz64", false);
+        ctz64.new_block("entry").end_with_return(None, bits.ctz(None, y).unwrap());
+        assert!(bits.popcount(None, ctx.new_rvalue_zero(ctx.new_type::<f64>())).is_none());
+
+        let result = ctx.compile();
+        unsafe {
+            let clz8 = result.function::<extern "C" fn(i8) -> i32, _>("clz8").unwrap();
+            let popcount8 = result.function::<extern "C" fn(i8) -> i32, _>("popcount8").unwrap();
+            let ctz64 = result.function::<extern "C" fn(u64) -> i32, _>("ctz64").unwrap();
+            assert_eq!(clz8(1), 7);
+            assert_eq!(popcount8(-1), 8);
+            assert_eq!(ctz64(1 << 40), 40);
+        }
+    }
+
+    #[test]
+    fn byte_swap_reverses_bytes() {
+        let ctx = Context::default();
+        let short_ty = ctx.new_type::<i16>();
+        let long_ty = ctx.new_type::<u64>();
+        let x = ctx.new_parameter(None, short_ty, "x");
+        let y = ctx.new_parameter(None, long_ty, "y");
+        let swap16 = ctx.new_function(None, FunctionType::Exported, short_ty, &[x], "swap16", false);
+        swap16.new_block("entry").end_with_return(None, x.to_rvalue().byte_swap(None).unwrap());
+        let swap64 = ctx.new_function(None, FunctionType::Exported, long_ty, &[y], "swap64", false);
+        swap64.new_block("entry").end_with_return(None, y.to_rvalue().byte_swap(None).unwrap());
+
+        let result = ctx.compile();
+        unsafe {
+            let swap16 = result.function::<extern "C" fn(i16) -> i16, _>("swap16").unwrap();
+            let swap64 = result.function::<extern "C" fn(u64) -> u64, _>("swap64").unwrap();
+            assert_eq!(swap16(0x12fe), 0x12feu16.swap_bytes() as i16);
+            assert_eq!(swap64(0x0102030405060708), 0x0807060504030201);
+        }
+    }
+}
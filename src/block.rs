@@ -8,12 +8,16 @@ use gccjit_sys;
 use object::{self, ToObject, Object};
 use function::{self, Function};
 use location::{self, Location};
-use rvalue::{self, ToRValue};
-use lvalue::{self, ToLValue};
+use rvalue::{self, RValue, ToRValue};
+use lvalue::{self, LValue, ToLValue};
+use checked::{self, CheckedOp};
+use case::{self, Case};
+use tracking::{self, Statement};
 
 /// BinaryOp is a enum representing the various binary operations
 /// that gccjit knows how to codegen.
 #[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum BinaryOp {
     Plus,
     Minus,
@@ -32,6 +36,7 @@ pub enum BinaryOp {
 /// UnaryOp is an enum representing the various unary operations
 /// that gccjit knows how to codegen.
 #[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum UnaryOp {
     Minus,
     BitwiseNegate,
@@ -42,6 +47,7 @@ pub enum UnaryOp {
 /// ComparisonOp is an enum representing the various comparisons that
 /// gccjit is capable of doing.
 #[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum ComparisonOp {
     Equals,
     NotEquals,
@@ -51,6 +57,28 @@ pub enum ComparisonOp {
     GreaterThanEquals
 }
 
+/// Whether prefetched memory is going to be read or written, the second
+/// argument of __builtin_prefetch.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PrefetchAccess {
+    Read,
+    Write
+}
+
+/// How long prefetched memory should stay in the cache, from None, for
+/// memory that is only accessed once, to High, for memory that should be
+/// kept in every level of the cache. This is the third argument of
+/// __builtin_prefetch.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PrefetchLocality {
+    None,
+    Low,
+    Moderate,
+    High
+}
+
 /// Block represents a basic block in gccjit. Blocks are created by functions.
 /// A basic block consists of a series of instructions terminated by a terminator
 /// instruction, which can be either a jump to one block, a conditional branch to
@@ -85,20 +113,71 @@ impl<'ctx> Block<'ctx> {
         }
     }
 
+    /// Sets the location that statements added to this block without an
+    /// explicit location are given, or stops giving them one if loc is
+    /// None. Statements that are given a location keep it.
+    pub fn set_default_location(&self, loc: Option<Location<'ctx>>) {
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        let block = self.ptr;
+        tracking::with_registry(|reg| {
+            if let Some(info) = reg.block_mut(block) {
+                info.default_location = loc_ptr;
+            }
+        });
+    }
+
+    /// The location set with set_default_location, if any.
+    pub fn default_location(&self) -> Option<Location<'ctx>> {
+        let block = self.ptr;
+        let loc_ptr = tracking::with_registry(|reg| {
+            reg.block(block).map_or(ptr::null_mut(), |info| info.default_location)
+        });
+        if loc_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { location::from_ptr(loc_ptr) })
+        }
+    }
+
+    /// Panics in debug builds if obj was created by a context that this
+    /// block's context can't refer to.
+    fn check_provenance<O: ToObject<'ctx>>(&self, obj: &O) {
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            tracking::check_provenance(ctx, object::get_ptr(&obj.to_object()));
+        }
+    }
+
+    /// The location to give a statement: loc if there is one, and the
+    /// default location of the block otherwise.
+    fn location_ptr(&self, loc: Option<Location<'ctx>>) -> *mut gccjit_sys::gcc_jit_location {
+        match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => {
+                let block = self.ptr;
+                tracking::with_registry(|reg| {
+                    reg.block(block).map_or(ptr::null_mut(), |info| info.default_location)
+                })
+            }
+        }
+    }
+
     /// Evaluates the rvalue parameter and discards its result. Equivalent
     /// to (void)<expr> in C.
     pub fn add_eval<T: ToRValue<'ctx>>(&self,
                                        loc: Option<Location<'ctx>>,
                                        value: T) {
         let rvalue = value.to_rvalue();
-        let loc_ptr = match loc {
-                Some(loc) => unsafe { location::get_ptr(&loc) },
-                None => ptr::null_mut()
-            };
+        self.check_provenance(&rvalue);
+        let loc_ptr = self.location_ptr(loc);
         unsafe {
             gccjit_sys::gcc_jit_block_add_eval(self.ptr,
                                                loc_ptr,
                                                rvalue::get_ptr(&rvalue));
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Eval(rvalue::get_ptr(&rvalue)));
         }
     }
 
@@ -110,15 +189,17 @@ impl<'ctx> Block<'ctx> {
                                                                 value: R) {
         let lvalue = assign_target.to_lvalue();
         let rvalue = value.to_rvalue();
-        let loc_ptr = match loc {
-                Some(loc) => unsafe { location::get_ptr(&loc) },
-                None => ptr::null_mut()
-            };
+        self.check_provenance(&lvalue);
+        self.check_provenance(&rvalue);
+        let loc_ptr = self.location_ptr(loc);
         unsafe {
             gccjit_sys::gcc_jit_block_add_assignment(self.ptr,
                                                      loc_ptr,
                                                      lvalue::get_ptr(&lvalue),
                                                      rvalue::get_ptr(&rvalue));
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::Assignment(lvalue::get_ptr(&lvalue),
+                                                             rvalue::get_ptr(&rvalue)));
         }
     }
 
@@ -132,16 +213,19 @@ impl<'ctx> Block<'ctx> {
                                                                    value: R) {
         let lvalue = assign_target.to_lvalue();
         let rvalue = value.to_rvalue();
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        self.check_provenance(&lvalue);
+        self.check_provenance(&rvalue);
+        let loc_ptr = self.location_ptr(loc);
         unsafe {
             gccjit_sys::gcc_jit_block_add_assignment_op(self.ptr,
                                                         loc_ptr,
                                                         lvalue::get_ptr(&lvalue),
                                                         mem::transmute(op),
                                                         rvalue::get_ptr(&rvalue));
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::AssignmentOp(lvalue::get_ptr(&lvalue),
+                                                               op,
+                                                               rvalue::get_ptr(&rvalue)));
         }
     }
 
@@ -151,15 +235,44 @@ impl<'ctx> Block<'ctx> {
                        loc: Option<Location<'ctx>>,
                        message: S) {
         let message_ref = message.as_ref();
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        let loc_ptr = self.location_ptr(loc);
         unsafe {
             let cstr = CString::new(message_ref).unwrap();
             gccjit_sys::gcc_jit_block_add_comment(self.ptr,
                                                   loc_ptr,
                                                   cstr.as_ptr());
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Comment(message_ref.to_owned()));
+        }
+    }
+
+    /// Adds a comment that names a frontend construct, such as a loop or
+    /// an if statement, that this block begins, if trace comments were
+    /// enabled with Context::set_trace_comments. Otherwise this does
+    /// nothing.
+    pub fn add_trace<S: AsRef<str>>(&self,
+                                    loc: Option<Location<'ctx>>,
+                                    construct: S) {
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || construct.as_ref().to_owned());
+    }
+
+    /// Adds a trace comment produced by describe, followed by the location
+    /// it refers to, if trace comments are enabled.
+    fn trace<F: FnOnce() -> String>(&self,
+                                    loc_ptr: *mut gccjit_sys::gcc_jit_location,
+                                    describe: F) {
+        let ctx = tracking::context_of(unsafe { object::get_ptr(&self.to_object()) });
+        if !tracking::with_context(ctx, |info| info.config.trace_comments) {
+            return;
+        }
+        let mut message = describe();
+        if !loc_ptr.is_null() {
+            message.push_str(&format!(" at {:?}", unsafe { location::from_ptr(loc_ptr) }));
+        }
+        unsafe {
+            let cstr = CString::new(message.clone()).unwrap();
+            gccjit_sys::gcc_jit_block_add_comment(self.ptr, loc_ptr, cstr.as_ptr());
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Comment(message));
         }
     }
 
@@ -171,31 +284,103 @@ impl<'ctx> Block<'ctx> {
                                 on_true: Block<'ctx>,
                                 on_false: Block<'ctx>) {
         let cond_rvalue = cond.to_rvalue();
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        self.check_provenance(&cond_rvalue);
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || format!("if ({:?}) goto {:?}; else goto {:?};", cond_rvalue, on_true, on_false));
         unsafe {
             gccjit_sys::gcc_jit_block_end_with_conditional(self.ptr,
                                                            loc_ptr,
                                                            rvalue::get_ptr(&cond_rvalue),
                                                            on_true.ptr,
                                                            on_false.ptr);
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::Conditional(rvalue::get_ptr(&cond_rvalue),
+                                                              on_true.ptr,
+                                                              on_false.ptr));
         }
     }
 
+    /// Terminates a block like end_with_conditional, telling GCC that cond
+    /// is expected to be true so that it lays out on_true as the fast path.
+    pub fn end_with_conditional_likely<T: ToRValue<'ctx>>(&self,
+                                                          loc: Option<Location<'ctx>>,
+                                                          cond: T,
+                                                          on_true: Block<'ctx>,
+                                                          on_false: Block<'ctx>) {
+        self.end_with_conditional(loc, expect(loc, cond.to_rvalue(), true), on_true, on_false);
+    }
+
+    /// Terminates a block by jumping to the block of the case whose range
+    /// contains the value of expr, or to default_block if no case does.
+    /// This is equivalent to C's switch statement. expr must have an
+    /// integer type.
+    pub fn end_with_switch<T: ToRValue<'ctx>>(&self,
+                                              loc: Option<Location<'ctx>>,
+                                              expr: T,
+                                              default_block: Block<'ctx>,
+                                              cases: &[Case<'ctx>]) {
+        let expr_rvalue = expr.to_rvalue();
+        self.check_provenance(&expr_rvalue);
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || {
+            let cases : Vec<_> = cases.iter().map(|case| format!("{:?}", case)).collect();
+            format!("switch ({:?}) {{ {} default: goto {:?}; }}", expr_rvalue, cases.join(" "), default_block)
+        });
+        let mut case_ptrs : Vec<_> = cases.iter()
+            .map(|case| unsafe { case::get_ptr(case) })
+            .collect();
+        let case_infos = cases.iter().map(case::get_info).collect();
+        unsafe {
+            gccjit_sys::gcc_jit_block_end_with_switch(self.ptr,
+                                                      loc_ptr,
+                                                      rvalue::get_ptr(&expr_rvalue),
+                                                      default_block.ptr,
+                                                      case_ptrs.len() as i32,
+                                                      case_ptrs.as_mut_ptr());
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::Switch(rvalue::get_ptr(&expr_rvalue),
+                                                         default_block.ptr,
+                                                         case_infos));
+        }
+    }
+
+    /// Terminates a block with a call to __builtin_trap, which aborts the
+    /// program, for paths that should fail loudly if they are ever taken,
+    /// such as failed bounds checks. Unlike end_with_return, no value of
+    /// the function's return type is needed.
+    pub fn end_with_trap(&self, loc: Option<Location<'ctx>>) {
+        self.end_with_noreturn_builtin(loc, "__builtin_trap");
+    }
+
+    /// Terminates a block with a call to __builtin_unreachable, which tells
+    /// GCC that the block can never be reached, such as after a switch
+    /// that covers every possible value. GCC may optimize assuming it is
+    /// never reached, so behavior is undefined if it is.
+    pub fn end_with_unreachable(&self, loc: Option<Location<'ctx>>) {
+        self.end_with_noreturn_builtin(loc, "__builtin_unreachable");
+    }
+
+    /// Terminates a block with a call to a function that doesn't return,
+    /// such as longjmp or exit. libgccjit needs every block to end with a
+    /// terminator even after such a call, so the block jumps to itself,
+    /// which GCC removes as dead code. If the call does return, the
+    /// program loops forever.
+    pub fn end_with_noreturn_call<T: ToRValue<'ctx>>(&self, loc: Option<Location<'ctx>>, call: T) {
+        self.add_eval(loc, call);
+        self.end_with_jump(loc, *self);
+    }
+
     /// Terminates a block by unconditionally jumping to another block.
     pub fn end_with_jump(&self,
                          loc: Option<Location<'ctx>>,
                          target: Block<'ctx>) {
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || format!("goto {:?};", target));
         unsafe {
             gccjit_sys::gcc_jit_block_end_with_jump(self.ptr,
                                                     loc_ptr,
                                                     target.ptr);
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Jump(target.ptr));
         }
     }
 
@@ -207,14 +392,15 @@ impl<'ctx> Block<'ctx> {
                                               loc: Option<Location<'ctx>>,
                                               ret: T) {
         let ret_rvalue = ret.to_rvalue();
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        self.check_provenance(&ret_rvalue);
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || format!("return {:?};", ret_rvalue));
         unsafe {
             gccjit_sys::gcc_jit_block_end_with_return(self.ptr,
                                                       loc_ptr,
                                                       rvalue::get_ptr(&ret_rvalue));
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::Return(rvalue::get_ptr(&ret_rvalue)));
         }
     }
 
@@ -223,13 +409,181 @@ impl<'ctx> Block<'ctx> {
     /// This function can only be used to terminate a block within a function
     /// that returns void.
     pub fn end_with_void_return(&self, loc: Option<Location<'ctx>>) {
-        let loc_ptr = match loc {
-            Some(loc) => unsafe { location::get_ptr(&loc) },
-            None => ptr::null_mut()
-        };
+        let loc_ptr = self.location_ptr(loc);
+        self.trace(loc_ptr, || "return;".to_owned());
         unsafe {
             gccjit_sys::gcc_jit_block_end_with_void_return(self.ptr,
                                                            loc_ptr);
+            tracking::record_statement(self.ptr, loc_ptr, Statement::VoidReturn);
+        }
+    }
+
+    /// Adds a try/catch statement that runs the statements of try_block,
+    /// and those of catch_block if an exception is thrown while running
+    /// them. The function must have a personality function, set with
+    /// Function::set_personality_function, to catch exceptions.
+    #[cfg(feature = "master")]
+    pub fn add_try_catch(&self,
+                         loc: Option<Location<'ctx>>,
+                         try_block: Block<'ctx>,
+                         catch_block: Block<'ctx>) {
+        let loc_ptr = self.location_ptr(loc);
+        unsafe {
+            gccjit_sys::gcc_jit_block_add_try_catch(self.ptr, loc_ptr, try_block.ptr, catch_block.ptr);
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::TryCatch(try_block.ptr, catch_block.ptr));
+        }
+    }
+
+    /// Adds a try/finally statement that runs the statements of try_block
+    /// and then those of finally_block, whether try_block finishes normally
+    /// or by unwinding. This is what defer or finally in a source language
+    /// lowers to.
+    #[cfg(feature = "master")]
+    pub fn add_try_finally(&self,
+                           loc: Option<Location<'ctx>>,
+                           try_block: Block<'ctx>,
+                           finally_block: Block<'ctx>) {
+        let loc_ptr = self.location_ptr(loc);
+        unsafe {
+            gccjit_sys::gcc_jit_block_add_try_finally(self.ptr, loc_ptr, try_block.ptr, finally_block.ptr);
+            tracking::record_statement(self.ptr, loc_ptr,
+                                       Statement::TryFinally(try_block.ptr, finally_block.ptr));
+        }
+    }
+
+    /// Fills size bytes starting at dest with the given byte value, like
+    /// C's memset. dest can be a pointer of any type and value and size can
+    /// be of any integer type; they are cast to the types that
+    /// __builtin_memset expects.
+    pub fn add_memset<D: ToRValue<'ctx>, V: ToRValue<'ctx>, S: ToRValue<'ctx>>(&self,
+                                                                                loc: Option<Location<'ctx>>,
+                                                                                dest: D,
+                                                                                value: V,
+                                                                                size: S) {
+        self.add_builtin_call(loc, "__builtin_memset",
+                              &[dest.to_rvalue(), value.to_rvalue(), size.to_rvalue()]);
+    }
+
+    /// Copies size bytes from src to dest, like C's memcpy. The two regions
+    /// must not overlap.
+    pub fn add_memcpy<D: ToRValue<'ctx>, R: ToRValue<'ctx>, S: ToRValue<'ctx>>(&self,
+                                                                                loc: Option<Location<'ctx>>,
+                                                                                dest: D,
+                                                                                src: R,
+                                                                                size: S) {
+        self.add_builtin_call(loc, "__builtin_memcpy",
+                              &[dest.to_rvalue(), src.to_rvalue(), size.to_rvalue()]);
+    }
+
+    /// Copies size bytes from src to dest, like C's memmove. Unlike
+    /// add_memcpy, the two regions may overlap.
+    pub fn add_memmove<D: ToRValue<'ctx>, R: ToRValue<'ctx>, S: ToRValue<'ctx>>(&self,
+                                                                                 loc: Option<Location<'ctx>>,
+                                                                                 dest: D,
+                                                                                 src: R,
+                                                                                 size: S) {
+        self.add_builtin_call(loc, "__builtin_memmove",
+                              &[dest.to_rvalue(), src.to_rvalue(), size.to_rvalue()]);
+    }
+
+    /// Hints that the memory at addr is about to be accessed, so that it
+    /// can be fetched into the cache ahead of time, like __builtin_prefetch.
+    /// addr can be a pointer of any type, and doesn't need to be valid: a
+    /// prefetch never faults.
+    pub fn add_prefetch<A: ToRValue<'ctx>>(&self,
+                                           loc: Option<Location<'ctx>>,
+                                           addr: A,
+                                           access: PrefetchAccess,
+                                           locality: PrefetchLocality) {
+        let addr = addr.to_rvalue();
+        self.check_provenance(&addr);
+        let loc_ptr = self.location_ptr(loc);
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let name = CString::new("__builtin_prefetch").unwrap();
+            let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, name.as_ptr());
+            // only the address is a declared parameter; the rest are
+            // variadic and must be integer constants.
+            let param = gccjit_sys::gcc_jit_param_as_rvalue(gccjit_sys::gcc_jit_function_get_param(builtin, 0));
+            let int_ty = gccjit_sys::gcc_jit_context_get_type(ctx, gccjit_sys::gcc_jit_types::GCC_JIT_TYPE_INT);
+            let mut args = [gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(&addr),
+                                                                 gccjit_sys::gcc_jit_rvalue_get_type(param)),
+                            gccjit_sys::gcc_jit_context_new_rvalue_from_int(ctx, int_ty, access as i32),
+                            gccjit_sys::gcc_jit_context_new_rvalue_from_int(ctx, int_ty, locality as i32)];
+            let call = gccjit_sys::gcc_jit_context_new_call(ctx, loc_ptr, builtin, args.len() as i32, args.as_mut_ptr());
+            gccjit_sys::gcc_jit_block_add_eval(self.ptr, loc_ptr, call);
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Eval(call));
+        }
+    }
+
+    /// Computes a + b into dest, and returns a bool that is true if the
+    /// result overflowed dest's integer type, in which case dest holds the
+    /// result wrapped around. a and b are converted to the type of dest.
+    ///
+    /// # Panics
+    /// Panics if dest isn't of an integer type of at most 64 bits.
+    pub fn add_checked_add<A: ToRValue<'ctx>, B: ToRValue<'ctx>>(&self,
+                                                                 loc: Option<Location<'ctx>>,
+                                                                 dest: LValue<'ctx>,
+                                                                 a: A,
+                                                                 b: B) -> RValue<'ctx> {
+        checked::add_checked(*self, loc, CheckedOp::Add, dest, a.to_rvalue(), b.to_rvalue())
+    }
+
+    /// Computes a - b into dest like add_checked_add, and returns whether
+    /// the result overflowed.
+    pub fn add_checked_sub<A: ToRValue<'ctx>, B: ToRValue<'ctx>>(&self,
+                                                                 loc: Option<Location<'ctx>>,
+                                                                 dest: LValue<'ctx>,
+                                                                 a: A,
+                                                                 b: B) -> RValue<'ctx> {
+        checked::add_checked(*self, loc, CheckedOp::Sub, dest, a.to_rvalue(), b.to_rvalue())
+    }
+
+    /// Computes a * b into dest like add_checked_add, and returns whether
+    /// the result overflowed.
+    pub fn add_checked_mul<A: ToRValue<'ctx>, B: ToRValue<'ctx>>(&self,
+                                                                 loc: Option<Location<'ctx>>,
+                                                                 dest: LValue<'ctx>,
+                                                                 a: A,
+                                                                 b: B) -> RValue<'ctx> {
+        checked::add_checked(*self, loc, CheckedOp::Mul, dest, a.to_rvalue(), b.to_rvalue())
+    }
+
+    /// Calls a builtin that doesn't return and terminates the block, like
+    /// end_with_noreturn_call.
+    fn end_with_noreturn_builtin(&self, loc: Option<Location<'ctx>>, name: &str) {
+        self.add_builtin_call(loc, name, &[]);
+        self.end_with_jump(loc, *self);
+    }
+
+    /// Evaluates a call to a builtin, casting every argument to the type
+    /// of the corresponding parameter.
+    fn add_builtin_call(&self,
+                        loc: Option<Location<'ctx>>,
+                        name: &str,
+                        args: &[rvalue::RValue<'ctx>]) {
+        let loc_ptr = self.location_ptr(loc);
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let cname = CString::new(name).unwrap();
+            let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, cname.as_ptr());
+            let mut arg_ptrs : Vec<_> = args.iter()
+                .enumerate()
+                .map(|(idx, arg)| {
+                    let param = gccjit_sys::gcc_jit_function_get_param(builtin, idx as i32);
+                    let param_ty = gccjit_sys::gcc_jit_rvalue_get_type(gccjit_sys::gcc_jit_param_as_rvalue(param));
+                    gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(arg), param_ty)
+                })
+                .collect();
+            let call = gccjit_sys::gcc_jit_context_new_call(ctx,
+                                                            loc_ptr,
+                                                            builtin,
+                                                            arg_ptrs.len() as i32,
+                                                            arg_ptrs.as_mut_ptr());
+            gccjit_sys::gcc_jit_block_add_eval(self.ptr, loc_ptr, call);
+            tracking::record_statement(self.ptr, loc_ptr, Statement::Eval(call));
         }
     }
 }
@@ -240,3 +594,30 @@ pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_block) -> Block<'ctx>
         ptr: ptr
     }
 }
+
+pub unsafe fn get_ptr<'ctx>(block: &Block<'ctx>) -> *mut gccjit_sys::gcc_jit_block {
+    block.ptr
+}
+
+/// Wraps cond in a call to __builtin_expect, for Context::expect. The
+/// result has the type of cond.
+pub fn expect<'ctx>(loc: Option<Location<'ctx>>, cond: RValue<'ctx>, likely: bool) -> RValue<'ctx> {
+    let loc_ptr = match loc {
+        Some(loc) => unsafe { location::get_ptr(&loc) },
+        None => ptr::null_mut()
+    };
+    unsafe {
+        let ctx = tracking::context_of(object::get_ptr(&cond.to_object()));
+        let name = CString::new("__builtin_expect").unwrap();
+        let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, name.as_ptr());
+        let param_ty = |idx| {
+            let param = gccjit_sys::gcc_jit_function_get_param(builtin, idx);
+            gccjit_sys::gcc_jit_rvalue_get_type(gccjit_sys::gcc_jit_param_as_rvalue(param))
+        };
+        let mut args = [gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(&cond), param_ty(0)),
+                        gccjit_sys::gcc_jit_context_new_rvalue_from_int(ctx, param_ty(1), likely as i32)];
+        let call = gccjit_sys::gcc_jit_context_new_call(ctx, loc_ptr, builtin, args.len() as i32, args.as_mut_ptr());
+        let cond_ty = gccjit_sys::gcc_jit_rvalue_get_type(rvalue::get_ptr(&cond));
+        rvalue::from_ptr(gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, call, cond_ty))
+    }
+}
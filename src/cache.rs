@@ -0,0 +1,196 @@
+//! An on-disk cache of compiled shared libraries, so that compiling the
+//! same code again, as expression JITs often do, loads the library that
+//! was compiled the first time instead of running gcc again.
+//!
+//! Libraries are keyed by a hash of the context's configuration and of the
+//! reproducer that libgccjit writes for it with
+//! Context::dump_reproducer_to_file. The reproducer replays every call
+//! made into libgccjit, so everything that ends up in the library, such as
+//! function attributes, the initializers of globals, and the exact values
+//! of constants, is part of the key. Host addresses, such as of imported
+//! functions, are too, so code that embeds them is only reused by
+//! processes where they are the same. Contexts with closures imported with
+//! Context::import_closure can't be cached at all, since the library would
+//! call into the process that compiled it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use context::{Context, OutputKind};
+use dylib::{self, LoadedLibrary};
+
+/// Computes the cache key of a context. Fails with
+/// io::ErrorKind::InvalidInput if the context or one of its parents has
+/// imported a closure.
+pub fn key(ctx: &Context) -> io::Result<u64> {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    if ctx.has_closures() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "contexts with imported closures can't be cached"));
+    }
+    let path = ::std::env::temp_dir().join(format!("gccjit-cache-key-{}-{}.c",
+                                                   process::id(),
+                                                   NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    ctx.dump_reproducer_to_file(path.to_string_lossy());
+    let reproducer = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    let canonical = format!("gccjit {}\n{:?}\n{}",
+                            env!("CARGO_PKG_VERSION"), ctx.config(), normalize_identifiers(&reproducer?));
+    // FNV-1a, which unlike the hashers of std is stable between releases
+    // of Rust, so that the cache stays valid across toolchain upgrades.
+    let hash = canonical.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    Ok(hash)
+}
+
+/// Replaces the addresses that libgccjit puts in the names of the
+/// reproducer's variables, as in rvalue_0x55d0c3a1b2c0, with numbers in
+/// the order they first appear, so that the same code gets the same key
+/// wherever its objects were allocated. Addresses that are values, as in
+/// (void *)0x7f0e10, aren't part of a name and are kept, as is everything
+/// inside string literals.
+fn normalize_identifiers(reproducer: &str) -> String {
+    let bytes = reproducer.as_bytes();
+    let mut out = String::with_capacity(reproducer.len());
+    let mut seen : Vec<&str> = vec![];
+    let (mut in_string, mut copied, mut idx) = (false, 0, 0);
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' if in_string => idx += 1,
+            b'"' => in_string = !in_string,
+            b'_' if !in_string && reproducer[idx + 1..].starts_with("0x") => {
+                let start = idx + 1;
+                let end = reproducer[start + 2..].find(|c: char| !c.is_ascii_hexdigit())
+                    .map_or(bytes.len(), |len| start + 2 + len);
+                let address = &reproducer[start..end];
+                let number = match seen.iter().position(|&seen| seen == address) {
+                    Some(number) => number,
+                    None => {
+                        seen.push(address);
+                        seen.len() - 1
+                    }
+                };
+                out.push_str(&reproducer[copied..start]);
+                out.push_str(&format!("obj{}", number));
+                copied = end;
+                idx = end;
+                continue;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    out.push_str(&reproducer[copied..]);
+    out
+}
+
+/// Gets the path the library of a context is cached at in a directory.
+pub fn cached_path<P: AsRef<Path>>(ctx: &Context, dir: P) -> io::Result<PathBuf> {
+    Ok(dir.as_ref().join(format!("{:016x}.so", key(ctx)?)))
+}
+
+/// Loads the cached library of a context from the given directory, or
+/// compiles the context and adds its library to the cache if there is
+/// none or it can't be loaded. Fails like key for contexts with imported
+/// closures.
+pub fn load_or_compile<P: AsRef<Path>>(ctx: &Context, dir: P) -> io::Result<LoadedLibrary> {
+    let dir = dir.as_ref();
+    let path = cached_path(ctx, dir)?;
+    if path.exists() {
+        if let Ok(library) = dylib::load(&path, false) {
+            return Ok(library);
+        }
+    }
+    fs::create_dir_all(dir)?;
+    // the library is written under a name of its own and renamed into
+    // place, so that other processes never load a partly written library.
+    let partial = dir.join(format!("{}.{}.partial", path.file_name().unwrap().to_string_lossy(), process::id()));
+    ctx.compile_to_file(OutputKind::DynamicLibrary, partial.to_string_lossy());
+    if let Err(err) = fs::rename(&partial, &path) {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+    dylib::load(&path, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use attribute::FnAttribute;
+    use function::FunctionType;
+
+    fn build(ctx: &Context, value: i32) {
+        let int_ty = ctx.new_type::<i32>();
+        let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[], "cached", false);
+        fun.new_block("entry").end_with_return(None, ctx.new_rvalue_from_int(int_ty, value));
+    }
+
+    #[test]
+    fn identical_code_shares_a_library() {
+        let dir = ::std::env::temp_dir().join("gccjit_rs_cache");
+        let (first, second, other) = (Context::default(), Context::default(), Context::default());
+        build(&first, 1);
+        build(&second, 1);
+        build(&other, 2);
+        assert_eq!(key(&first).unwrap(), key(&second).unwrap());
+        assert_ne!(key(&first).unwrap(), key(&other).unwrap());
+
+        let library = load_or_compile(&first, &dir).unwrap();
+        assert!(cached_path(&second, &dir).unwrap().exists());
+        let reloaded = load_or_compile(&second, &dir).unwrap();
+        let cached = unsafe { reloaded.function::<extern "C" fn() -> i32>("cached") }.unwrap();
+        assert_eq!((*cached)(), 1);
+        drop((library, reloaded));
+        let _ = fs::remove_file(cached_path(&first, &dir).unwrap());
+    }
+
+    #[test]
+    fn attributes_are_part_of_the_key() {
+        let (plain, cold) = (Context::default(), Context::default());
+        for &(ctx, is_cold) in &[(&plain, false), (&cold, true)] {
+            build(ctx, 1);
+            let fun = ctx.new_function(None, FunctionType::Exported, ctx.new_type::<()>(), &[], "rarely", false);
+            if is_cold {
+                fun.add_attribute(FnAttribute::Cold);
+            }
+            fun.new_block("entry").end_with_void_return(None);
+        }
+        assert_ne!(key(&plain).unwrap(), key(&cold).unwrap());
+    }
+
+    #[test]
+    fn byte_strings_keep_the_key() {
+        let (first, second) = (Context::default(), Context::default());
+        for ctx in &[&first, &second] {
+            let char_ptr_ty = ctx.new_type::<char>().make_const().make_pointer();
+            let fun = ctx.new_function(None, FunctionType::Exported, char_ptr_ty, &[], "bytes", false);
+            fun.new_block("entry").end_with_return(None, ctx.new_byte_string_literal(b"a\0b"));
+        }
+        assert_eq!(key(&first).unwrap(), key(&second).unwrap());
+    }
+
+    #[test]
+    fn imported_closures_are_rejected() {
+        let dir = ::std::env::temp_dir().join("gccjit_rs_cache");
+        let ctx = Context::default();
+        build(&ctx, 1);
+        let get = ctx.import_closure("get", Box::new(|| 1) as Box<dyn Fn() -> i32>);
+        let fun = ctx.new_function(None, FunctionType::Exported, ctx.new_type::<i32>(), &[], "call_get", false);
+        fun.new_block("entry").end_with_return(None, ctx.new_call(None, get, &[]));
+        assert_eq!(key(&ctx).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(load_or_compile(&ctx, &dir).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let child = ctx.new_child_context();
+        assert_eq!(key(&child).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn addresses_in_names_are_numbered() {
+        let reproducer = "rvalue_0x55d0: f (rvalue_0x41, block_0x55d0, (void *)0x7f00, \"a\\\"_0x1\")";
+        assert_eq!(normalize_identifiers(reproducer),
+                   "rvalue_obj0: f (rvalue_obj1, block_obj0, (void *)0x7f00, \"a\\\"_0x1\")");
+    }
+}
@@ -0,0 +1,182 @@
+//! Calls to jitted functions whose signatures are only known at runtime.
+//!
+//! On the SysV x86-64 and AAPCS64 calling conventions, integer and floating
+//! point arguments are assigned to two separate sequences of registers, so
+//! any function whose arguments all fit in registers can be called through
+//! a single function pointer type that takes every integer register
+//! followed by every floating point register. Registers that the callee
+//! doesn't use are simply ignored. Windows x64 assigns registers by
+//! argument position instead, so it isn't supported.
+
+use std::error::Error;
+use std::fmt;
+use std::mem;
+
+use abi::Convention;
+use context::CompileResult;
+use value::JitValue;
+
+/// The C types that parameters and return values of functions called
+/// through CompileResult::call can have.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CallType {
+    /// No value; only valid as a return type.
+    Void,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    Bool,
+    Ptr,
+    F32,
+    F64
+}
+
+impl CallType {
+    fn is_float(self) -> bool {
+        matches!(self, CallType::F32 | CallType::F64)
+    }
+}
+
+/// The runtime signature of a function called through CompileResult::call.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Signature {
+    pub params: Vec<CallType>,
+    pub ret: CallType
+}
+
+/// The reasons that CompileResult::call can fail without calling the
+/// function.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CallError {
+    /// The host calling convention isn't supported.
+    UnsupportedTarget,
+    /// No function with the given name was compiled.
+    MissingFunction(String),
+    /// The number of arguments doesn't match the signature.
+    ArgumentCount { expected: usize, found: usize },
+    /// The argument at the given index can't be converted to its
+    /// parameter's type, or a parameter has type Void.
+    ArgumentType(usize),
+    /// The arguments don't all fit in registers.
+    TooManyArguments
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            CallError::UnsupportedTarget => {
+                write!(fmt, "calls with runtime signatures aren't supported on this target")
+            }
+            CallError::MissingFunction(ref name) => write!(fmt, "no function named `{}` was compiled", name),
+            CallError::ArgumentCount { expected, found } => {
+                write!(fmt, "expected {} arguments, found {}", expected, found)
+            }
+            CallError::ArgumentType(idx) => write!(fmt, "argument {} has an incompatible type", idx),
+            CallError::TooManyArguments => write!(fmt, "too many arguments to pass in registers")
+        }
+    }
+}
+
+impl Error for CallError {}
+
+const MAX_REGISTERS: usize = 8;
+
+type Invoker = unsafe extern "C" fn(u64, u64, u64, u64, u64, u64, u64, u64,
+                                    f64, f64, f64, f64, f64, f64, f64, f64) -> u64;
+type FloatInvoker = unsafe extern "C" fn(u64, u64, u64, u64, u64, u64, u64, u64,
+                                         f64, f64, f64, f64, f64, f64, f64, f64) -> f64;
+
+pub unsafe fn call(result: &CompileResult,
+                   name: &str,
+                   signature: &Signature,
+                   args: &[JitValue]) -> Result<Option<JitValue>, CallError> {
+    let int_registers = match Convention::host() {
+        Some(Convention::SysV64) => 6,
+        Some(Convention::Aapcs64) => 8,
+        _ => return Err(CallError::UnsupportedTarget)
+    };
+    if args.len() != signature.params.len() {
+        return Err(CallError::ArgumentCount {
+            expected: signature.params.len(),
+            found: args.len()
+        });
+    }
+    let mut ints = [0u64; MAX_REGISTERS];
+    let mut floats = [0f64; MAX_REGISTERS];
+    let (mut num_ints, mut num_floats) = (0, 0);
+    for (idx, (&ty, arg)) in signature.params.iter().zip(args.iter()).enumerate() {
+        if ty.is_float() {
+            if num_floats == MAX_REGISTERS {
+                return Err(CallError::TooManyArguments);
+            }
+            let value = arg.as_f64();
+            // a float is passed in the low half of the register.
+            floats[num_floats] = match ty {
+                CallType::F32 => f64::from_bits((value as f32).to_bits() as u64),
+                _ => value
+            };
+            num_floats += 1;
+        } else {
+            if num_ints == int_registers {
+                return Err(CallError::TooManyArguments);
+            }
+            ints[num_ints] = match (ty, *arg) {
+                (CallType::Void, _) => return Err(CallError::ArgumentType(idx)),
+                (CallType::Ptr, JitValue::Float(_)) |
+                (CallType::Ptr, JitValue::Bool(_)) => return Err(CallError::ArgumentType(idx)),
+                (CallType::Bool, _) => (arg.as_i64() != 0 || arg.as_f64() != 0.0) as u64,
+                _ => extend(ty, arg.as_i64() as u64)
+            };
+            num_ints += 1;
+        }
+    }
+    let func = result.get_function(name);
+    if func.is_null() {
+        return Err(CallError::MissingFunction(name.to_owned()));
+    }
+    if signature.ret.is_float() {
+        let invoke = mem::transmute::<*mut (), FloatInvoker>(func);
+        let ret = invoke(ints[0], ints[1], ints[2], ints[3], ints[4], ints[5], ints[6], ints[7],
+                         floats[0], floats[1], floats[2], floats[3],
+                         floats[4], floats[5], floats[6], floats[7]);
+        let ret = match signature.ret {
+            CallType::F32 => f32::from_bits(ret.to_bits() as u32) as f64,
+            _ => ret
+        };
+        return Ok(Some(JitValue::Float(ret)));
+    }
+    let invoke = mem::transmute::<*mut (), Invoker>(func);
+    let ret = invoke(ints[0], ints[1], ints[2], ints[3], ints[4], ints[5], ints[6], ints[7],
+                     floats[0], floats[1], floats[2], floats[3],
+                     floats[4], floats[5], floats[6], floats[7]);
+    // only the low bits of the register belong to a narrow return value.
+    let value = match signature.ret {
+        CallType::Void => return Ok(None),
+        CallType::Bool => JitValue::Bool(ret as u8 != 0),
+        CallType::Ptr => JitValue::Ptr(ret as usize as *mut ()),
+        CallType::I8 | CallType::I16 | CallType::I32 | CallType::I64 => {
+            JitValue::Int(extend(signature.ret, ret) as i64)
+        }
+        _ => JitValue::UInt(extend(signature.ret, ret))
+    };
+    Ok(Some(value))
+}
+
+/// Truncates a value to the width of an integer type and sign or zero
+/// extends it back to 64 bits.
+fn extend(ty: CallType, value: u64) -> u64 {
+    match ty {
+        CallType::I8 => value as i8 as i64 as u64,
+        CallType::I16 => value as i16 as i64 as u64,
+        CallType::I32 => value as i32 as i64 as u64,
+        CallType::U8 => value as u8 as u64,
+        CallType::U16 => value as u16 as u64,
+        CallType::U32 => value as u32 as u64,
+        _ => value
+    }
+}
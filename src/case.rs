@@ -0,0 +1,42 @@
+use gccjit_sys;
+
+use std::marker::PhantomData;
+use std::fmt;
+
+use context::Context;
+use dot;
+use tracking::CaseInfo;
+
+/// Case represents a case of a switch statement, which jumps to a block
+/// when the switch's value is within a range of constants. Cases are
+/// created with Context::new_case and used with Block::end_with_switch.
+#[derive(Copy, Clone)]
+pub struct Case<'ctx> {
+    marker: PhantomData<&'ctx Context<'ctx>>,
+    ptr: *mut gccjit_sys::gcc_jit_case,
+    info: CaseInfo
+}
+
+impl<'ctx> fmt::Debug for Case<'ctx> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        // the case is described by its parts, since gccjit returns no case
+        // at all for a range whose minimum is greater than its maximum.
+        fmt.write_str(&dot::case_text(&self.info))
+    }
+}
+
+pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_case, info: CaseInfo) -> Case<'ctx> {
+    Case {
+        marker: PhantomData,
+        ptr,
+        info
+    }
+}
+
+pub unsafe fn get_ptr<'ctx>(c: &Case<'ctx>) -> *mut gccjit_sys::gcc_jit_case {
+    c.ptr
+}
+
+pub fn get_info(c: &Case) -> CaseInfo {
+    c.info
+}
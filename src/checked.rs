@@ -0,0 +1,126 @@
+//! Overflow-checked integer arithmetic through GCC's typed overflow
+//! builtins, such as __builtin_sadd_overflow and __builtin_umulll_overflow.
+//! The builtin is picked by the signedness and size of the destination.
+//! Types narrower than int are computed in int or unsigned int, and the
+//! result also overflows if it doesn't fit back into the destination.
+
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::{c_int, c_long, c_longlong};
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_binary_op::GCC_JIT_BINARY_OP_LOGICAL_OR;
+use gccjit_sys::gcc_jit_comparison::GCC_JIT_COMPARISON_NE;
+use gccjit_sys::gcc_jit_types::*;
+
+use abi;
+use block::Block;
+use location::{self, Location};
+use lvalue::LValue;
+use object::{self, ToObject};
+use rvalue::{self, RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+#[derive(Copy, Clone, Debug)]
+pub enum CheckedOp {
+    Add,
+    Sub,
+    Mul
+}
+
+impl CheckedOp {
+    fn name(self) -> &'static str {
+        match self {
+            CheckedOp::Add => "add",
+            CheckedOp::Sub => "sub",
+            CheckedOp::Mul => "mul"
+        }
+    }
+}
+
+pub fn add_checked<'ctx>(block: Block<'ctx>,
+                         loc: Option<Location<'ctx>>,
+                         op: CheckedOp,
+                         dest: LValue<'ctx>,
+                         a: RValue<'ctx>,
+                         b: RValue<'ctx>) -> RValue<'ctx> {
+    let dest_ty = dest.to_rvalue().get_type();
+    let size = tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(unsafe { types::get_ptr(&dest_ty) })) {
+            Some(TypeKind::Basic(kind)) if is_integer(kind) => abi::basic_layout(kind).map(|(size, _, _)| size),
+            _ => None
+        }
+    }).unwrap_or_else(|| panic!("checked arithmetic needs an integer destination, not {:?}", dest_ty));
+    let signed = dest_ty.is_signed();
+    let (suffix, wide_kind) = if size <= mem::size_of::<c_int>() {
+        ("", if signed { GCC_JIT_TYPE_INT } else { GCC_JIT_TYPE_UNSIGNED_INT })
+    } else if size == mem::size_of::<c_long>() {
+        ("l", if signed { GCC_JIT_TYPE_LONG } else { GCC_JIT_TYPE_UNSIGNED_LONG })
+    } else if size == mem::size_of::<c_longlong>() {
+        ("ll", if signed { GCC_JIT_TYPE_LONG_LONG } else { GCC_JIT_TYPE_UNSIGNED_LONG_LONG })
+    } else {
+        panic!("there is no overflow builtin for {:?}", dest_ty)
+    };
+    let name = format!("__builtin_{}{}{}_overflow", if signed { "s" } else { "u" }, op.name(), suffix);
+    let narrow = size < mem::size_of::<c_int>();
+
+    let func = block.get_function();
+    let loc_ptr = match loc {
+        Some(loc) => unsafe { location::get_ptr(&loc) },
+        None => ::std::ptr::null_mut()
+    };
+    unsafe {
+        let ctx = tracking::context_of(object::get_ptr(&block.to_object()));
+        let wide_ty = types::from_ptr(gccjit_sys::gcc_jit_context_get_type(ctx, wide_kind));
+        let bool_ty = types::from_ptr(gccjit_sys::gcc_jit_context_get_type(ctx, GCC_JIT_TYPE_BOOL));
+        let cast = |value: RValue<'ctx>, ty: Type<'ctx>| {
+            rvalue::from_ptr(gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(&value),
+                                                                  types::get_ptr(&ty)))
+        };
+        let result = if narrow { func.new_local(loc, wide_ty, "checked_wide") } else { dest };
+
+        let cname = CString::new(name.as_str()).unwrap();
+        let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, cname.as_ptr());
+        if builtin.is_null() {
+            panic!("libgccjit doesn't provide {}", name);
+        }
+        let param_ty = |idx: c_int| {
+            let param = gccjit_sys::gcc_jit_function_get_param(builtin, idx);
+            types::from_ptr(gccjit_sys::gcc_jit_rvalue_get_type(gccjit_sys::gcc_jit_param_as_rvalue(param)))
+        };
+        let mut args = [rvalue::get_ptr(&cast(a, param_ty(0))),
+                        rvalue::get_ptr(&cast(b, param_ty(1))),
+                        rvalue::get_ptr(&cast(result.get_address(loc), param_ty(2)))];
+        let call = rvalue::from_ptr(gccjit_sys::gcc_jit_context_new_call(ctx, loc_ptr, builtin,
+                                                                         args.len() as c_int,
+                                                                         args.as_mut_ptr()));
+        let overflowed = func.new_local(loc, bool_ty, "checked_overflow");
+        block.add_assignment(loc, overflowed, call);
+        if narrow {
+            // the value overflows the destination if converting it back
+            // doesn't give the value computed in the wider type.
+            block.add_assignment(loc, dest, cast(result.to_rvalue(), dest_ty));
+            let widened = cast(dest.to_rvalue(), wide_ty);
+            let truncated = gccjit_sys::gcc_jit_context_new_comparison(ctx, loc_ptr, GCC_JIT_COMPARISON_NE,
+                                                                       rvalue::get_ptr(&widened),
+                                                                       rvalue::get_ptr(&result.to_rvalue()));
+            let either = gccjit_sys::gcc_jit_context_new_binary_op(ctx, loc_ptr, GCC_JIT_BINARY_OP_LOGICAL_OR,
+                                                                   types::get_ptr(&bool_ty),
+                                                                   rvalue::get_ptr(&overflowed.to_rvalue()),
+                                                                   truncated);
+            block.add_assignment(loc, overflowed, rvalue::from_ptr(either));
+        }
+        overflowed.to_rvalue()
+    }
+}
+
+fn is_integer(kind: &gccjit_sys::gcc_jit_types) -> bool {
+    matches!(*kind,
+             GCC_JIT_TYPE_CHAR | GCC_JIT_TYPE_SIGNED_CHAR | GCC_JIT_TYPE_UNSIGNED_CHAR |
+             GCC_JIT_TYPE_SHORT | GCC_JIT_TYPE_UNSIGNED_SHORT |
+             GCC_JIT_TYPE_INT | GCC_JIT_TYPE_UNSIGNED_INT |
+             GCC_JIT_TYPE_LONG | GCC_JIT_TYPE_UNSIGNED_LONG |
+             GCC_JIT_TYPE_LONG_LONG | GCC_JIT_TYPE_UNSIGNED_LONG_LONG |
+             GCC_JIT_TYPE_SIZE_T)
+}
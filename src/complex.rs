@@ -0,0 +1,87 @@
+//! C's complex number types, which have no Rust equivalent and so can't
+//! be created with Context::new_type. A complex value is laid out like a
+//! struct of its real and imaginary parts, so constants are built from a
+//! vector of the two parts, and the parts are read back with GCC's
+//! creal and cimag builtins.
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types::*;
+
+use context::{self, Context};
+use function;
+use location::Location;
+use rvalue::RValue;
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// C types that don't correspond to a Rust type, created with
+/// Context::new_c_type.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CType {
+    /// _Complex float.
+    ComplexFloat,
+    /// _Complex double.
+    ComplexDouble,
+    /// _Complex long double.
+    ComplexLongDouble
+}
+
+pub fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>, ty: CType) -> Type<'a> {
+    // gcc_jit_types isn't Copy, so the kind is made once for each use.
+    let kind = || match ty {
+        CType::ComplexFloat => GCC_JIT_TYPE_COMPLEX_FLOAT,
+        CType::ComplexDouble => GCC_JIT_TYPE_COMPLEX_DOUBLE,
+        CType::ComplexLongDouble => GCC_JIT_TYPE_COMPLEX_LONG_DOUBLE
+    };
+    unsafe {
+        let ptr = gccjit_sys::gcc_jit_context_get_type(context::get_ptr(ctx), kind());
+        types::record(types::from_ptr(ptr), TypeKind::Basic(kind()))
+    }
+}
+
+/// Finds which complex type a type is.
+fn complex_kind(ty: Type) -> Option<CType> {
+    let ty = unsafe { types::get_ptr(&ty) };
+    tracking::with_registry(|reg| {
+        match *reg.type_kind(reg.unqualified(ty))? {
+            TypeKind::Basic(GCC_JIT_TYPE_COMPLEX_FLOAT) => Some(CType::ComplexFloat),
+            TypeKind::Basic(GCC_JIT_TYPE_COMPLEX_DOUBLE) => Some(CType::ComplexDouble),
+            TypeKind::Basic(GCC_JIT_TYPE_COMPLEX_LONG_DOUBLE) => Some(CType::ComplexLongDouble),
+            _ => None
+        }
+    })
+}
+
+pub fn new_rvalue<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                            ty: Type<'a>,
+                            re: f64,
+                            im: f64) -> RValue<'a> {
+    let part_ty = match complex_kind(ty) {
+        Some(CType::ComplexFloat) => ctx.new_type::<f32>(),
+        Some(CType::ComplexDouble) => ctx.new_type::<f64>(),
+        // there are no vectors of long doubles to build the value from.
+        Some(CType::ComplexLongDouble) => panic!("complex long double constants aren't supported"),
+        None => panic!("new_rvalue_from_complex needs a complex type, not {:?}", ty)
+    };
+    let parts = [ctx.new_rvalue_from_double(part_ty, re), ctx.new_rvalue_from_double(part_ty, im)];
+    let vector = ctx.new_rvalue_from_vector(None, part_ty.make_vector(2), &parts);
+    ctx.new_bitcast(None, vector, ty)
+}
+
+pub fn part<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                      loc: Option<Location<'a>>,
+                      value: RValue<'a>,
+                      name: &str) -> RValue<'a> {
+    let ty = value.get_type();
+    let suffix = match complex_kind(ty) {
+        Some(CType::ComplexFloat) => "f",
+        Some(CType::ComplexDouble) => "",
+        Some(CType::ComplexLongDouble) => "l",
+        None => panic!("{} needs a complex value, not a value of type {:?}", name, ty)
+    };
+    let builtin = ctx.get_builtin_function(format!("__builtin_{}{}", name, suffix));
+    if unsafe { function::get_ptr(&builtin) }.is_null() {
+        panic!("libgccjit doesn't provide __builtin_{}{}", name, suffix);
+    }
+    ctx.new_call(loc, builtin, &[value])
+}
@@ -0,0 +1,388 @@
+use std::error::Error;
+use std::fmt;
+
+use context::OptimizationLevel;
+
+/// A snapshot of the options that have been set on a Context, obtained
+/// from Context::config. Child contexts start out with a copy of their
+/// parent's configuration, just like libgccjit does with the options
+/// themselves.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ContextConfig {
+    pub program_name: Option<String>,
+    pub optimization_level: OptimizationLevel,
+    pub debug_info: bool,
+    pub dump_code_on_compile: bool,
+    pub trace_comments: bool,
+    /// Whether statements, calls, and field accesses are recorded, set
+    /// with Context::set_tracking.
+    pub tracking: bool,
+    pub allow_unreachable_blocks: bool,
+    pub use_external_driver: bool,
+    /// The stack usage past which Context::verify reports a violation.
+    pub stack_warning_threshold: Option<usize>,
+    /// The extra command line options, in the order they were added.
+    pub command_line_options: Vec<String>,
+    /// The options passed to the driver when linking, in the order they
+    /// were added.
+    pub driver_options: Vec<String>,
+    pub lowering: Lowering
+}
+
+impl Default for ContextConfig {
+    fn default() -> ContextConfig {
+        ContextConfig {
+            program_name: None,
+            optimization_level: OptimizationLevel::None,
+            debug_info: false,
+            dump_code_on_compile: false,
+            trace_comments: false,
+            tracking: false,
+            allow_unreachable_blocks: false,
+            use_external_driver: false,
+            stack_warning_threshold: None,
+            command_line_options: vec![],
+            driver_options: vec![],
+            lowering: Lowering::Branchy
+        }
+    }
+}
+
+/// How helpers that choose between values, such as Context::new_select,
+/// are expanded. Set with Context::set_lowering.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Lowering {
+    /// Branches to the code for the chosen value, which is smaller and
+    /// avoids evaluating both values.
+    Branchy,
+    /// Evaluates both values and combines them with arithmetic where the
+    /// type allows it, which avoids mispredicted branches.
+    Branchless
+}
+
+/// Whether generated code is position independent, set with
+/// Context::set_position_independent.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PicLevel {
+    /// Code that must be loaded at a fixed address, linked into a
+    /// non-PIE executable.
+    None,
+    /// Code that can be loaded at any address, as needed for shared
+    /// libraries.
+    Pic,
+    /// Code for a position independent executable, which is the default
+    /// for executables on most modern distributions.
+    Pie
+}
+
+/// How much debug info is generated, set with Context::set_debug_level.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DebugLevel {
+    /// Only enough to give backtraces: functions and line numbers, but no
+    /// information about locals.
+    G1,
+    /// The default of -g, which includes locals and types.
+    G2,
+    /// Everything in G2, plus the macros of the frontend's source language.
+    G3
+}
+
+/// The version of DWARF that debug info is generated in, set with
+/// Context::set_debug_format.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DebugFormat {
+    Dwarf4,
+    Dwarf5
+}
+
+/// Which functions get stack canaries that catch stack buffer overflows,
+/// set with Context::set_stack_protector.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StackProtector {
+    None,
+    /// Functions with local arrays or locals whose address is taken.
+    Strong,
+    /// Every function.
+    All
+}
+
+/// The sanitizers that can instrument generated code, enabled with
+/// Context::enable_sanitizer.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sanitizer {
+    /// AddressSanitizer, which catches out of bounds and use after free
+    /// accesses. Its runtime is libasan.
+    Address,
+    /// UndefinedBehaviorSanitizer, which catches overflows, misaligned
+    /// accesses, and other undefined behavior. Its runtime is libubsan.
+    Undefined,
+    /// ThreadSanitizer, which catches data races. Its runtime is libtsan.
+    Thread
+}
+
+impl Sanitizer {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Undefined => "undefined",
+            Sanitizer::Thread => "thread"
+        }
+    }
+}
+
+/// Named presets of compilation options, applied with
+/// Context::apply_profile.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Profile {
+    /// No optimizations, with debug info and frame pointers so that
+    /// jitted code can be stepped through in a debugger.
+    Debug,
+    /// All optimizations, including ones that don't strictly follow
+    /// IEEE floating point semantics.
+    ReleaseFast,
+    /// Optimizes for code size over speed.
+    ReleaseSmall,
+    /// Standard optimizations, with stack protection and without
+    /// optimizations that assume undefined behavior never happens.
+    ReleaseSafe
+}
+
+impl Profile {
+    /// The optimization level that this profile uses.
+    pub fn optimization_level(self) -> OptimizationLevel {
+        match self {
+            Profile::Debug => OptimizationLevel::None,
+            Profile::ReleaseFast => OptimizationLevel::Aggressive,
+            Profile::ReleaseSmall | Profile::ReleaseSafe => OptimizationLevel::Standard
+        }
+    }
+
+    /// Whether this profile generates debug info.
+    pub fn debug_info(self) -> bool {
+        self == Profile::Debug
+    }
+
+    /// The command line options that this profile passes to gcc.
+    pub fn command_line_options(self) -> &'static [&'static str] {
+        match self {
+            Profile::Debug => &["-fno-omit-frame-pointer"],
+            Profile::ReleaseFast => &["-fomit-frame-pointer", "-ffast-math"],
+            Profile::ReleaseSmall => &["-Os", "-fomit-frame-pointer"],
+            Profile::ReleaseSafe => &["-fno-omit-frame-pointer", "-fstack-protector-strong",
+                                      "-fstack-clash-protection", "-fno-strict-overflow",
+                                      "-fno-delete-null-pointer-checks"]
+        }
+    }
+}
+
+/// Options that are accepted exactly as written.
+const EXACT_OPTIONS: &[&str] = &[
+    "-O0", "-O1", "-O2", "-O3", "-Os", "-Ofast", "-Og",
+    "-g", "-g0", "-g1", "-g2", "-g3", "-ggdb", "-gdwarf", "-gdwarf-4", "-gdwarf-5",
+    "-w", "-pg", "-fPIC", "-fpic", "-fPIE", "-fpie", "-fno-PIC", "-fno-pic", "-fno-PIE", "-fno-pie"
+];
+
+/// Code generation and optimization flags, accepted after -f or -fno-.
+const F_FLAGS: &[&str] = &[
+    "fast-math", "math-errno", "trapping-math", "signed-zeros", "finite-math-only",
+    "associative-math", "reciprocal-math", "unsafe-math-optimizations", "rounding-math",
+    "omit-frame-pointer", "stack-protector", "stack-protector-strong", "stack-protector-all",
+    "stack-protector-explicit", "stack-clash-protection", "inline", "inline-functions",
+    "inline-small-functions", "unroll-loops", "unroll-all-loops", "tree-vectorize",
+    "tree-loop-vectorize", "tree-slp-vectorize", "strict-aliasing", "strict-overflow", "wrapv",
+    "trapv", "exceptions", "non-call-exceptions", "unwind-tables", "asynchronous-unwind-tables",
+    "split-stack", "builtin", "delete-null-pointer-checks", "optimize-sibling-calls",
+    "function-sections", "data-sections", "plt", "semantic-interposition", "ipa-pta", "lto",
+    "fat-lto-objects", "align-functions", "align-loops", "align-jumps", "align-labels",
+    "verbose-asm", "var-tracking", "var-tracking-assignments", "common", "toplevel-reorder",
+    "profile-arcs", "test-coverage", "profile-correction"
+];
+
+/// Flags that take a value, accepted as -f<name>=<value>.
+const F_VALUE_FLAGS: &[&str] = &[
+    "cf-protection", "sanitize", "fp-contract", "excess-precision", "visibility", "tls-model",
+    "stack-reuse", "profile-dir", "profile-note", "profile-generate", "profile-use",
+    "align-functions", "align-loops", "align-jumps", "align-labels"
+];
+
+/// Target flags, accepted after -m or -mno-.
+const M_FLAGS: &[&str] = &[
+    "sse", "sse2", "sse3", "ssse3", "sse4", "sse4.1", "sse4.2", "avx", "avx2", "avx512f",
+    "avx512bw", "avx512dq", "avx512vl", "fma", "bmi", "bmi2", "popcnt", "lzcnt", "red-zone",
+    "general-regs-only", "outline-atomics"
+];
+
+/// Target flags that take a value, accepted as -m<name>=<value>.
+const M_VALUE_FLAGS: &[&str] = &["arch", "tune", "cpu", "cmodel", "abi", "fpmath"];
+
+/// Whether a command line option is one that is known to be accepted by
+/// gcc when compiling jitted code. This isn't an exhaustive list of gcc's
+/// options, only the ones that are commonly useful for jitted code.
+pub(crate) fn is_known_option(option: &str) -> bool {
+    if EXACT_OPTIONS.contains(&option) {
+        return true;
+    }
+    let (flags, value_flags, rest) = if let Some(rest) = option.strip_prefix("-f") {
+        (F_FLAGS, F_VALUE_FLAGS, rest)
+    } else if let Some(rest) = option.strip_prefix("-m") {
+        (M_FLAGS, M_VALUE_FLAGS, rest)
+    } else if let Some(rest) = option.strip_prefix("-W") {
+        // warnings don't change the generated code, so any are accepted.
+        return !rest.is_empty();
+    } else {
+        return false;
+    };
+    match rest.find('=') {
+        Some(idx) => idx + 1 < rest.len() && value_flags.contains(&&rest[..idx]),
+        None => flags.contains(&rest.strip_prefix("no-").unwrap_or(rest))
+    }
+}
+
+/// Whether a value can be given to an option such as -march as is.
+pub(crate) fn is_valid_option_value(value: &str) -> bool {
+    !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '=')
+}
+
+/// An argument that Context::configure_from_args couldn't make sense of.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ArgError {
+    /// The argument isn't an option that is known to be accepted by gcc.
+    Unknown(String),
+    /// The option takes a value, such as the library of -l, that wasn't
+    /// given.
+    MissingValue(String),
+    /// The option's value can't be passed to gcc.
+    InvalidValue(String)
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArgError::Unknown(ref arg) => write!(fmt, "unknown option {:?}", arg),
+            ArgError::MissingValue(ref arg) => write!(fmt, "option {:?} is missing its value", arg),
+            ArgError::InvalidValue(ref arg) => write!(fmt, "option {:?} has an invalid value", arg)
+        }
+    }
+}
+
+impl Error for ArgError {}
+
+/// An argument of a gcc-like argument list, sorted by the Context setter
+/// that it corresponds to.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Arg {
+    /// An optimization level, along with the option that picks a variant
+    /// of it, such as -Os.
+    Optimization(OptimizationLevel, Option<String>),
+    DebugInfo(bool),
+    March(String),
+    Mtune(String),
+    Library(String),
+    LibraryPath(String),
+    Driver(String),
+    CommandLine(String)
+}
+
+/// Parses a gcc-like argument list, such as ["-O2", "-g", "-lm"]. Options
+/// that take a value can be given it in the same argument or in the next
+/// one, as in "-lm" and "-l", "m". Every argument that can't be used is
+/// reported.
+pub(crate) fn parse_args(args: &[&str]) -> Result<Vec<Arg>, Vec<ArgError>> {
+    let mut parsed = vec![];
+    let mut errors = vec![];
+    let mut iter = args.iter();
+    while let Some(&arg) = iter.next() {
+        let (prefix, joined) = match arg {
+            "-l" | "-L" => (arg, None),
+            _ if arg.starts_with("-l") || arg.starts_with("-L") => (&arg[..2], Some(&arg[2..])),
+            _ => ("", None)
+        };
+        if !prefix.is_empty() {
+            match joined.or_else(|| iter.next().cloned()) {
+                None | Some("") => errors.push(ArgError::MissingValue(arg.to_owned())),
+                Some(value) if value.contains('\0') => errors.push(ArgError::InvalidValue(arg.to_owned())),
+                Some(value) if prefix == "-l" => parsed.push(Arg::Library(value.to_owned())),
+                Some(value) => parsed.push(Arg::LibraryPath(value.to_owned()))
+            }
+            continue;
+        }
+        // options are passed to gcc as C strings, which can't hold NULs.
+        if arg.contains('\0') {
+            errors.push(ArgError::InvalidValue(arg.to_owned()));
+            continue;
+        }
+        let result = match arg {
+            "-O0" => Arg::Optimization(OptimizationLevel::None, None),
+            "-O" | "-O1" => Arg::Optimization(OptimizationLevel::Limited, None),
+            "-O2" => Arg::Optimization(OptimizationLevel::Standard, None),
+            "-O3" => Arg::Optimization(OptimizationLevel::Aggressive, None),
+            "-Og" => Arg::Optimization(OptimizationLevel::Limited, Some(arg.to_owned())),
+            "-Os" => Arg::Optimization(OptimizationLevel::Standard, Some(arg.to_owned())),
+            "-Ofast" => Arg::Optimization(OptimizationLevel::Aggressive, Some(arg.to_owned())),
+            "-g" => Arg::DebugInfo(true),
+            "-g0" => Arg::DebugInfo(false),
+            _ if arg.starts_with("-march=") || arg.starts_with("-mtune=") => {
+                let value = &arg["-march=".len()..];
+                if !is_valid_option_value(value) {
+                    errors.push(ArgError::InvalidValue(arg.to_owned()));
+                    continue;
+                }
+                if arg.starts_with("-march=") {
+                    Arg::March(value.to_owned())
+                } else {
+                    Arg::Mtune(value.to_owned())
+                }
+            }
+            // -Wl, options are for the linker, unlike other -W options.
+            _ if arg.starts_with("-Wl,") => Arg::Driver(arg.to_owned()),
+            _ if is_known_option(arg) => Arg::CommandLine(arg.to_owned()),
+            _ => {
+                errors.push(ArgError::Unknown(arg.to_owned()));
+                continue;
+            }
+        };
+        parsed.push(result);
+    }
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_options() {
+        assert!(is_known_option("-ffast-math"));
+        assert!(is_known_option("-fno-math-errno"));
+        assert!(is_known_option("-march=native"));
+        assert!(is_known_option("-O2"));
+        assert!(is_known_option("-fno-PIE"));
+        assert!(!is_known_option("-ffast-maths"));
+        assert!(!is_known_option("-march="));
+        assert!(!is_known_option("fast-math"));
+    }
+
+    #[test]
+    fn parse_gcc_args() {
+        let args = parse_args(&["-O2", "-g", "-march=native", "-fPIC", "-l", "m", "-L/opt/lib", "-Wl,-z,now"]);
+        assert_eq!(args.unwrap(), [Arg::Optimization(OptimizationLevel::Standard, None),
+                                   Arg::DebugInfo(true),
+                                   Arg::March("native".to_owned()),
+                                   Arg::CommandLine("-fPIC".to_owned()),
+                                   Arg::Library("m".to_owned()),
+                                   Arg::LibraryPath("/opt/lib".to_owned()),
+                                   Arg::Driver("-Wl,-z,now".to_owned())]);
+        assert_eq!(parse_args(&["-O2", "--frobnicate", "-mtune=", "-l"]).unwrap_err(),
+                   [ArgError::Unknown("--frobnicate".to_owned()),
+                    ArgError::InvalidValue("-mtune=".to_owned()),
+                    ArgError::MissingValue("-l".to_owned())]);
+        assert_eq!(parse_args(&["-l", "", "-L", "", "-Wl,-z\0now"]).unwrap_err(),
+                   [ArgError::MissingValue("-l".to_owned()),
+                    ArgError::MissingValue("-L".to_owned()),
+                    ArgError::InvalidValue("-Wl,-z\0now".to_owned())]);
+    }
+}
@@ -1,19 +1,57 @@
+use std::collections::HashMap;
+use std::any::Any;
+use std::cell::RefCell;
 use std::default::Default;
 use std::ops::Drop;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::marker::PhantomData;
 use std::mem;
+use std::process;
 use std::ptr;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::vec;
 
 use location::{self, Location};
-use structs::{self, Struct};
+use object::{self, ToObject};
+use structs::{self, OpaqueStruct, Struct};
+use enums::{self, EnumType};
 use types;
 use field::{self, Field};
+use case::{self, Case};
 use rvalue::{self, RValue, ToRValue};
 use function::{self, Function, FunctionType};
-use block::{BinaryOp, UnaryOp, ComparisonOp};
+use attribute::FnAttribute;
+use block::{self, Block, BinaryOp, UnaryOp, ComparisonOp};
 use parameter::{self, Parameter};
 use lvalue::{self, LValue};
+use tracking::{self, FunctionInfo, GlobalInfo, CallInfo, CaseInfo, ContextStats, SymbolInfo, TypeKind};
+use verify::{self, Violation};
+use import::{HostSymbol, ImportableClosure, ImportableFn, Symbol};
+use decl::{self, FnDecl, GlobalDecl, StructDecl};
+use varargs::{self, VaList};
+use dot;
+use emit;
+use pretty;
+use config::{self, Arg, ArgError, ContextConfig, DebugFormat, DebugLevel, Lowering, PicLevel, Profile, Sanitizer, StackProtector};
+#[cfg(feature = "ffi-call")]
+use call::{self, CallError, Signature};
+#[cfg(feature = "ffi-call")]
+use value::JitValue;
+#[cfg(all(feature = "dylib", unix))]
+use dylib::{self, LoadedLibrary};
+use math::{self, BuiltinMath};
+use bits::{self, BuiltinBits};
+use libc::{self, Libc};
+use multiversion;
+use adapter::{self, AdapterStrategy};
+use vector;
+use complex::{self, CType};
+use select;
 use gccjit_sys;
 use gccjit_sys::gcc_jit_int_option::*;
 use gccjit_sys::gcc_jit_str_option::*;
@@ -22,6 +60,7 @@ use gccjit_sys::gcc_jit_bool_option::*;
 /// Represents an optimization level that the JIT compiler
 /// will use when compiling your code.
 #[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum OptimizationLevel {
     /// No optimizations are applied.
     None,
@@ -51,15 +90,60 @@ pub enum OutputKind {
     Executable
 }
 
+/// Indicates to gccjit the linkage of a global variable created
+/// by new_global.
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GlobalKind {
+    /// The global is defined by the JIT and is visible by name
+    /// through CompileResult::get_global.
+    Exported,
+    /// The global is defined by the JIT but is invisible outside of
+    /// jitted code, analogous to a "static" global in C.
+    Internal,
+    /// The global is not defined by the JIT; it refers to a global
+    /// that is defined elsewhere, analogous to an "extern" global in C.
+    Imported
+}
+
+/// The dumps captured by Context::compile_with_dumps, keyed by the name of
+/// the pass that produced them.
+#[derive(Clone, Debug, Default)]
+pub struct CompileDumps {
+    dumps: HashMap<String, String>
+}
+
+impl CompileDumps {
+    /// Gets the dump of the given pass, or None if the pass didn't run or
+    /// isn't a pass that gcc knows of.
+    pub fn get(&self, pass: &str) -> Option<&str> {
+        self.dumps.get(pass).map(|dump| dump.as_str())
+    }
+
+    /// Iterates over the names of the passes that were dumped.
+    pub fn passes(&self) -> impl Iterator<Item = &str> {
+        self.dumps.keys().map(|pass| pass.as_str())
+    }
+}
+
 /// Represents a successful compilation of a context. This type
 /// provides the means to access compiled functions and globals.
 /// JIT compiled functions are exposted to Rust as an extern "C" function
 /// pointer.
 pub struct CompileResult {
-    ptr: *mut gccjit_sys::gcc_jit_result
+    ptr: *mut gccjit_sys::gcc_jit_result,
+    /// The closures that the compiled code can call, which are dropped
+    /// after the code is released.
+    closures: Vec<Rc<dyn Any>>
 }
 
 impl CompileResult {
+    /// Returns true if the compiled code can call closures imported with
+    /// Context::import_closure.
+    pub(crate) fn has_closures(&self) -> bool {
+        !self.closures.is_empty()
+    }
+
     /// Gets a function pointer to a JIT compiled function. If the function
     /// does not exist (wasn't compiled by the Context that produced this
     /// CompileResult), this function returns a null pointer.
@@ -78,6 +162,29 @@ impl CompileResult {
         }
     }
 
+    /// Gets a JIT compiled function as a function pointer of type T, such
+    /// as extern "C" fn(i32) -> i32, or None if no function with the
+    /// given name was compiled.
+    ///
+    /// # Safety
+    /// This function is unsafe because the caller must ensure that T is an
+    /// extern "C" function pointer type whose signature matches the one
+    /// that the function was declared with, and that the pointer isn't
+    /// called after this CompileResult is dropped.
+    ///
+    /// # Panics
+    /// Panics if T isn't the size of a pointer.
+    pub unsafe fn function<T: Copy, S: AsRef<str>>(&self, name: S) -> Option<T> {
+        assert_eq!(mem::size_of::<T>(), mem::size_of::<*mut ()>(),
+                   "CompileResult::function must be used with a function pointer type");
+        let ptr = self.get_function(name);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(mem::transmute_copy(&ptr))
+        }
+    }
+
     /// Gets a pointer to a global variable that lives on the JIT heap.
     ///
     /// It is the caller's responsibility
@@ -91,6 +198,75 @@ impl CompileResult {
             mem::transmute(ptr)
         }
     }
+
+    /// Calls a JIT compiled function whose signature is only known at
+    /// runtime, converting each argument to the type of its parameter the
+    /// way a C cast would. Returns None for functions that return void.
+    ///
+    /// This is only supported on SysV x86-64 and AArch64 targets, and only
+    /// for functions whose arguments all fit in registers: up to six (or
+    /// eight on AArch64) integer and pointer arguments and up to eight
+    /// floating point arguments. Variadic functions can't be called.
+    ///
+    /// # Safety
+    /// This function is unsafe because the caller must ensure that the
+    /// signature matches the one that the function was declared with in
+    /// the Context that produced this CompileResult, and that the function
+    /// is safe to call with the given arguments.
+    #[cfg(feature = "ffi-call")]
+    pub unsafe fn call<S: AsRef<str>>(&self,
+                                      name: S,
+                                      signature: &Signature,
+                                      args: &[JitValue]) -> Result<Option<JitValue>, CallError> {
+        call::call(self, name.as_ref(), signature, args)
+    }
+
+    /// Gets a typed handle to a global variable that lives on the JIT heap,
+    /// or None if no global with the given name was compiled. The returned
+    /// handle borrows this CompileResult, so it can't outlive the memory
+    /// it points to.
+    ///
+    /// # Safety
+    /// This function is unsafe because the caller must ensure that T has
+    /// the same size and layout as the type that the global was declared
+    /// with in the Context that produced this CompileResult.
+    pub unsafe fn global<T: Copy, S: AsRef<str>>(&self, name: S) -> Option<JitGlobal<'_, T>> {
+        let ptr = self.get_global(name) as *mut T;
+        if ptr.is_null() {
+            None
+        } else {
+            Some(JitGlobal {
+                marker: PhantomData,
+                ptr
+            })
+        }
+    }
+}
+
+/// A typed handle to a global variable of a compiled context, obtained
+/// from CompileResult::global. Reads and writes through this handle are
+/// observed by jitted code that accesses the same global.
+pub struct JitGlobal<'res, T> {
+    marker: PhantomData<&'res CompileResult>,
+    ptr: *mut T
+}
+
+impl<'res, T: Copy> JitGlobal<'res, T> {
+    /// Reads the current value of the global.
+    pub fn read(&self) -> T {
+        unsafe { ptr::read(self.ptr) }
+    }
+
+    /// Overwrites the current value of the global.
+    pub fn write(&self, value: T) {
+        unsafe { ptr::write(self.ptr, value) }
+    }
+
+    /// Gets the raw address of the global. The pointer is valid for
+    /// as long as the CompileResult that this handle borrows.
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
 }
 
 impl Drop for CompileResult {
@@ -112,15 +288,24 @@ impl Drop for CompileResult {
 /// less than the parent context.
 pub struct Context<'ctx> {
     marker: PhantomData<&'ctx Context<'ctx>>,
-    ptr: *mut gccjit_sys::gcc_jit_context
+    ptr: *mut gccjit_sys::gcc_jit_context,
+    /// The context this one was created from with new_child_context.
+    parent: Option<&'ctx Context<'ctx>>,
+    /// The closures imported with import_closure, which are dropped after
+    /// the context is released.
+    closures: RefCell<Vec<Rc<dyn Any>>>
 }
 
 impl Default for Context<'static> {
     fn default() -> Context<'static> {
         unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_acquire();
+            tracking::register(ptr, None);
             Context {
                 marker: PhantomData,
-                ptr: gccjit_sys::gcc_jit_context_acquire()
+                ptr,
+                parent: None,
+                closures: RefCell::new(Vec::new())
             }
         }
     }
@@ -136,6 +321,7 @@ impl<'ctx> Context<'ctx> {
                                                        GCC_JIT_STR_OPTION_PROGNAME,
                                                        c_str.as_ptr());
         }
+        tracking::with_context(self.ptr, |info| info.config.program_name = Some(name_ref.to_owned()));
     }
     
     /// Sets the optimization level that the JIT compiler will use.
@@ -147,6 +333,7 @@ impl<'ctx> Context<'ctx> {
                                                        GCC_JIT_INT_OPTION_OPTIMIZATION_LEVEL,
                                                        level as i32);
         }
+        tracking::with_context(self.ptr, |info| info.config.optimization_level = level);
     }
     
     /// When set to true, dumps the code that the JIT generates to standard
@@ -157,25 +344,528 @@ impl<'ctx> Context<'ctx> {
                                                         GCC_JIT_BOOL_OPTION_DUMP_GENERATED_CODE,
                                                         value as i32);
         }
+        tracking::with_context(self.ptr, |info| info.config.dump_code_on_compile = value);
+    }
+
+    /// When set to true, the JIT generates debug info for the code it
+    /// compiles, so that it can be inspected in a debugger.
+    pub fn set_debug_info(&self, value: bool) {
+        unsafe {
+            gccjit_sys::gcc_jit_context_set_bool_option(self.ptr,
+                                                        GCC_JIT_BOOL_OPTION_DEBUGINFO,
+                                                        value as i32);
+        }
+        tracking::with_context(self.ptr, |info| info.config.debug_info = value);
+    }
+
+    /// Generates debug info at the given level, as with set_debug_info(true)
+    /// and -g1, -g2, or -g3.
+    pub fn set_debug_level(&self, level: DebugLevel) {
+        self.set_debug_info(true);
+        let option = match level {
+            DebugLevel::G1 => "-g1",
+            DebugLevel::G2 => "-g2",
+            DebugLevel::G3 => "-g3"
+        };
+        let unknown = self.add_command_line_options([option]);
+        debug_assert!(unknown.is_empty());
+    }
+
+    /// Sets the DWARF version of the debug info, for debuggers that don't
+    /// support the version that gcc uses by default.
+    pub fn set_debug_format(&self, format: DebugFormat) {
+        let option = match format {
+            DebugFormat::Dwarf4 => "-gdwarf-4",
+            DebugFormat::Dwarf5 => "-gdwarf-5"
+        };
+        let unknown = self.add_command_line_options([option]);
+        debug_assert!(unknown.is_empty());
+    }
+
+    /// When set to true, blocks that can't be reached from the entry block
+    /// of their function are silently dropped instead of being an error,
+    /// both by gccjit and by verify.
+    pub fn set_allow_unreachable_blocks(&self, value: bool) {
+        unsafe {
+            gccjit_sys::gcc_jit_context_set_bool_allow_unreachable_blocks(self.ptr, value as i32);
+        }
+        tracking::with_context(self.ptr, |info| info.config.allow_unreachable_blocks = value);
+    }
+
+    /// When set to true, gccjit assembles and links by running the gcc
+    /// driver found on the PATH as a separate process, instead of the
+    /// driver built into libgccjit. This is needed where the built-in
+    /// driver can't find the assembler or linker, as in some containers
+    /// and cross-compilation setups.
+    pub fn set_use_external_driver(&self, value: bool) {
+        unsafe {
+            gccjit_sys::gcc_jit_context_set_bool_use_external_driver(self.ptr, value as i32);
+        }
+        tracking::with_context(self.ptr, |info| info.config.use_external_driver = value);
+    }
+
+    /// Sets the text of the .ident directive in the generated code, which
+    /// ends up in the .comment section of object files, libraries, and
+    /// executables compiled with compile_to_file. It can be used to stamp
+    /// them with the version of the program that generated them.
+    #[cfg(feature = "master")]
+    pub fn set_output_ident<S: AsRef<str>>(&self, ident: S) {
+        let c_str = CString::new(ident.as_ref()).unwrap();
+        unsafe {
+            gccjit_sys::gcc_jit_context_set_output_ident(self.ptr, c_str.as_ptr());
+        }
+    }
+
+    /// When set to true, blocks get comments that describe how they are
+    /// terminated and which frontend constructs (added with
+    /// Block::add_trace) they implement, along with the locations of
+    /// each. The comments show up in dumps of the generated code, which
+    /// makes it easier to find the frontend source that produced them.
+    pub fn set_trace_comments(&self, value: bool) {
+        tracking::with_context(self.ptr, |info| info.config.trace_comments = value);
+    }
+
+    /// When set to true, the statements added to blocks, the calls, and
+    /// the field accesses created through this context are recorded, which
+    /// Context::verify, Context::pretty_print, and Function::to_dot need to
+    /// see the code of functions. It is off by default, to not pay for the
+    /// records when they aren't used, and only affects what is created
+    /// after it is set. Child contexts created afterwards inherit it.
+    pub fn set_tracking(&self, value: bool) {
+        tracking::with_context(self.ptr, |info| info.config.tracking = value);
+    }
+
+    /// Sets the number of bytes of locals past which Context::verify
+    /// reports a function as having a large stack frame, or None to not
+    /// check stack frames at all.
+    pub fn set_stack_warning_threshold(&self, bytes: Option<usize>) {
+        tracking::with_context(self.ptr, |info| info.config.stack_warning_threshold = bytes);
+    }
+
+    /// Sets how helpers that choose between values, such as new_select,
+    /// are expanded.
+    pub fn set_lowering(&self, lowering: Lowering) {
+        tracking::with_context(self.ptr, |info| info.config.lowering = lowering);
+    }
+
+    /// Makes gcc emit functions and globals in the order they were created
+    /// in, instead of the order its optimizers prefer, so that object
+    /// files and assembly built from the same program diff cleanly
+    /// between builds. This can't be undone, since libgccjit has no way
+    /// of removing command line options.
+    pub fn preserve_symbol_order(&self) {
+        let known = self.add_command_line_option("-fno-toplevel-reorder");
+        debug_assert!(known);
+    }
+
+    /// Applies a preset of options: the profile's optimization level and
+    /// debug info setting, along with its command line options.
+    pub fn apply_profile(&self, profile: Profile) {
+        self.set_optimization_level(profile.optimization_level());
+        self.set_debug_info(profile.debug_info());
+        let unknown = self.add_command_line_options(profile.command_line_options());
+        debug_assert!(unknown.is_empty(), "profile uses unknown options: {:?}", unknown);
+    }
+
+    /// Adds an option that is passed to gcc when the context is compiled,
+    /// such as "-ffast-math". Returns false if the option isn't one that
+    /// is known to be accepted by gcc, since a misspelled option would
+    /// otherwise only be reported when compilation fails. Unknown options
+    /// are still passed on to gcc.
+    #[must_use]
+    pub fn add_command_line_option<S: AsRef<str>>(&self, option: S) -> bool {
+        let option_ref = option.as_ref();
+        let c_str = CString::new(option_ref).unwrap();
+        unsafe {
+            gccjit_sys::gcc_jit_context_add_command_line_option(self.ptr, c_str.as_ptr());
+        }
+        tracking::with_context(self.ptr, |info| info.config.command_line_options.push(option_ref.to_owned()));
+        config::is_known_option(option_ref)
+    }
+
+    /// Adds several options like add_command_line_option, and returns the
+    /// ones that aren't known to be accepted by gcc.
+    #[must_use]
+    pub fn add_command_line_options<I, S>(&self, options: I) -> Vec<String>
+        where I: IntoIterator<Item=S>,
+              S: AsRef<str> {
+        options.into_iter()
+            .filter(|option| !self.add_command_line_option(option))
+            .map(|option| option.as_ref().to_owned())
+            .collect()
+    }
+
+    /// A number that identifies this context, unique among the contexts
+    /// created by the process and increasing in the order they were
+    /// created in. It is used to name contexts in the panics that debug
+    /// builds raise when an object of one context is passed to another.
+    pub fn id(&self) -> u64 {
+        tracking::with_context(self.ptr, |info| info.id)
+    }
+
+    /// Panics in debug builds if obj was created by a context other than
+    /// this one or one of its parents.
+    fn check_provenance<'a, O: ToObject<'a>>(&'a self, obj: &O) {
+        tracking::check_provenance(self.ptr, unsafe { object::get_ptr(&obj.to_object()) });
+    }
+
+    /// Sets the CPU to generate code for, as with -march. "native" selects
+    /// the CPU of the host.
+    ///
+    /// # Panics
+    /// Panics if the name is empty or contains whitespace or '='.
+    pub fn set_march<S: AsRef<str>>(&self, cpu: S) {
+        let known = self.add_command_line_option(format!("-march={}", checked_option_value("set_march", cpu.as_ref())));
+        debug_assert!(known);
+    }
+
+    /// Sets the CPU to tune code for without requiring it, as with -mtune.
+    ///
+    /// # Panics
+    /// Panics if the name is empty or contains whitespace or '='.
+    pub fn set_mtune<S: AsRef<str>>(&self, cpu: S) {
+        let known = self.add_command_line_option(format!("-mtune={}", checked_option_value("set_mtune", cpu.as_ref())));
+        debug_assert!(known);
+    }
+
+    /// Turns link-time optimization on or off, with the -flto spelling for
+    /// both compiling and linking. Object files written by compile_to_file
+    /// then hold GCC's intermediate representation along with regular code
+    /// (-ffat-lto-objects), so they can be linked with gcc -flto to be
+    /// optimized together with the rest of a program, or linked normally.
+    /// Contexts compiled to memory or to libraries and executables are only
+    /// optimized as a unit on their own.
+    pub fn set_lto(&self, enabled: bool) {
+        let options : &[&str] = if enabled { &["-flto", "-ffat-lto-objects"] } else { &["-fno-lto"] };
+        let unknown = self.add_command_line_options(options);
+        debug_assert!(unknown.is_empty());
+        self.add_driver_option(if enabled { "-flto" } else { "-fno-lto" });
+    }
+
+    /// Sets whether the generated code is position independent. Pic is
+    /// needed for code that ends up in a shared library, and Pie or None
+    /// for executables, which must also match how the driver links them:
+    /// Pie links with -pie and None with -no-pie.
+    pub fn set_position_independent(&self, level: PicLevel) {
+        let (options, driver_option) : (&[&str], _) = match level {
+            PicLevel::None => (&["-fno-PIC", "-fno-PIE"], Some("-no-pie")),
+            PicLevel::Pic => (&["-fPIC"], None),
+            PicLevel::Pie => (&["-fPIE"], Some("-pie"))
+        };
+        let unknown = self.add_command_line_options(options);
+        debug_assert!(unknown.is_empty());
+        if let Some(option) = driver_option {
+            self.add_driver_option(option);
+        }
+    }
+
+    /// Sets which functions check for stack buffer overflows before they
+    /// return.
+    pub fn set_stack_protector(&self, protector: StackProtector) {
+        let option = match protector {
+            StackProtector::None => "-fno-stack-protector",
+            StackProtector::Strong => "-fstack-protector-strong",
+            StackProtector::All => "-fstack-protector-all"
+        };
+        let unknown = self.add_command_line_options([option]);
+        debug_assert!(unknown.is_empty());
+    }
+
+    /// Sets whether functions probe the stack as they grow it, so that
+    /// large stack allocations can't skip over the guard page.
+    pub fn set_stack_clash_protection(&self, enabled: bool) {
+        let option = if enabled { "-fstack-clash-protection" } else { "-fno-stack-clash-protection" };
+        let unknown = self.add_command_line_options([option]);
+        debug_assert!(unknown.is_empty());
+    }
+
+    /// Instruments the generated code with the given sanitizer, and links
+    /// its runtime into dynamic libraries and executables compiled from
+    /// this context.
+    ///
+    /// Code compiled to memory is loaded into the current process, which
+    /// must already have the sanitizer's runtime loaded: either the host
+    /// program is built with the same sanitizer, or the runtime is
+    /// preloaded, as in LD_PRELOAD=libasan.so. Address and Thread can't be
+    /// enabled together.
+    pub fn enable_sanitizer(&self, sanitizer: Sanitizer) {
+        let option = format!("-fsanitize={}", sanitizer.as_str());
+        let unknown = self.add_command_line_options([&option[..], "-fno-omit-frame-pointer"]);
+        debug_assert!(unknown.is_empty());
+        self.add_driver_option(option);
+    }
+
+    /// Instruments the generated code to count how often each of its
+    /// branches is taken, for gcov, and links in libgcov. Returns the path
+    /// of the notes file (.gcno), which is written to the given directory
+    /// when the context is compiled. The counts (.gcda) are written to the
+    /// same directory when the compiled code is unloaded, which is when
+    /// the CompileResult is dropped or the process exits, named after the
+    /// path of libgccjit's temporary files with '/' replaced by '#'.
+    pub fn enable_coverage<P: AsRef<Path>>(&self, dir: P) -> PathBuf {
+        let dir = dir.as_ref();
+        let notes = dir.join(format!("context-{}.gcno", self.id()));
+        let unknown = self.add_command_line_options([
+            "-fprofile-arcs".to_owned(),
+            "-ftest-coverage".to_owned(),
+            format!("-fprofile-dir={}", dir.display()),
+            format!("-fprofile-note={}", notes.display())
+        ]);
+        debug_assert!(unknown.is_empty());
+        self.add_driver_option("-lgcov");
+        notes
+    }
+
+    /// Instruments the generated code to collect a profile of how it runs
+    /// into the given directory, for recompiling it with set_profile_use.
+    /// The profile is written when the compiled code is unloaded, which is
+    /// when the CompileResult is dropped or the process exits.
+    ///
+    /// Profiles are matched up with code by the name of the object file it
+    /// was compiled to. Contexts compiled to memory are compiled in a new
+    /// temporary directory each time, so their profiles can't be found
+    /// again; compile the instrumented and optimized code with
+    /// compile_to_file to the same path instead.
+    pub fn set_profile_generate<P: AsRef<Path>>(&self, dir: P) {
+        let unknown = self.add_command_line_options([format!("-fprofile-generate={}", dir.as_ref().display())]);
+        debug_assert!(unknown.is_empty());
+        self.add_driver_option("-fprofile-generate");
+    }
+
+    /// Optimizes the generated code using a profile collected into the
+    /// given directory by code compiled with set_profile_generate. Code
+    /// that has no profile is compiled as usual.
+    pub fn set_profile_use<P: AsRef<Path>>(&self, dir: P) {
+        let unknown = self.add_command_line_options([
+            format!("-fprofile-use={}", dir.as_ref().display()),
+            // counts can be slightly off if the code ran on several threads.
+            "-fprofile-correction".to_owned(),
+            "-Wno-missing-profile".to_owned()
+        ]);
+        debug_assert!(unknown.is_empty());
+    }
+
+    /// Adds an option that is passed to the driver when the context is
+    /// compiled to a dynamic library or executable, or to memory, such as
+    /// "-lm" or "-Wl,--as-needed".
+    pub fn add_driver_option<S: AsRef<str>>(&self, option: S) {
+        let option_ref = option.as_ref();
+        let c_str = CString::new(option_ref).unwrap();
+        unsafe {
+            gccjit_sys::gcc_jit_context_add_driver_option(self.ptr, c_str.as_ptr());
+        }
+        tracking::with_context(self.ptr, |info| info.config.driver_options.push(option_ref.to_owned()));
+    }
+
+    /// Links against a library, given as in link_library("m") for libm,
+    /// so that jitted code can call its functions.
+    ///
+    /// # Panics
+    /// Panics if the name is empty, contains whitespace, or is given with
+    /// its "-l" or "lib" prefix or a file extension.
+    pub fn link_library<S: AsRef<str>>(&self, name: S) {
+        let name = checked_option_value("link_library", name.as_ref());
+        if name.starts_with("-l") || name.starts_with("lib") || name.ends_with(".so") || name.ends_with(".a") {
+            panic!("link_library takes the bare name of a library, such as \"m\" for libm, not {:?}", name);
+        }
+        self.add_driver_option(format!("-l{}", name));
+    }
+
+    /// Adds a directory to the ones that libraries are searched for in, as
+    /// with -L.
+    ///
+    /// # Panics
+    /// Panics if the path is empty.
+    pub fn add_library_path<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref();
+        if path.as_os_str().is_empty() {
+            panic!("add_library_path was given an empty path");
+        }
+        self.add_driver_option(format!("-L{}", path.display()));
+    }
+
+    /// Configures this context from a gcc-like argument list, such as
+    /// ["-O2", "-g", "-march=native", "-fPIC", "-lm"], so that tools built
+    /// on this crate can forward flags from their users. Optimization
+    /// levels, debug info, -march and -mtune, and libraries and library
+    /// paths go through the corresponding setters, -Wl, options are passed
+    /// to the driver, and other options are added as command line options.
+    ///
+    /// If any argument isn't known to be accepted by gcc, or is missing its
+    /// value, nothing is configured and every such argument is returned.
+    pub fn configure_from_args(&self, args: &[&str]) -> Result<(), Vec<ArgError>> {
+        for arg in config::parse_args(args)? {
+            match arg {
+                Arg::Optimization(level, variant) => {
+                    self.set_optimization_level(level);
+                    if let Some(variant) = variant {
+                        let known = self.add_command_line_option(variant);
+                        debug_assert!(known);
+                    }
+                }
+                Arg::DebugInfo(value) => self.set_debug_info(value),
+                Arg::March(cpu) => self.set_march(cpu),
+                Arg::Mtune(cpu) => self.set_mtune(cpu),
+                Arg::Library(name) => self.add_driver_option(format!("-l{}", name)),
+                Arg::LibraryPath(path) => self.add_library_path(path),
+                Arg::Driver(option) => self.add_driver_option(option),
+                Arg::CommandLine(option) => {
+                    let known = self.add_command_line_option(option);
+                    debug_assert!(known);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the types, fields, functions, globals, blocks, and statements
+    /// that have been created through this context, along with its child
+    /// contexts that are still alive. libgccjit doesn't report how much
+    /// memory a context uses, but it keeps everything created through a
+    /// context until the context is released, so these counts grow with
+    /// its memory use. Statements are only counted if tracking was enabled
+    /// with set_tracking.
+    ///
+    /// Objects created through a child context are owned by the child and
+    /// released along with it when it is dropped, so a long-lived parent
+    /// only keeps growing through what is created on the parent itself.
+    /// There is no way to release children from their parent, since the
+    /// child Context borrows the parent: drop the children instead, such
+    /// as by creating each one in the scope that uses it.
+    pub fn stats(&self) -> ContextStats {
+        tracking::with_registry(|reg| reg.stats(self.ptr))
+    }
+
+    /// Gets a snapshot of the options that have been set on this context.
+    pub fn config(&self) -> ContextConfig {
+        tracking::with_context(self.ptr, |info| info.config.clone())
+    }
+
+    /// Returns the errors that have been recorded on this context since
+    /// the last call, oldest first, so that a frontend can check for
+    /// errors after every risky step without seeing the same error twice.
+    /// gccjit only keeps the first and the most recent error of a context,
+    /// so if several errors were recorded since the last call, the ones in
+    /// between are lost.
+    pub fn drain_new_errors(&self) -> vec::IntoIter<String> {
+        let (first, last) = unsafe {
+            (gccjit_sys::gcc_jit_context_get_first_error(self.ptr),
+             gccjit_sys::gcc_jit_context_get_last_error(self.ptr))
+        };
+        if first.is_null() || last.is_null() {
+            return Vec::new().into_iter();
+        }
+        let first_msg = unsafe { CStr::from_ptr(first) }.to_string_lossy().into_owned();
+        let last_msg = unsafe { CStr::from_ptr(last) }.to_string_lossy().into_owned();
+        let errors = tracking::with_context(self.ptr, |info| {
+            let seen = &mut info.seen_errors;
+            let mut errors = vec![];
+            if !seen.first {
+                seen.first = true;
+                errors.push(first_msg.clone());
+            }
+            let last_is_new = match seen.last {
+                Some((ptr, ref msg)) => ptr != last || *msg != last_msg,
+                None => last_msg != first_msg
+            };
+            if last_is_new {
+                errors.push(last_msg.clone());
+            }
+            seen.last = Some((last, last_msg));
+            errors
+        });
+        errors.into_iter()
     }
 
     /// Compiles the context and returns a CompileResult that contains
     /// the means to access functions and globals that have currently
     /// been JIT compiled.
     pub fn compile(&self) -> CompileResult {
+        let closures = self.closures();
         unsafe {
             CompileResult {
-                ptr: gccjit_sys::gcc_jit_context_compile(self.ptr)
+                ptr: gccjit_sys::gcc_jit_context_compile(self.ptr),
+                closures
             }
         }
     }
-    
+
+    /// Gets the closures imported into this context and its parents, since
+    /// compiling a child context also compiles what its parents contain.
+    pub(crate) fn closures(&self) -> Vec<Rc<dyn Any>> {
+        let mut closures = self.closures.borrow().clone();
+        if let Some(parent) = self.parent {
+            closures.extend(parent.closures());
+        }
+        closures
+    }
+
+    /// Returns true if this context or one of its parents has imported a
+    /// closure with import_closure.
+    pub(crate) fn has_closures(&self) -> bool {
+        !self.closures.borrow().is_empty() || self.parent.is_some_and(|parent| parent.has_closures())
+    }
+
+    /// Compiles the context like compile, and captures the dumps of the
+    /// given gcc passes, such as "tree-optimized" or "rtl-expand", instead
+    /// of writing them to a file.
+    pub fn compile_with_dumps<I, S>(&self, passes: I) -> (CompileResult, CompileDumps)
+        where I: IntoIterator<Item = S>,
+              S: AsRef<str> {
+        // libgccjit writes to the dump buffers on every later compile of
+        // the context they're enabled on, so they're enabled on a child
+        // context that is only compiled once. gccjit keeps the pass names
+        // without copying them, so they have to outlive the compile.
+        let child = self.new_child_context();
+        let names : Vec<CString> = passes.into_iter()
+            .map(|pass| CString::new(pass.as_ref()).unwrap())
+            .collect();
+        let mut buffers = vec![ptr::null_mut(); names.len()];
+        unsafe {
+            for (name, buffer) in names.iter().zip(buffers.iter_mut()) {
+                gccjit_sys::gcc_jit_context_enable_dump(child.ptr, name.as_ptr(), buffer);
+            }
+            let result = child.compile();
+            let mut dumps = HashMap::new();
+            for (name, buffer) in names.iter().zip(buffers) {
+                if !buffer.is_null() {
+                    let dump = CStr::from_ptr(buffer).to_string_lossy().into_owned();
+                    gccjit_sys::free(buffer as *mut _);
+                    dumps.insert(name.to_string_lossy().into_owned(), dump);
+                }
+            }
+            (result, CompileDumps { dumps })
+        }
+    }
+
+    /// Compiles the context to a shared library in the temporary directory
+    /// and loads it. Unlike a CompileResult, the loaded library can outlive
+    /// this context, and keeps alive the closures imported into it and its
+    /// parents. Fails if the library can't be loaded, such as when
+    /// compiling it failed.
+    #[cfg(all(feature = "dylib", unix))]
+    pub fn compile_to_loaded_library(&self) -> io::Result<LoadedLibrary> {
+        dylib::compile_and_load(self)
+    }
+
     /// Compiles the context and saves the result to a file. The
     /// type of the file is controlled by the OutputKind parameter.
+    ///
+    /// # Panics
+    /// Panics if this context or one of its parents has imported a closure
+    /// with import_closure, since the output would call into this process.
     pub fn compile_to_file<S: AsRef<str>>(&self, kind: OutputKind, file: S) {
+        if self.has_closures() {
+            panic!("contexts with imported closures can't be compiled to a file");
+        }
+        self.compile_to_file_unchecked(kind, file.as_ref())
+    }
+
+    /// Compiles the context to a file like compile_to_file, but allows
+    /// imported closures, for output that is only loaded into this process.
+    pub(crate) fn compile_to_file_unchecked(&self, kind: OutputKind, file: &str) {
         unsafe {
-            let file_ref = file.as_ref();
-            let cstr = CString::new(file_ref).unwrap();
+            let cstr = CString::new(file).unwrap();
             gccjit_sys::gcc_jit_context_compile_to_file(self.ptr,
                                                         mem::transmute(kind),
                                                         cstr.as_ptr());
@@ -184,14 +874,81 @@ impl<'ctx> Context<'ctx> {
     
     
     
+    /// Compiles this context together with other, independently built
+    /// contexts into one dynamic library or executable. The other contexts
+    /// are compiled to object files, which are passed to the driver when
+    /// a child of this context is compiled to the given path, so this
+    /// context's own options apply to the link. Functions of one context
+    /// can call functions of another through FunctionType::Extern
+    /// declarations.
+    ///
+    /// Fails with io::ErrorKind::InvalidInput if kind isn't DynamicLibrary
+    /// or Executable or if any of the contexts or their parents has
+    /// imported a closure, or with the first error of a context that fails
+    /// to compile.
+    pub fn compile_group_to_file<S: AsRef<str>>(&self,
+                                                others: &[&Context],
+                                                kind: OutputKind,
+                                                path: S) -> io::Result<()> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        match kind {
+            OutputKind::DynamicLibrary | OutputKind::Executable => {}
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                           "only dynamic libraries and executables can be linked"))
+        }
+        if self.has_closures() || others.iter().any(|other| other.has_closures()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "contexts with imported closures can't be compiled to a file"));
+        }
+        let failed = |ctx: &Context| {
+            let message = ctx.drain_new_errors().next()
+                .unwrap_or_else(|| "the context failed to compile".to_owned());
+            io::Error::other(message)
+        };
+        let linker = self.new_child_context();
+        let mut objects = vec![];
+        let mut outcome = Ok(());
+        for other in others {
+            let object = env::temp_dir().join(format!("gccjit-group-{}-{}.o",
+                                                      process::id(),
+                                                      NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+            other.compile_to_file(OutputKind::ObjectFile, object.to_string_lossy());
+            if !object.exists() {
+                outcome = Err(failed(other));
+                break;
+            }
+            linker.add_driver_option(object.to_string_lossy());
+            objects.push(object);
+        }
+        if outcome.is_ok() {
+            // an earlier output would hide a failure to write the new one.
+            let path = path.as_ref();
+            let _ = fs::remove_file(path);
+            linker.compile_to_file(kind, path);
+            if !Path::new(path).exists() {
+                outcome = Err(failed(&linker));
+            }
+        }
+        for object in objects {
+            let _ = fs::remove_file(object);
+        }
+        outcome
+    }
+
     /// Creates a new child context from this context. The child context
     /// is a fully-featured context, but it has a lifetime that is strictly
     /// less than the lifetime that spawned it.
     pub fn new_child_context<'b>(&'b self) -> Context<'b> {
+        let config = self.config();
         unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_child_context(self.ptr);
+            tracking::register(ptr, Some(self.ptr));
+            tracking::with_context(ptr, |info| info.config = config);
             Context {
                 marker: PhantomData,
-                ptr: gccjit_sys::gcc_jit_context_new_child_context(self.ptr)
+                ptr,
+                parent: Some(self),
+                closures: RefCell::new(Vec::new())
             }
         }
     }
@@ -223,6 +980,11 @@ impl<'ctx> Context<'ctx> {
         <T as types::Typeable>::get_type(self)
     }
     
+    /// Gets a C type that has no Rust equivalent, such as _Complex double.
+    pub fn new_c_type<'a>(&'a self, ty: CType) -> types::Type<'a> {
+        complex::get_type(self, ty)
+    }
+
     /// Constructs a new field with an optional source location, type, and name.
     /// This field can be used to compose unions or structs.
     pub fn new_field<'a, S: AsRef<str>>(&'a self,
@@ -230,6 +992,7 @@ impl<'ctx> Context<'ctx> {
                          ty: types::Type<'a>,
                          name: S) -> Field<'a> {
         let name_ref = name.as_ref();
+        self.check_provenance(&ty);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -240,16 +1003,52 @@ impl<'ctx> Context<'ctx> {
                                                             loc_ptr,
                                                             types::get_ptr(&ty),
                                                             cstr.as_ptr());
+            tracking::with_context(self.ptr, |info| {
+                info.field_types.insert(ptr, types::get_ptr(&ty));
+                info.field_names.insert(ptr, name_ref.to_owned());
+            });
             field::from_ptr(ptr)
         }
     }
-    
+
+    /// Constructs a new bitfield with the given integral type, width in
+    /// bits, and name. Bitfields can be used in structs and unions just
+    /// like fields created with new_field, but their address can't be
+    /// taken.
+    pub fn new_bitfield<'a, S: AsRef<str>>(&'a self,
+                            loc: Option<Location<'a>>,
+                            ty: types::Type<'a>,
+                            width: i32,
+                            name: S) -> Field<'a> {
+        let name_ref = name.as_ref();
+        self.check_provenance(&ty);
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let cstr = CString::new(name_ref).unwrap();
+            let ptr = gccjit_sys::gcc_jit_context_new_bitfield(self.ptr,
+                                                               loc_ptr,
+                                                               types::get_ptr(&ty),
+                                                               width,
+                                                               cstr.as_ptr());
+            tracking::with_context(self.ptr, |info| {
+                info.field_types.insert(ptr, types::get_ptr(&ty));
+                info.field_widths.insert(ptr, width);
+                info.field_names.insert(ptr, name_ref.to_owned());
+            });
+            field::from_ptr(ptr)
+        }
+    }
+
     /// Constructs a new array type with a given base element type and a
     /// size.
     pub fn new_array_type<'a>(&'a self,
                               loc: Option<Location<'a>>,
                               ty: types::Type<'a>,
                               num_elements: i32) -> types::Type<'a> {
+        self.check_provenance(&ty);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -259,6 +1058,9 @@ impl<'ctx> Context<'ctx> {
                                                                  loc_ptr,
                                                                  types::get_ptr(&ty),
                                                                  num_elements);
+            tracking::with_context(self.ptr, |info| {
+                info.types.insert(ptr, TypeKind::Array(types::get_ptr(&ty), num_elements));
+            });
             types::from_ptr(ptr)
         }
     }
@@ -275,6 +1077,9 @@ impl<'ctx> Context<'ctx> {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
         };
+        for field in fields {
+            self.check_provenance(field);
+        }
         let num_fields = fields.len() as i32;
         let mut fields_ptrs : Vec<_> = fields.iter()
             .map(|x| unsafe { field::get_ptr(&x) })
@@ -286,6 +1091,8 @@ impl<'ctx> Context<'ctx> {
                                                                   cname.as_ptr(),
                                                                   num_fields,
                                                                   fields_ptrs.as_mut_ptr());
+            let ty = gccjit_sys::gcc_jit_struct_as_type(ptr);
+            tracking::record_fields(self.ptr, ty, &fields_ptrs, false);
             structs::from_ptr(ptr)
         }
     }
@@ -294,7 +1101,7 @@ impl<'ctx> Context<'ctx> {
     /// be added to this struct later, but only once.
     pub fn new_opaque_struct_type<'a, S: AsRef<str>>(&'a self,
                                                      loc: Option<Location<'a>>,
-                                                     name: S) -> Struct<'a> {
+                                                     name: S) -> OpaqueStruct<'a> {
         let name_ref = name.as_ref();
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
@@ -305,10 +1112,27 @@ impl<'ctx> Context<'ctx> {
             let ptr = gccjit_sys::gcc_jit_context_new_opaque_struct(self.ptr,
                                                                     loc_ptr,
                                                                     cstr.as_ptr());
-            structs::from_ptr(ptr)
+            let ty = gccjit_sys::gcc_jit_struct_as_type(ptr);
+            tracking::with_context(self.ptr, |info| {
+                info.types.insert(ty, TypeKind::Opaque);
+            });
+            structs::opaque_from_ptr(ptr)
         }
     }
     
+    /// Creates a new enum type with the given name, whose variants are
+    /// constants of the given underlying integer type. The constants can
+    /// be looked up by name through the returned EnumType.
+    pub fn new_enum_type<'a, S: AsRef<str>>(&'a self,
+                                            name: S,
+                                            variants: &[(&str, i64)],
+                                            underlying: types::Type<'a>) -> EnumType<'a> {
+        let variants = variants.iter()
+            .map(|&(name, value)| (name.to_owned(), self.new_rvalue_from_long(underlying, value)))
+            .collect();
+        enums::new(name.as_ref().to_owned(), underlying, variants)
+    }
+
     /// Creates a new union type from a set of fields.
     pub fn new_union_type<'a, S: AsRef<str>>(&'a self,
                                              loc: Option<Location<'a>>,
@@ -319,6 +1143,9 @@ impl<'ctx> Context<'ctx> {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
         };
+        for field in fields {
+            self.check_provenance(field);
+        }
         let num_fields = fields.len() as i32;
         let mut fields_ptrs : Vec<_> = fields.iter()
             .map(|x| unsafe { field::get_ptr(&x) })
@@ -330,6 +1157,7 @@ impl<'ctx> Context<'ctx> {
                                                                  cname.as_ptr(),
                                                                  num_fields,
                                                                  fields_ptrs.as_mut_ptr());
+            tracking::record_fields(self.ptr, ptr, &fields_ptrs, true);
             types::from_ptr(ptr)
         }
     }
@@ -372,10 +1200,14 @@ impl<'ctx> Context<'ctx> {
                                            name: S,
                                            is_variadic: bool) -> Function<'a> {
         let name_ref = name.as_ref();
+        self.check_provenance(&return_ty);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
         };
+        for param in params {
+            self.check_provenance(param);
+        }
         let num_params = params.len() as i32;
         let mut params_ptrs : Vec<_> = params.iter()
             .map(|x| unsafe { parameter::get_ptr(&x) })
@@ -390,10 +1222,252 @@ impl<'ctx> Context<'ctx> {
                                                                num_params,
                                                                params_ptrs.as_mut_ptr(),
                                                                is_variadic as i32);
+            tracking::with_context(self.ptr, |info| {
+                info.add_function(FunctionInfo {
+                    ptr,
+                    name: name_ref.to_owned(),
+                    kind,
+                    return_type: types::get_ptr(&return_ty),
+                    params: params_ptrs,
+                    is_variadic,
+                    blocks: Vec::new(),
+                    locals: Vec::new(),
+                    inline_mode: None
+                });
+            });
             function::from_ptr(ptr)
         }
     }
 
+    /// Creates a function that dispatches to the best of several clones of
+    /// its body for the CPU that it runs on. build is called once for each
+    /// target, given in order of preference as in "avx2,fma" or
+    /// "arch=haswell", and once with None for a fallback clone, and should
+    /// fill in the body of the function that it is given. The parameters
+    /// are given as types and names, since every clone needs its own.
+    ///
+    /// Returns None if libgccjit doesn't provide the CPU detection builtins.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_multiversioned_function<'a, S, F>(&'a self,
+                                                 loc: Option<Location<'a>>,
+                                                 kind: FunctionType,
+                                                 return_ty: types::Type<'a>,
+                                                 params: &[(types::Type<'a>, &str)],
+                                                 name: S,
+                                                 targets: &[&str],
+                                                 build: F) -> Option<Function<'a>>
+        where S: AsRef<str>,
+              F: FnMut(Function<'a>, Option<&str>)
+    {
+        multiversion::new(self, loc, kind, return_ty, params, name.as_ref(), targets, build)
+    }
+
+    /// Creates a function with the given signature that calls target,
+    /// passing its parameters on as described by strategy, so that target
+    /// can be used where a different signature is expected, such as for a
+    /// C callback. The adapter's return value is target's, cast to
+    /// return_ty; if return_ty is void, target's return value is ignored.
+    ///
+    /// # Panics
+    /// Panics if strategy doesn't give exactly one argument for each of
+    /// target's parameters or refers to a parameter that the adapter
+    /// doesn't have, or if target returns void and return_ty doesn't.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_adapter<'a, S: AsRef<str>>(&'a self,
+                                          loc: Option<Location<'a>>,
+                                          kind: FunctionType,
+                                          return_ty: types::Type<'a>,
+                                          params: &[(types::Type<'a>, &str)],
+                                          name: S,
+                                          target: Function<'a>,
+                                          strategy: AdapterStrategy<'_, 'a>) -> Function<'a> {
+        adapter::new(self, loc, kind, return_ty, params, name.as_ref(), target, strategy)
+    }
+
+    /// Creates an exported function that is an alias of target, with the
+    /// same signature, so that both names refer to the same code, as with
+    /// the GNU C alias attribute. target must be defined in this context.
+    /// Returns None if target wasn't created with new_function.
+    pub fn new_function_alias<'a, S: AsRef<str>>(&'a self,
+                                                 loc: Option<Location<'a>>,
+                                                 target: Function<'a>,
+                                                 name: S) -> Option<Function<'a>> {
+        let target_ptr = unsafe { function::get_ptr(&target) };
+        let (target_name, return_ty, param_ptrs, is_variadic) = tracking::with_registry(|reg| {
+            reg.function(target_ptr)
+                .map(|info| (info.name.clone(), info.return_type, info.params.clone(), info.is_variadic))
+        })?;
+        let params : Vec<_> = param_ptrs.into_iter()
+            .map(|param| unsafe {
+                let rvalue = gccjit_sys::gcc_jit_param_as_rvalue(param);
+                let ty = types::from_ptr(gccjit_sys::gcc_jit_rvalue_get_type(rvalue));
+                let name = emit::debug_string(gccjit_sys::gcc_jit_param_as_object(param));
+                self.new_parameter(loc, ty, name)
+            })
+            .collect();
+        let alias = self.new_function(loc, FunctionType::Exported, unsafe { types::from_ptr(return_ty) },
+                                      &params, name, is_variadic);
+        alias.add_attribute(FnAttribute::Alias(&target_name));
+        Some(alias)
+    }
+
+    /// Creates a new global variable with the given linkage, type, and name.
+    /// Exported globals can be accessed after compilation through
+    /// CompileResult::get_global or CompileResult::global.
+    pub fn new_global<'a, S: AsRef<str>>(&'a self,
+                                         loc: Option<Location<'a>>,
+                                         kind: GlobalKind,
+                                         ty: types::Type<'a>,
+                                         name: S) -> LValue<'a> {
+        let name_ref = name.as_ref();
+        self.check_provenance(&ty);
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let cstr = CString::new(name_ref).unwrap();
+            let ptr = gccjit_sys::gcc_jit_context_new_global(self.ptr,
+                                                             loc_ptr,
+                                                             mem::transmute::<GlobalKind, gccjit_sys::gcc_jit_global_kind>(kind),
+                                                             types::get_ptr(&ty),
+                                                             cstr.as_ptr());
+            tracking::with_context(self.ptr, |info| {
+                info.globals.push(GlobalInfo {
+                    name: name_ref.to_owned(),
+                    kind,
+                    ty: types::get_ptr(&ty)
+                });
+            });
+            lvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Declares every function described by decls, in order, and returns
+    /// them in the same order.
+    pub fn declare_functions<'a>(&'a self, decls: &[FnDecl<'a, '_>]) -> Vec<Function<'a>> {
+        decl::functions(self, decls)
+    }
+
+    /// Declares every global described by decls, in order, and returns
+    /// them in the same order.
+    pub fn declare_globals<'a>(&'a self, decls: &[GlobalDecl<'a, '_>]) -> Vec<LValue<'a>> {
+        decl::globals(self, decls)
+    }
+
+    /// Declares every struct described by decls and returns them in the
+    /// same order. Fields can refer to pointers to any of the structs
+    /// being declared with DeclType::PointerTo, including their own.
+    pub fn declare_structs<'a>(&'a self, decls: &[StructDecl<'a, '_>]) -> Vec<Struct<'a>> {
+        decl::structs(self, decls)
+    }
+
+    /// Imports a Rust function so that it can be called from jitted code.
+    /// The gccjit signature of the function is derived from its Rust type.
+    /// This returns an extern declaration of the function with the given
+    /// name, along with an RValue holding the function's address that can
+    /// be called with new_call_through_ptr.
+    ///
+    /// The declaration links against the symbol with the given name, so it
+    /// can only be called directly if the Rust function is exported under
+    /// that name (i.e. with #[no_mangle]). The pointer RValue can always be
+    /// called, and must not outlive the function it points to.
+    pub fn import_function<'a, F: ImportableFn, S: AsRef<str>>(&'a self,
+                                                               name: S,
+                                                               func: F) -> (Function<'a>, RValue<'a>) {
+        let return_ty = F::return_type(self);
+        let param_types = F::param_types(self);
+        let params : Vec<_> = param_types.iter()
+            .enumerate()
+            .map(|(idx, &ty)| self.new_parameter(None, ty, format!("arg{}", idx)))
+            .collect();
+        let decl = self.new_function(None,
+                                     FunctionType::Extern,
+                                     return_ty,
+                                     &params,
+                                     name,
+                                     false);
+        let ptr_ty = self.new_function_pointer_type(None, return_ty, &param_types, false);
+        let ptr = self.new_rvalue_from_ptr(ptr_ty, func.as_ptr());
+        (decl, ptr)
+    }
+
+    /// Imports a Rust closure so that it can be called from jitted code,
+    /// and returns an internal function with the given name and the
+    /// closure's signature that calls it. The closure is kept alive by
+    /// this context and by every CompileResult compiled from it or from
+    /// its children.
+    ///
+    /// The generated code refers to the closure by its address in this
+    /// process, so closures are only for code that is JIT compiled:
+    /// compile_to_file panics and compile_group_to_file fails for this
+    /// context and its children, and the cache module refuses them.
+    /// compile_to_loaded_library still works, since the library is loaded
+    /// into this process and keeps the closures alive.
+    pub fn import_closure<'a, F, S>(&'a self, name: S, closure: Box<F>) -> Function<'a>
+        where F: ?Sized + ImportableClosure + 'static,
+              S: AsRef<str> {
+        let closure = Rc::new(closure);
+        let env = Rc::as_ptr(&closure) as *mut ();
+        self.closures.borrow_mut().push(closure);
+
+        let return_ty = F::return_type(self);
+        let param_types = F::param_types(self);
+        let void_ptr_ty = self.new_type::<*mut ()>();
+        let mut trampoline_params = vec![void_ptr_ty];
+        trampoline_params.extend_from_slice(&param_types);
+        let trampoline_ty = self.new_function_pointer_type(None, return_ty, &trampoline_params, false);
+        let trampoline = self.new_rvalue_from_ptr(trampoline_ty, F::trampoline());
+
+        let params : Vec<_> = param_types.iter()
+            .enumerate()
+            .map(|(idx, &ty)| self.new_parameter(None, ty, format!("arg{}", idx)))
+            .collect();
+        let func = self.new_function(None, FunctionType::Internal, return_ty, &params, name, false);
+        let mut args = vec![self.new_rvalue_from_ptr(void_ptr_ty, env)];
+        args.extend(params.iter().map(|param| param.to_rvalue()));
+        let block = func.new_block("entry");
+        let call = self.new_call_through_ptr(None, trampoline, &args);
+        if return_ty.is_void() {
+            block.add_eval(None, call);
+            block.end_with_void_return(None);
+        } else {
+            block.end_with_return(None, call);
+        }
+        func
+    }
+
+    /// Imports a host function or global into this context in one step, and
+    /// registers it under the given name so that it can be found again
+    /// with lookup_symbol, here or in a child context. Functions are
+    /// imported as pointers of the function pointer type derived from
+    /// their Rust type, and pointers to values as globals at that address.
+    /// A symbol registered under a name that is already taken replaces it.
+    ///
+    /// The symbol must outlive every CompileResult that uses it.
+    pub fn register_symbol<'a, T: HostSymbol, S: Into<String>>(&'a self, name: S, symbol: T) -> Symbol<'a> {
+        let symbol = symbol.import(self);
+        let info = match symbol {
+            Symbol::Function(ptr) => SymbolInfo::Function(unsafe { rvalue::get_ptr(&ptr) }),
+            Symbol::Global(global) => SymbolInfo::Global(unsafe { lvalue::get_ptr(&global) })
+        };
+        let name = name.into();
+        tracking::with_context(self.ptr, |ctx| ctx.symbols.insert(name, info));
+        symbol
+    }
+
+    /// Looks up a symbol registered with register_symbol on this context
+    /// or one of its parents.
+    pub fn lookup_symbol<'a>(&'a self, name: &str) -> Option<Symbol<'a>> {
+        let ctx = self.ptr;
+        tracking::with_registry(|reg| {
+            reg.symbol(ctx, name).map(|info| match *info {
+                SymbolInfo::Function(ptr) => Symbol::Function(unsafe { rvalue::from_ptr(ptr) }),
+                SymbolInfo::Global(ptr) => Symbol::Global(unsafe { lvalue::from_ptr(ptr) })
+            })
+        })
+    }
+
     /// Creates a new binary operation between two RValues and produces a new RValue.
     pub fn new_binary_op<'a, L: ToRValue<'a>, R: ToRValue<'a>>(&'a self,
                                                                loc: Option<Location<'a>>,
@@ -403,6 +1477,9 @@ impl<'ctx> Context<'ctx> {
                                                                right: R) -> RValue<'a> {
         let left_rvalue = left.to_rvalue();
         let right_rvalue = right.to_rvalue();
+        self.check_provenance(&ty);
+        self.check_provenance(&left_rvalue);
+        self.check_provenance(&right_rvalue);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -425,6 +1502,8 @@ impl<'ctx> Context<'ctx> {
                                              ty: types::Type<'a>,
                                              target: T) -> RValue<'a> {
         let rvalue = target.to_rvalue();
+        self.check_provenance(&ty);
+        self.check_provenance(&rvalue);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -446,6 +1525,8 @@ impl<'ctx> Context<'ctx> {
                                                                 right: R) -> RValue<'a> {
         let left_rvalue = left.to_rvalue();
         let right_rvalue = right.to_rvalue();
+        self.check_provenance(&left_rvalue);
+        self.check_provenance(&right_rvalue);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -466,15 +1547,20 @@ impl<'ctx> Context<'ctx> {
     /// not possible to be generic over different types of arguments (RValues
     /// together with LValues and Parameters, for example), so in order to
     /// mix the types of the arguments it may be necessary to call to_rvalue()
-    /// before calling this function.
+    /// before calling this function, or to use new_call_with or the call!
+    /// macro instead.
     pub fn new_call<'a>(&'a self,
                         loc: Option<Location<'a>>,
                         func: Function<'a>,
                         args: &[RValue<'a>]) -> RValue<'a> {
+        self.check_provenance(&func);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
         };
+        for arg in args {
+            self.check_provenance(arg);
+        }
         let num_params = args.len() as i32;
         let mut params_ptrs : Vec<_> = args.iter()
             .map(|x| unsafe { rvalue::get_ptr(&x) })
@@ -485,10 +1571,30 @@ impl<'ctx> Context<'ctx> {
                                                            function::get_ptr(&func),
                                                            num_params,
                                                            params_ptrs.as_mut_ptr());
+            tracking::with_context(self.ptr, |info| {
+                if info.config.tracking {
+                    info.calls.push(CallInfo {
+                        loc: loc_ptr,
+                        function: function::get_ptr(&func),
+                        args: params_ptrs
+                    });
+                }
+            });
             rvalue::from_ptr(ptr)
         }
     }
 
+    /// Like new_call, but takes arguments of mixed kinds, such as
+    /// parameters, lvalues, and rvalues, without converting each of them
+    /// with to_rvalue first: &[&param, &local, &value].
+    pub fn new_call_with<'a>(&'a self,
+                             loc: Option<Location<'a>>,
+                             func: Function<'a>,
+                             args: &[&dyn ToRValue<'a>]) -> RValue<'a> {
+        let args : Vec<_> = args.iter().map(|arg| arg.to_rvalue()).collect();
+        self.new_call(loc, func, &args)
+    }
+
     /// Creates an indirect function call that dereferences a function pointer and
     /// attempts to invoke it with the given arguments. The RValue that is returned
     /// is the result of the function call.
@@ -497,6 +1603,7 @@ impl<'ctx> Context<'ctx> {
                                                      fun_ptr: F,
                                                      args: &[RValue<'a>]) -> RValue<'a> {
         let fun_ptr_rvalue = fun_ptr.to_rvalue();
+        self.check_provenance(&fun_ptr_rvalue);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -522,6 +1629,8 @@ impl<'ctx> Context<'ctx> {
                                          value: T,
                                          dest_type: types::Type<'a>) -> RValue<'a> {
         let rvalue = value.to_rvalue();
+        self.check_provenance(&rvalue);
+        self.check_provenance(&dest_type);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -535,6 +1644,28 @@ impl<'ctx> Context<'ctx> {
         }
     }
 
+    /// Reinterprets the bits of a value as a value of another type of the
+    /// same size, like transmute in Rust.
+    pub fn new_bitcast<'a, T: ToRValue<'a>>(&'a self,
+                                            loc: Option<Location<'a>>,
+                                            value: T,
+                                            dest_type: types::Type<'a>) -> RValue<'a> {
+        let rvalue = value.to_rvalue();
+        self.check_provenance(&rvalue);
+        self.check_provenance(&dest_type);
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_bitcast(self.ptr,
+                                                              loc_ptr,
+                                                              rvalue::get_ptr(&rvalue),
+                                                              types::get_ptr(&dest_type));
+            rvalue::from_ptr(ptr)
+        }
+    }
+
     /// Creates an LValue from an array pointer and an offset. The LValue can be the target
     /// of an assignment, or it can be converted into an RValue (i.e. loaded).
     pub fn new_array_access<'a, A: ToRValue<'a>, I: ToRValue<'a>>(&'a self,
@@ -543,6 +1674,8 @@ impl<'ctx> Context<'ctx> {
                                                                   index: I) -> LValue<'a> {
         let array_rvalue = array_ptr.to_rvalue();
         let idx_rvalue = index.to_rvalue();
+        self.check_provenance(&array_rvalue);
+        self.check_provenance(&idx_rvalue);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -564,19 +1697,157 @@ impl<'ctx> Context<'ctx> {
             let ptr = gccjit_sys::gcc_jit_context_new_rvalue_from_long(self.ptr,
                                                                        types::get_ptr(&ty),
                                                                        value);
+            tracking::record_int_constant(self.ptr, ptr, value);
             rvalue::from_ptr(ptr)
         }
     }
 
-    /// Creates a new RValue from a given int value.
-    pub fn new_rvalue_from_int<'a>(&'a self,
-                                   ty: types::Type<'a>,
-                                   value: i32) -> RValue<'a> {
-
-        unsafe {
-            let ptr = gccjit_sys::gcc_jit_context_new_rvalue_from_int(self.ptr,
+    /// Creates a vector RValue of the given vector type from its elements.
+    ///
+    /// # Panics
+    /// Panics if the number of elements doesn't match the vector type.
+    pub fn new_rvalue_from_vector<'a>(&'a self,
+                                      loc: Option<Location<'a>>,
+                                      vec_type: types::Type<'a>,
+                                      elements: &[RValue<'a>]) -> RValue<'a> {
+        self.check_provenance(&vec_type);
+        for element in elements {
+            self.check_provenance(element);
+        }
+        let num_units = tracking::with_registry(|reg| {
+            match reg.type_kind(reg.unqualified(unsafe { types::get_ptr(&vec_type) })) {
+                Some(&TypeKind::Vector(_, num_units)) => Some(num_units),
+                _ => None
+            }
+        });
+        if let Some(num_units) = num_units {
+            assert_eq!(num_units, elements.len(), "wrong number of elements for {:?}", vec_type);
+        }
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        let mut elements_ptrs : Vec<_> = elements.iter()
+            .map(|x| unsafe { rvalue::get_ptr(x) })
+            .collect();
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_rvalue_from_vector(self.ptr,
+                                                                         loc_ptr,
+                                                                         types::get_ptr(&vec_type),
+                                                                         elements_ptrs.len() as _,
+                                                                         elements_ptrs.as_mut_ptr());
+            rvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Converts each element of a vector to the element type of another
+    /// vector type with the same number of elements, such as to widen a
+    /// vector of 16 u8s to a vector of 16 u16s.
+    #[cfg(feature = "master")]
+    pub fn convert_vector<'a, T: ToRValue<'a>>(&'a self,
+                                               loc: Option<Location<'a>>,
+                                               vector: T,
+                                               target_vec_type: types::Type<'a>) -> RValue<'a> {
+        let rvalue = vector.to_rvalue();
+        self.check_provenance(&rvalue);
+        self.check_provenance(&target_vec_type);
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_convert_vector(self.ptr,
+                                                                 loc_ptr,
+                                                                 rvalue::get_ptr(&rvalue),
+                                                                 types::get_ptr(&target_vec_type));
+            rvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Gets the type of the masks that comparing vectors of the given
+    /// vector type gives: a vector of signed integers of the same size as
+    /// its elements. Returns None if the type isn't a vector type created
+    /// with Type::make_vector.
+    pub fn new_vector_mask_type<'a>(&'a self, vec_ty: types::Type<'a>) -> Option<types::Type<'a>> {
+        vector::mask_type(self, vec_ty)
+    }
+
+    /// Compares two vectors element by element, giving a mask vector (of
+    /// the type from new_vector_mask_type) whose elements are -1 where the
+    /// comparison holds and 0 where it doesn't.
+    ///
+    /// # Panics
+    /// Panics if the values aren't vectors.
+    #[cfg(feature = "master")]
+    pub fn new_vector_comparison<'a, L: ToRValue<'a>, R: ToRValue<'a>>(&'a self,
+                                                                       loc: Option<Location<'a>>,
+                                                                       op: ComparisonOp,
+                                                                       lhs: L,
+                                                                       rhs: R) -> RValue<'a> {
+        vector::comparison(self, loc, op, lhs.to_rvalue(), rhs.to_rvalue())
+    }
+
+    /// Picks each element of the result from on_true where the element of
+    /// mask is all ones, and from on_false where it is all zeros, using
+    /// bitwise operations rather than branches. The mask can be of any
+    /// vector type of the same size as the values.
+    ///
+    /// # Panics
+    /// Panics if the values aren't vectors.
+    pub fn new_vector_select<'a, M, T, F>(&'a self,
+                                          loc: Option<Location<'a>>,
+                                          mask: M,
+                                          on_true: T,
+                                          on_false: F) -> RValue<'a>
+        where M: ToRValue<'a>,
+              T: ToRValue<'a>,
+              F: ToRValue<'a>
+    {
+        vector::select(self, loc, mask.to_rvalue(), on_true.to_rvalue(), on_false.to_rvalue())
+    }
+
+    /// Creates a constant of a complex type from its real and imaginary
+    /// parts.
+    ///
+    /// # Panics
+    /// Panics if the type isn't complex float or complex double.
+    pub fn new_rvalue_from_complex<'a>(&'a self,
+                                       ty: types::Type<'a>,
+                                       re: f64,
+                                       im: f64) -> RValue<'a> {
+        complex::new_rvalue(self, ty, re, im)
+    }
+
+    /// Gets the real part of a complex value, like creal in C.
+    ///
+    /// # Panics
+    /// Panics if the value isn't complex.
+    pub fn new_complex_real<'a, T: ToRValue<'a>>(&'a self,
+                                                 loc: Option<Location<'a>>,
+                                                 value: T) -> RValue<'a> {
+        complex::part(self, loc, value.to_rvalue(), "creal")
+    }
+
+    /// Gets the imaginary part of a complex value, like cimag in C.
+    ///
+    /// # Panics
+    /// Panics if the value isn't complex.
+    pub fn new_complex_imag<'a, T: ToRValue<'a>>(&'a self,
+                                                 loc: Option<Location<'a>>,
+                                                 value: T) -> RValue<'a> {
+        complex::part(self, loc, value.to_rvalue(), "cimag")
+    }
+
+    /// Creates a new RValue from a given int value.
+    pub fn new_rvalue_from_int<'a>(&'a self,
+                                   ty: types::Type<'a>,
+                                   value: i32) -> RValue<'a> {
+
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_rvalue_from_int(self.ptr,
                                                                       types::get_ptr(&ty),
                                                                       value);
+            tracking::record_int_constant(self.ptr, ptr, value as i64);
             rvalue::from_ptr(ptr)
         }
     }
@@ -599,6 +1870,7 @@ impl<'ctx> Context<'ctx> {
         unsafe {
             let ptr = gccjit_sys::gcc_jit_context_zero(self.ptr,
                                                        types::get_ptr(&ty));
+            tracking::record_int_constant(self.ptr, ptr, 0);
             rvalue::from_ptr(ptr)
         }
     }
@@ -609,6 +1881,55 @@ impl<'ctx> Context<'ctx> {
         unsafe {
             let ptr = gccjit_sys::gcc_jit_context_one(self.ptr,
                                                       types::get_ptr(&ty));
+            tracking::record_int_constant(self.ptr, ptr, 1);
+            rvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Creates a case for Block::end_with_switch that jumps to dest when
+    /// the switch's value is between min and max, inclusive. min and max
+    /// must be integer constants of the type of the switch's value, and
+    /// the cases of a switch must not overlap.
+    pub fn new_case<'a, S: ToRValue<'a>, T: ToRValue<'a>>(&'a self,
+                                                        min: S,
+                                                        max: T,
+                                                        dest: Block<'a>) -> Case<'a> {
+        let min_rvalue = min.to_rvalue();
+        let max_rvalue = max.to_rvalue();
+        self.check_provenance(&min_rvalue);
+        self.check_provenance(&max_rvalue);
+        self.check_provenance(&dest);
+        unsafe {
+            let min_ptr = rvalue::get_ptr(&min_rvalue);
+            let max_ptr = rvalue::get_ptr(&max_rvalue);
+            let dest_ptr = block::get_ptr(&dest);
+            let ptr = gccjit_sys::gcc_jit_context_new_case(self.ptr, min_ptr, max_ptr, dest_ptr);
+            case::from_ptr(ptr, CaseInfo {
+                min: min_ptr,
+                max: max_ptr,
+                dest: dest_ptr
+            })
+        }
+    }
+
+    /// Creates an int RValue holding the size in bytes of a given type on
+    /// the target, like sizeof in C.
+    pub fn new_sizeof<'a>(&'a self,
+                          ty: types::Type<'a>) -> RValue<'a> {
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_sizeof(self.ptr,
+                                                             types::get_ptr(&ty));
+            rvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Creates an int RValue holding the alignment in bytes of a given type
+    /// on the target, like alignof in C.
+    pub fn new_alignof<'a>(&'a self,
+                           ty: types::Type<'a>) -> RValue<'a> {
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_context_new_alignof(self.ptr,
+                                                              types::get_ptr(&ty));
             rvalue::from_ptr(ptr)
         }
     }
@@ -648,6 +1969,106 @@ impl<'ctx> Context<'ctx> {
         }
     }
 
+    /// Creates a pointer to a NUL-terminated copy of the given bytes, which
+    /// unlike new_string_literal may contain interior NULs. The bytes are
+    /// stored in an internal global char array, and the returned RValue has
+    /// the same type as a string literal (const char *).
+    pub fn new_byte_string_literal<'a>(&'a self, bytes: &[u8]) -> RValue<'a> {
+        let mut data = bytes.to_vec();
+        data.push(0);
+        let char_ty = self.new_type::<char>();
+        let array_ty = self.new_array_type(None, char_ty, data.len() as i32);
+        // the globals are numbered per context, and by the depth of the
+        // context so that they don't clash with those of its parents, so
+        // that contexts built the same way name them the same.
+        let ctx = self.ptr;
+        let name = tracking::with_registry(|reg| {
+            let depth = reg.depth(ctx);
+            format!("__gccjit_bytes_{}_{}", depth, reg.context(ctx).next_internal_global())
+        });
+        let global = self.new_global(None, GlobalKind::Internal, array_ty, name);
+        unsafe {
+            gccjit_sys::gcc_jit_global_set_initializer(lvalue::get_ptr(&global),
+                                                       data.as_ptr() as *const _,
+                                                       data.len() as _);
+        }
+        let first = self.new_array_access(None, global, self.new_rvalue_zero(self.new_type::<i32>()));
+        self.new_cast(None, first.get_address(None), char_ty.make_const().make_pointer())
+    }
+
+    /// Checks everything that has been built in this context against the
+    /// rules that gccjit enforces when compiling - every block ends in a
+    /// terminator and is reachable, assigned, returned, and passed values
+    /// have compatible types, the cases of each switch are ordered ranges
+    /// that don't overlap, and fields are only accessed on the struct that
+    /// owns them. Unlike gccjit, which stops at the first error it
+    /// encounters, every violation that is found is reported.
+    ///
+    /// These checks are performed on the Rust side using what this library
+    /// has recorded about the context, so they are best-effort: types that
+    /// weren't constructed through this library are assumed to be correct.
+    /// The checks of statements, calls, and field accesses need tracking to
+    /// be enabled with set_tracking before the code is built; if it isn't,
+    /// verify reports a ViolationKind::TrackingDisabled violation instead.
+    pub fn verify(&self) -> Result<(), Vec<Violation>> {
+        verify::verify(self.ptr)
+    }
+
+    /// Writes a C header to the given path that declares the exported
+    /// functions and globals of this context, along with the structs and
+    /// unions that they use. The header can be used to link C code against
+    /// the output of compile_to_file. Types that weren't constructed through
+    /// this library are spelled the way gccjit prints them.
+    pub fn write_c_header<S: AsRef<str>>(&self, path: S) -> io::Result<()> {
+        let path = Path::new(path.as_ref());
+        fs::write(path, emit::c_header(self.ptr, path))
+    }
+
+    /// Writes Rust bindings to the given path for the exported functions
+    /// and globals of this context, so that a build script can generate
+    /// them next to the library written by compile_to_file and include!
+    /// them. The bindings contain #[repr(C)] definitions of the structs and
+    /// unions that the symbols use, extern declarations in an ffi module,
+    /// and wrappers that call them: a function for each non-variadic
+    /// function, which is unsafe if the function takes pointers, and a
+    /// <name>_ptr function for each global. Symbols whose types have no
+    /// Rust equivalent are skipped with a comment.
+    pub fn write_rust_bindings<S: AsRef<str>>(&self, path: S) -> io::Result<()> {
+        fs::write(path.as_ref(), emit::rust_bindings(self.ptr))
+    }
+
+    /// Lists the structs, unions, globals, and functions created so far,
+    /// with the statements of each block of each function, in a C-like
+    /// syntax. Unlike gccjit's dumps, the listing doesn't need the context
+    /// to be compiled and shows the statements in the order they were added.
+    /// Statements are only listed if tracking was enabled with set_tracking
+    /// when they were added.
+    pub fn pretty_print(&self) -> String {
+        let ctx = self.ptr;
+        tracking::with_registry(|reg| {
+            reg.get_context(ctx).map_or_else(String::new, |info| pretty::listing(reg, info))
+        })
+    }
+
+    /// Writes the graph rendered by Function::to_dot for every function
+    /// defined in this context to <name>.dot in the given directory, which
+    /// is created if needed, along with an index.dot that links to them.
+    pub fn dump_all_to_dot<P: AsRef<Path>>(&self, dir: P) -> io::Result<()> {
+        let dir = dir.as_ref();
+        let ctx = self.ptr;
+        let graphs = tracking::with_registry(|reg| {
+            match reg.get_context(ctx) {
+                Some(info) => dot::context_graphs(reg, info),
+                None => vec![]
+            }
+        });
+        fs::create_dir_all(dir)?;
+        for (file, graph) in graphs {
+            fs::write(dir.join(file), graph)?;
+        }
+        Ok(())
+    }
+
     /// Dumps a small C file to the path that can be used to reproduce a series
     /// of API calls. You should only ever need to call this if you are debugging
     /// an issue in gccjit itself or this library.
@@ -667,6 +2088,7 @@ impl<'ctx> Context<'ctx> {
                                             ty: types::Type<'a>,
                                             name: S) -> Parameter<'a> {
         let name_ref = name.as_ref();
+        self.check_provenance(&ty);
         let loc_ptr = match loc {
             Some(loc) => unsafe { location::get_ptr(&loc) },
             None => ptr::null_mut()
@@ -681,6 +2103,92 @@ impl<'ctx> Context<'ctx> {
         }
     }
 
+    /// Gets a helper that looks up math builtins such as sqrt and pow by
+    /// the floating point type that they should operate on.
+    pub fn builtin_math<'a>(&'a self) -> BuiltinMath<'a> {
+        math::new(self)
+    }
+
+    /// Gets a helper that counts the bits of integers with builtins such as
+    /// popcount and clz, picking the variant for the integer type.
+    pub fn builtin_bits<'a>(&'a self) -> BuiltinBits<'a> {
+        bits::new(self)
+    }
+
+    /// Tells GCC whether cond is likely to be true, like __builtin_expect,
+    /// so that it can lay out the code that is expected to run as the fast
+    /// path. The result has the same value and type as cond, and should be
+    /// used in its place, such as in Block::end_with_conditional.
+    pub fn expect<'a, T: ToRValue<'a>>(&'a self,
+                                       loc: Option<Location<'a>>,
+                                       cond: T,
+                                       likely: bool) -> RValue<'a> {
+        block::expect(loc, cond.to_rvalue(), likely)
+    }
+
+    /// Picks on_true if cond is true and on_false otherwise, like the ?:
+    /// operator in C, except that both values are always evaluated. With
+    /// Lowering::Branchy, or for types other than integers, the select
+    /// branches, so it ends the given block and the returned block is
+    /// where the result becomes available and code generation should
+    /// continue. With Lowering::Branchless, integer selects are computed
+    /// with bitwise operations and the given block is returned.
+    pub fn new_select<'a, C, T, F>(&'a self,
+                                   loc: Option<Location<'a>>,
+                                   block: Block<'a>,
+                                   cond: C,
+                                   on_true: T,
+                                   on_false: F) -> (Block<'a>, RValue<'a>)
+        where C: ToRValue<'a>,
+              T: ToRValue<'a>,
+              F: ToRValue<'a>
+    {
+        select::select(self, loc, block, cond.to_rvalue(), on_true.to_rvalue(), on_false.to_rvalue())
+    }
+
+    /// Gets a helper that declares functions of the C library, such as
+    /// putchar and malloc, with their C signatures.
+    pub fn libc<'a>(&'a self) -> Libc<'a> {
+        libc::new(self)
+    }
+
+    /// Starts reading the variadic arguments of the function that owns the
+    /// given block, like va_start in C. The function must have been created
+    /// as variadic through new_function and have at least one named
+    /// parameter. Returns None if that isn't the case, if the va_list layout
+    /// of the host isn't known, or if libgccjit doesn't provide va_start.
+    pub fn va_start<'a>(&'a self,
+                        loc: Option<Location<'a>>,
+                        block: Block<'a>) -> Option<VaList<'a>> {
+        varargs::start(self, loc, block)
+    }
+
+    /// Reads the next variadic argument of the given type, like va_arg in
+    /// C. Reading an argument requires branching, so the read is started
+    /// at the end of the given block (which is terminated if necessary) and
+    /// the returned block is where the returned value becomes available and
+    /// code generation should continue.
+    ///
+    /// Only integer, pointer, and floating point types can be read. Since
+    /// arguments undergo the default promotions when passed through an
+    /// ellipsis, integers narrower than int should be read as int. Floats
+    /// are read as doubles and converted. Returns None for other types.
+    pub fn va_arg<'a>(&'a self,
+                      loc: Option<Location<'a>>,
+                      block: Block<'a>,
+                      list: &VaList<'a>,
+                      ty: types::Type<'a>) -> Option<(Block<'a>, RValue<'a>)> {
+        varargs::arg(self, loc, block, list, ty)
+    }
+
+    /// Finishes reading variadic arguments, like va_end in C.
+    pub fn va_end<'a>(&'a self,
+                      loc: Option<Location<'a>>,
+                      block: Block<'a>,
+                      list: &VaList<'a>) {
+        varargs::end(self, loc, block, list)
+    }
+
     /// Get a builtin function from gcc. It's not clear what functions are
     /// builtin and you'll likely need to consult the GCC internal docs
     /// for a full list.
@@ -695,11 +2203,21 @@ impl<'ctx> Context<'ctx> {
     }
 }
 
+/// Checks the value given to one of the typed option setters, which would
+/// otherwise only be reported, if at all, when compilation fails.
+fn checked_option_value<'a>(setter: &str, value: &'a str) -> &'a str {
+    if !config::is_valid_option_value(value) {
+        panic!("{} was given {:?}, which can't be passed to gcc as a single option value", setter, value);
+    }
+    value
+}
+
 impl<'ctx> Drop for Context<'ctx> {
     fn drop(&mut self) {
         unsafe {
             gccjit_sys::gcc_jit_context_release(self.ptr);
         }
+        tracking::release(self.ptr);
     }
 }
 
@@ -766,6 +2284,1450 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exported_global() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let counter = context.new_global(None, GlobalKind::Exported, int_ty, "counter");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "bump", false);
+        let block = fun.new_block("main_block");
+        block.add_assignment_op(None, counter, BinaryOp::Plus, context.new_rvalue_one(int_ty));
+        block.end_with_return(None, counter.to_rvalue());
+
+        let result = context.compile();
+        unsafe {
+            assert!(result.global::<i32, _>("missing").is_none());
+            let global = result.global::<i32, _>("counter").unwrap();
+            global.write(41);
+            let bump : extern "C" fn() -> i32 = mem::transmute(result.get_function("bump"));
+            assert_eq!(bump(), 42);
+            assert_eq!(global.read(), 42);
+        }
+    }
+
+    #[test]
+    fn variadic_arguments() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let double_ty = context.new_type::<f64>();
+        let count = context.new_parameter(None, int_ty, "count");
+        let fun = context.new_function(None, FunctionType::Exported, double_ty, &[count], "mix", true);
+        let block = fun.new_block("main_block");
+        let list = context.va_start(None, block).unwrap();
+        let (block, x) = context.va_arg(None, block, &list, int_ty).unwrap();
+        let (block, y) = context.va_arg(None, block, &list, double_ty).unwrap();
+        context.va_end(None, block, &list);
+        block.end_with_return(None, context.new_cast(None, x, double_ty) + y);
+
+        let result = context.compile();
+        unsafe {
+            let mix : unsafe extern "C" fn(i32, ...) -> f64 = mem::transmute(result.get_function("mix"));
+            assert_eq!(mix(2, 40i32, 2.5f64), 42.5);
+        }
+    }
+
+    #[test]
+    fn c_header_declares_exports() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let x = context.new_field(None, int_ty, "x");
+        let point = context.new_struct_type(None, "point", &[x]).as_type();
+        let parameter = context.new_parameter(None, point.make_pointer(), "p");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "get_x", false);
+        let block = fun.new_block("main_block");
+        block.end_with_return(None, fun.get_param(0).to_rvalue().dereference_field(None, x));
+        context.new_global(None, GlobalKind::Exported, int_ty, "counter");
+
+        let path = ::std::env::temp_dir().join("gccjit_rs_exports.h");
+        context.write_c_header(path.to_str().unwrap()).unwrap();
+        let header = ::std::fs::read_to_string(&path).unwrap();
+        assert!(header.contains("#ifndef GCCJIT_RS_EXPORTS_H"));
+        assert!(header.contains("struct point {\n    int x;\n};"));
+        assert!(header.contains("int get_x(struct point *p);"));
+        assert!(header.contains("extern int counter;"));
+    }
+
+    #[test]
+    fn typed_options() {
+        let context = Context::default();
+        context.set_march("native");
+        context.set_mtune("generic");
+        context.link_library("m");
+        context.add_library_path("/opt/lib");
+        let config = context.config();
+        assert_eq!(config.command_line_options, ["-march=native", "-mtune=generic"]);
+        assert_eq!(config.driver_options, ["-lm", "-L/opt/lib"]);
+
+        context.set_lto(true);
+        assert!(context.config().command_line_options.contains(&"-flto".to_owned()));
+        assert_eq!(context.config().driver_options.last().unwrap(), "-flto");
+
+        context.set_position_independent(PicLevel::Pie);
+        assert!(context.config().command_line_options.contains(&"-fPIE".to_owned()));
+        assert_eq!(context.config().driver_options.last().unwrap(), "-pie");
+
+        context.set_stack_protector(StackProtector::Strong);
+        context.set_stack_clash_protection(true);
+        assert!(context.config().command_line_options.ends_with(&["-fstack-protector-strong".to_owned(),
+                                                                  "-fstack-clash-protection".to_owned()]));
+
+        context.enable_sanitizer(Sanitizer::Undefined);
+        assert!(context.config().command_line_options.contains(&"-fsanitize=undefined".to_owned()));
+        assert_eq!(context.config().driver_options.last().unwrap(), "-fsanitize=undefined");
+
+        let notes = context.enable_coverage("/tmp/coverage");
+        assert_eq!(notes.to_str().unwrap(), format!("/tmp/coverage/context-{}.gcno", context.id()));
+        assert!(context.config().command_line_options.contains(&"-fprofile-dir=/tmp/coverage".to_owned()));
+
+        context.set_debug_level(DebugLevel::G3);
+        context.set_debug_format(DebugFormat::Dwarf4);
+        assert!(context.config().debug_info);
+        assert!(context.config().command_line_options.ends_with(&["-g3".to_owned(), "-gdwarf-4".to_owned()]));
+
+        context.set_use_external_driver(true);
+        assert!(context.config().use_external_driver);
+
+        context.set_profile_use("/tmp/profile");
+        assert!(context.config().command_line_options.contains(&"-fprofile-use=/tmp/profile".to_owned()));
+    }
+
+    #[test]
+    #[should_panic(expected = "bare name of a library")]
+    fn link_library_rejects_file_names() {
+        Context::default().link_library("libm.so");
+    }
+
+    #[test]
+    fn apply_profile() {
+        let context = Context::default();
+        context.apply_profile(Profile::ReleaseFast);
+        let config = context.config();
+        assert_eq!(config.optimization_level, OptimizationLevel::Aggressive);
+        assert!(!config.debug_info);
+        assert!(config.command_line_options.contains(&"-ffast-math".to_owned()));
+
+        let child = context.new_child_context();
+        assert_eq!(child.config(), config);
+    }
+
+    #[test]
+    fn sizeof_struct() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fields : Vec<_> = (0..3).map(|i| context.new_field(None, int_ty, format!("f{}", i))).collect();
+        let triple = context.new_struct_type(None, "triple", &fields).as_type();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "size", false);
+        let block = fun.new_block("main_block");
+        block.end_with_return(None, context.new_sizeof(triple));
+
+        let result = context.compile();
+        let size : extern "C" fn() -> i32 = unsafe { mem::transmute(result.get_function("size")) };
+        assert_eq!(size(), 12);
+    }
+
+    #[test]
+    fn alignof_struct() {
+        let context = Context::default();
+        let char_ty = context.new_type::<u8>();
+        let double_ty = context.new_type::<f64>();
+        let tag = context.new_field(None, char_ty, "tag");
+        let value = context.new_field(None, double_ty, "value");
+        let tagged = context.new_struct_type(None, "tagged", &[tag, value]).as_type();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "align", false);
+        let block = fun.new_block("main_block");
+        block.end_with_return(None, context.new_alignof(tagged));
+
+        let result = context.compile();
+        let align : extern "C" fn() -> i32 = unsafe { mem::transmute(result.get_function("align")) };
+        assert_eq!(align() as usize, mem::align_of::<f64>());
+    }
+
+    #[test]
+    fn bitfield_struct() {
+        let context = Context::default();
+        let uint_ty = context.new_type::<u32>();
+        let ready = context.new_bitfield(None, uint_ty, 1, "ready");
+        let mode = context.new_bitfield(None, uint_ty, 3, "mode");
+        let flags = context.new_struct_type(None, "flags", &[ready, mode]).as_type();
+        let parameter = context.new_parameter(None, uint_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, uint_ty, &[parameter], "truncate", false);
+        let block = fun.new_block("main_block");
+        let local = fun.new_local(None, flags, "local");
+        block.add_assignment(None, local.access_field(None, mode), fun.get_param(0));
+        block.end_with_return(None, local.access_field(None, mode));
+
+        let result = context.compile();
+        let truncate : extern "C" fn(u32) -> u32 = unsafe { mem::transmute(result.get_function("truncate")) };
+        assert_eq!(truncate(13), 5);
+    }
+
+    #[test]
+    fn rust_bindings_declare_exports() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "square", false);
+        let block = fun.new_block("main_block");
+        let parm = fun.get_param(0).to_rvalue();
+        block.end_with_return(None, parm * parm);
+
+        let path = ::std::env::temp_dir().join("gccjit_rs_exports.rs");
+        context.write_rust_bindings(path.to_str().unwrap()).unwrap();
+        let bindings = ::std::fs::read_to_string(&path).unwrap();
+        assert!(bindings.contains("pub fn square(x: ::std::os::raw::c_int) -> ::std::os::raw::c_int;"));
+        assert!(bindings.contains("pub fn square(x: ::std::os::raw::c_int) -> ::std::os::raw::c_int {"));
+    }
+
+    #[test]
+    fn byte_string_with_interior_nul() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "last_byte", false);
+        let block = fun.new_block("main_block");
+        let bytes = context.new_byte_string_literal(b"a\0bc");
+        let last = context.new_array_access(None, bytes, context.new_rvalue_from_int(int_ty, 3));
+        block.end_with_return(None, context.new_cast(None, last, int_ty));
+
+        let result = context.compile();
+        unsafe {
+            let last_byte : extern "C" fn() -> i32 = mem::transmute(result.get_function("last_byte"));
+            assert_eq!(last_byte(), b'c' as i32);
+        }
+    }
+
+    #[test]
+    fn byte_strings_of_parent_and_child() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let first = context.new_byte_string_literal(b"x");
+        let child = context.new_child_context();
+        let second = child.new_byte_string_literal(b"y");
+        let fun = child.new_function(None, FunctionType::Exported, int_ty, &[], "sum", false);
+        let zero = child.new_rvalue_zero(int_ty);
+        let sum = child.new_binary_op(None, BinaryOp::Plus, int_ty,
+                                      child.new_cast(None, child.new_array_access(None, first, zero), int_ty),
+                                      child.new_cast(None, child.new_array_access(None, second, zero), int_ty));
+        fun.new_block("entry").end_with_return(None, sum);
+
+        let result = child.compile();
+        let sum = unsafe { result.function::<extern "C" fn() -> i32, _>("sum") }.unwrap();
+        assert_eq!(sum(), (b'x' + b'y') as i32);
+    }
+
+    #[test]
+    #[cfg(all(feature = "dylib", unix))]
+    fn loaded_library_outlives_context() {
+        let library = {
+            let context = Context::default();
+            let int_ty = context.new_type::<i32>();
+            let x = context.new_parameter(None, int_ty, "x");
+            let fun = context.new_function(None, FunctionType::Exported, int_ty, &[x], "negate", false);
+            fun.new_block("entry").end_with_return(None, context.new_unary_op(None, UnaryOp::Minus, int_ty, x));
+            context.new_global(None, GlobalKind::Exported, int_ty, "counter");
+            context.compile_to_loaded_library().unwrap()
+        };
+        let negate = unsafe { library.function::<extern "C" fn(i32) -> i32>("negate") }.unwrap();
+        assert_eq!(negate(7), -7);
+        assert!(library.global::<i32>("counter").is_some());
+        assert!(library.global::<i32>("missing").is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "dylib", unix))]
+    fn loaded_library_keeps_closures_alive() {
+        let total = ::std::rc::Rc::new(::std::cell::Cell::new(0));
+        let library = {
+            let context = Context::default();
+            let int_ty = context.new_type::<i32>();
+            let counted = total.clone();
+            let add = context.import_closure("add", Box::new(move |x: i32| {
+                counted.set(counted.get() + x);
+                counted.get()
+            }) as Box<dyn Fn(i32) -> i32>);
+            let param = context.new_parameter(None, int_ty, "x");
+            let fun = context.new_function(None, FunctionType::Exported, int_ty, &[param], "call_add", false);
+            fun.new_block("entry").end_with_return(None, context.new_call(None, add, &[param.to_rvalue()]));
+            context.compile_to_loaded_library().unwrap()
+        };
+        let call_add = unsafe { library.function::<extern "C" fn(i32) -> i32>("call_add") }.unwrap();
+        assert_eq!(call_add(5), 5);
+        assert_eq!(total.get(), 5);
+        drop(library);
+        assert_eq!(::std::rc::Rc::strong_count(&total), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "ffi-call")]
+    fn call_with_runtime_signature() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let double_ty = context.new_type::<f64>();
+        let x = context.new_parameter(None, int_ty, "x");
+        let y = context.new_parameter(None, double_ty, "y");
+        let fun = context.new_function(None, FunctionType::Exported, double_ty, &[x, y], "scale", false);
+        let block = fun.new_block("main_block");
+        let x = context.new_cast(None, fun.get_param(0), double_ty);
+        block.end_with_return(None, x * fun.get_param(1).to_rvalue());
+
+        let result = context.compile();
+        let signature = Signature {
+            params: vec![CallType::I32, CallType::F64],
+            ret: CallType::F64
+        };
+        unsafe {
+            let ret = result.call("scale", &signature, &[JitValue::Int(3), JitValue::Float(1.5)]);
+            assert_eq!(ret, Ok(Some(JitValue::Float(4.5))));
+            assert!(result.call("scale", &signature, &[JitValue::Int(3)]).is_err());
+        }
+    }
+
+    #[test]
+    fn verify_reports_all_violations() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        let double_ty = context.new_type::<f64>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "broken", false);
+        let entry = fun.new_block("entry");
+        let dead = fun.new_block("dead");
+        let local = fun.new_local(None, int_ty, "x");
+        entry.add_assignment(None, local, context.new_rvalue_zero(double_ty));
+        dead.end_with_return(None, local);
+
+        let violations = context.verify().unwrap_err();
+        let kinds : Vec<_> = violations.iter().map(|v| v.kind).collect();
+        assert!(kinds.contains(&ViolationKind::UnterminatedBlock));
+        assert!(kinds.contains(&ViolationKind::IncompatibleAssignment));
+        assert!(kinds.contains(&ViolationKind::UnreachableBlock));
+
+        context.set_allow_unreachable_blocks(true);
+        let violations = context.verify().unwrap_err();
+        assert!(violations.iter().all(|v| v.kind != ViolationKind::UnreachableBlock));
+    }
+
+    #[test]
+    fn switch_on_ranges() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "classify", false);
+        let entry = fun.new_block("entry");
+        let small = fun.new_block("small");
+        let large = fun.new_block("large");
+        let other = fun.new_block("other");
+        let cases = [context.new_case(context.new_rvalue_zero(int_ty), context.new_rvalue_from_int(int_ty, 9), small),
+                     context.new_case(context.new_rvalue_from_int(int_ty, 10), context.new_rvalue_from_int(int_ty, 99), large)];
+        entry.end_with_switch(None, fun.get_param(0), other, &cases);
+        small.end_with_return(None, context.new_rvalue_one(int_ty));
+        large.end_with_return(None, context.new_rvalue_from_int(int_ty, 2));
+        other.end_with_return(None, context.new_rvalue_zero(int_ty));
+
+        let result = context.compile();
+        let classify : extern "C" fn(i32) -> i32 = unsafe { mem::transmute(result.get_function("classify")) };
+        assert_eq!((classify(5), classify(50), classify(-1), classify(100)), (1, 2, 0, 0));
+    }
+
+    #[test]
+    fn verify_reports_case_ranges() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "cases", false);
+        let entry = fun.new_block("entry");
+        let target = fun.new_block("target");
+        target.end_with_return(None, context.new_rvalue_zero(int_ty));
+        let int = |value| context.new_rvalue_from_int(int_ty, value);
+        let cases = [context.new_case(int(0), int(9), target),
+                     context.new_case(int(3), int(4), target),
+                     context.new_case(int(6), int(6), target),
+                     context.new_case(int(20), int(10), target),
+                     context.new_case(int(10), int(19), target)];
+        entry.end_with_switch(None, fun.get_param(0), target, &cases);
+
+        let violations = context.verify().unwrap_err();
+        let messages : Vec<_> = violations.iter()
+            .filter(|v| v.kind == ViolationKind::CaseRange)
+            .map(|v| v.message.clone())
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert!(messages[0].contains("minimum greater than its maximum"));
+        assert!(messages[1..].iter().all(|message| message.contains("overlaps")));
+    }
+
+    #[test]
+    fn verify_leaves_integer_aliases_to_gccjit() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let ulong_ty = context.new_type::<u64>();
+        let size_ty = context.new_type::<usize>();
+        let fun = context.new_function(None, FunctionType::Exported, ulong_ty, &[], "length", false);
+        let local = fun.new_local(None, ulong_ty, "x");
+        let entry = fun.new_block("entry");
+        entry.add_assignment(None, local, context.new_rvalue_zero(size_ty));
+        entry.end_with_return(None, local);
+        assert!(context.verify().is_ok());
+    }
+
+    #[test]
+    fn trace_comments() {
+        let context = Context::default();
+        context.set_tracking(true);
+        context.set_trace_comments(true);
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "sign", false);
+        let entry = fun.new_block("entry");
+        let negative = fun.new_block("negative");
+        let positive = fun.new_block("positive");
+        let loc = context.new_location("sign.src", 1, 1);
+        entry.add_trace(Some(loc), "if x < 0");
+        let zero = context.new_rvalue_zero(int_ty);
+        let cond = context.new_comparison(Some(loc), ComparisonOp::LessThan, fun.get_param(0), zero);
+        entry.end_with_conditional(Some(loc), cond, negative, positive);
+        negative.end_with_return(None, context.new_rvalue_from_int(int_ty, -1));
+        positive.end_with_return(None, context.new_rvalue_one(int_ty));
+        assert!(context.verify().is_ok());
+
+        let result = context.compile();
+        let sign : extern "C" fn(i32) -> i32 = unsafe { mem::transmute(result.get_function("sign")) };
+        assert_eq!(sign(-5), -1);
+    }
+
+    #[test]
+    fn function_attributes() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty.make_pointer(), "p");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "load", false);
+        fun.add_attribute(FnAttribute::NoInline);
+        fun.add_attribute(FnAttribute::Pure);
+        fun.add_attribute(FnAttribute::NonNull(&[1]));
+        fun.new_block("entry").end_with_return(None, fun.get_param(0).to_rvalue().dereference(None));
+
+        let result = context.compile();
+        let load : extern "C" fn(*const i32) -> i32 = unsafe { mem::transmute(result.get_function("load")) };
+        assert_eq!(load(&7), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "already set to AlwaysInline")]
+    fn inline_mode_is_set_once() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "one", false);
+        fun.new_block("entry").end_with_return(None, context.new_rvalue_one(int_ty));
+        fun.set_inline_mode(InlineMode::AlwaysInline);
+        fun.set_inline_mode(InlineMode::NoInline);
+    }
+
+    #[test]
+    fn function_alias() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "negate_v2", false);
+        let negated = context.new_unary_op(None, UnaryOp::Minus, int_ty, fun.get_param(0));
+        fun.new_block("entry").end_with_return(None, negated);
+        context.new_function_alias(None, fun, "negate").unwrap();
+
+        let result = context.compile();
+        let negate : extern "C" fn(i32) -> i32 = unsafe { mem::transmute(result.get_function("negate")) };
+        assert_eq!(negate(7), -7);
+    }
+
+    #[test]
+    fn select_lowerings() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        for &(lowering, name) in &[(Lowering::Branchy, "max_branchy"), (Lowering::Branchless, "max_branchless")] {
+            context.set_lowering(lowering);
+            let a = context.new_parameter(None, int_ty, "a");
+            let b = context.new_parameter(None, int_ty, "b");
+            let fun = context.new_function(None, FunctionType::Exported, int_ty, &[a, b], name, false);
+            let entry = fun.new_block("entry");
+            let cond = context.new_comparison(None, ComparisonOp::GreaterThan, a, b);
+            let (block, max) = context.new_select(None, entry, cond, a, b);
+            assert_eq!(format!("{:?}", block) == "entry", lowering == Lowering::Branchless);
+            block.end_with_return(None, max);
+        }
+
+        let result = context.compile();
+        for name in &["max_branchy", "max_branchless"] {
+            let max : extern "C" fn(i32, i32) -> i32 = unsafe { mem::transmute(result.get_function(name)) };
+            assert_eq!(max(3, -4), 3);
+            assert_eq!(max(-3, 4), 4);
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn constructor_runs_on_load() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let void_ty = context.new_type::<()>();
+        let initialized = context.new_global(None, GlobalKind::Exported, int_ty, "initialized");
+        let init = context.new_function(None, FunctionType::Internal, void_ty, &[], "init", false);
+        let block = init.new_block("entry");
+        block.add_assignment(None, initialized, context.new_rvalue_from_int(int_ty, 42));
+        block.end_with_void_return(None);
+        init.set_constructor(Some(200));
+
+        let result = context.compile();
+        let value = unsafe { *(result.get_global("initialized") as *const i32) };
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn constructors_have_distinct_symbols() {
+        let (lib, user) = (Context::default(), Context::default());
+        for context in &[&lib, &user] {
+            let void_ty = context.new_type::<()>();
+            let init = context.new_function(None, FunctionType::Internal, void_ty, &[], "init", false);
+            init.new_block("entry").end_with_void_return(None);
+            init.set_constructor(None);
+            init.set_destructor(None);
+        }
+        let int_ty = user.new_type::<i32>();
+        let answer = user.new_function(None, FunctionType::Exported, int_ty, &[], "answer", false);
+        answer.new_block("entry").end_with_return(None, user.new_rvalue_zero(int_ty));
+
+        let path = ::std::env::temp_dir().join("gccjit_rs_constructors.so");
+        user.compile_group_to_file(&[&lib], OutputKind::DynamicLibrary, path.to_str().unwrap()).unwrap();
+        let _ = ::std::fs::remove_file(&path);
+
+        let header = ::std::env::temp_dir().join("gccjit_rs_constructors.h");
+        user.write_c_header(header.to_str().unwrap()).unwrap();
+        let declared = ::std::fs::read_to_string(&header).unwrap();
+        let _ = ::std::fs::remove_file(&header);
+        assert!(declared.contains("answer") && !declared.contains("__gccjit"));
+    }
+
+    #[test]
+    fn symbols_keep_creation_order() {
+        let context = Context::default();
+        context.set_optimization_level(OptimizationLevel::Standard);
+        context.preserve_symbol_order();
+        let int_ty = context.new_type::<i32>();
+        let names = ["zeta", "alpha", "mu"];
+        for name in &names {
+            let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], name, false);
+            fun.new_block("entry").end_with_return(None, context.new_rvalue_zero(int_ty));
+            context.new_global(None, GlobalKind::Exported, int_ty, format!("{}_count", name));
+        }
+
+        let asm = ::testing::assembly(&context).unwrap();
+        for suffix in &["", "_count"] {
+            let positions : Vec<_> = names.iter()
+                .map(|name| asm.find(&format!("\n{}{}:", name, suffix)).unwrap())
+                .collect();
+            let mut sorted = positions.clone();
+            sorted.sort();
+            assert_eq!(positions, sorted);
+        }
+    }
+
+    #[test]
+    fn declare_in_bulk() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let structs = context.declare_structs(&[StructDecl {
+            loc: None,
+            name: "node",
+            fields: &[(DeclType::Type(int_ty), "value"), (DeclType::PointerTo(0), "next")]
+        }]);
+        let node_ptr = structs[0].as_type().make_pointer();
+        let globals = context.declare_globals(&[GlobalDecl {
+            loc: None,
+            kind: GlobalKind::Exported,
+            ty: node_ptr,
+            name: "head"
+        }]);
+        let functions = context.declare_functions(&[FnDecl {
+            loc: None,
+            kind: FunctionType::Exported,
+            return_type: int_ty,
+            params: &[(node_ptr, "node")],
+            name: "second_value",
+            is_variadic: false
+        }]);
+        let node = functions[0].get_param(0).to_rvalue();
+        let value = node.arrow_path(None, "next.value").unwrap();
+        functions[0].new_block("entry").end_with_return(None, value);
+        assert_eq!(format!("{:?}", globals[0]), "head");
+        assert!(functions[0].accepts_argument_count(1));
+    }
+
+    #[test]
+    fn while_loop_with_libc() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let n = context.new_parameter(None, int_ty, "n");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[n], "sum_to", false);
+        let entry = fun.new_block("entry");
+        let sum = fun.new_local(None, int_ty, "sum");
+        let i = fun.new_local(None, int_ty, "i");
+        entry.add_assignment(None, sum, context.new_rvalue_zero(int_ty));
+        entry.add_assignment(None, i, context.new_rvalue_one(int_ty));
+        let sum_loop = fun.new_while_loop(None, entry, "sum");
+        sum_loop.test(None, context.new_comparison(None, ComparisonOp::LessThanEquals, i, n));
+        sum_loop.body.add_assignment_op(None, sum, BinaryOp::Plus, i);
+        sum_loop.body.add_assignment_op(None, i, BinaryOp::Plus, context.new_rvalue_one(int_ty));
+        let exit = sum_loop.close(None, sum_loop.body);
+        let libc = context.libc();
+        assert_eq!(unsafe { function::get_ptr(&libc.putchar()) }, unsafe { function::get_ptr(&libc.putchar()) });
+        exit.add_eval(None, context.new_call(None, libc.putchar(), &[context.new_rvalue_from_int(int_ty, 10)]));
+        exit.end_with_return(None, sum);
+
+        let result = context.compile();
+        let sum_to : extern "C" fn(i32) -> i32 = unsafe { result.function("sum_to").unwrap() };
+        assert_eq!(sum_to(10), 55);
+        assert!(unsafe { result.function::<extern "C" fn(), _>("missing") }.is_none());
+    }
+
+    #[test]
+    fn scalarized_struct_in_loop() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let a = context.new_field(None, int_ty, "a");
+        let b = context.new_field(None, int_ty, "b");
+        let pair = context.new_struct_type(None, "pair", &[a, b]).as_type();
+        let p = context.new_parameter(None, pair, "p");
+        let n = context.new_parameter(None, int_ty, "n");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[p, n], "step", false);
+        let entry = fun.new_block("entry");
+        let fields = fun.scalarize(None, entry, p.to_rvalue(), "p").unwrap();
+        assert_eq!(fields.locals().len(), 2);
+        let i = fun.new_local(None, int_ty, "i");
+        entry.add_assignment(None, i, context.new_rvalue_zero(int_ty));
+        let step_loop = fun.new_while_loop(None, entry, "step");
+        step_loop.test(None, context.new_comparison(None, ComparisonOp::LessThan, i, n));
+        step_loop.body.add_assignment_op(None, fields.field(a), BinaryOp::Plus, fields.field(b));
+        step_loop.body.add_assignment_op(None, fields.field(b), BinaryOp::Plus, context.new_rvalue_one(int_ty));
+        step_loop.body.add_assignment_op(None, i, BinaryOp::Plus, context.new_rvalue_one(int_ty));
+        let exit = step_loop.close(None, step_loop.body);
+        let value = fields.reassemble(None, exit);
+        exit.end_with_return(None, value.access_field(None, a));
+        assert!(fun.scalarize(None, exit, n.to_rvalue(), "n").is_none());
+
+        #[repr(C)]
+        struct Pair(i32, i32);
+        let result = context.compile();
+        let step : extern "C" fn(Pair, i32) -> i32 = unsafe { result.function("step").unwrap() };
+        assert_eq!(step(Pair(0, 1), 4), 1 + 2 + 3 + 4);
+    }
+
+    #[test]
+    fn adapter_skips_env_and_widens() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let long_ty = context.new_type::<i64>();
+        let void_ptr = context.new_type::<()>().make_pointer();
+        let a = context.new_parameter(None, long_ty, "a");
+        let b = context.new_parameter(None, long_ty, "b");
+        let sub = context.new_function(None, FunctionType::Internal, long_ty, &[a, b], "sub", false);
+        sub.new_block("entry").end_with_return(None, context.new_binary_op(None, BinaryOp::Minus, long_ty, a, b));
+
+        context.new_adapter(None, FunctionType::Exported, int_ty,
+                            &[(void_ptr, "env"), (int_ty, "x"), (int_ty, "y")],
+                            "sub_callback", sub, AdapterStrategy::SkipLeading(1));
+        context.new_adapter(None, FunctionType::Exported, int_ty, &[(int_ty, "x"), (int_ty, "y")],
+                            "sub_swapped", sub, AdapterStrategy::Map(&[adapter::AdapterArg::Param(1), adapter::AdapterArg::Param(0)]));
+
+        let result = context.compile();
+        let callback : extern "C" fn(*mut u8, i32, i32) -> i32 = unsafe { result.function("sub_callback").unwrap() };
+        let swapped : extern "C" fn(i32, i32) -> i32 = unsafe { result.function("sub_swapped").unwrap() };
+        assert_eq!(callback(std::ptr::null_mut(), 7, 2), 5);
+        assert_eq!(swapped(7, 2), -5);
+    }
+
+    #[test]
+    fn vector_arithmetic() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let vec_ty = int_ty.make_vector(4);
+        let out = context.new_parameter(None, int_ty.make_pointer(), "out");
+        let fun = context.new_function(None, FunctionType::Exported, context.new_type::<()>(), &[out],
+                                       "doubled", false);
+        let elements : Vec<_> = (1..5).map(|i| context.new_rvalue_from_int(int_ty, i)).collect();
+        let vector = context.new_rvalue_from_vector(None, vec_ty, &elements);
+        let block = fun.new_block("entry");
+        let sum = fun.new_local(None, vec_ty, "sum");
+        block.add_assignment(None, sum, context.new_binary_op(None, BinaryOp::Plus, vec_ty, vector, vector));
+        block.add_assignment(None, sum.vector_element(None, context.new_rvalue_from_int(int_ty, 2)),
+                             context.new_rvalue_from_int(int_ty, 10));
+        let out_vec = context.new_cast(None, out, vec_ty.make_pointer()).dereference(None);
+        block.add_assignment(None, out_vec, sum);
+        block.end_with_void_return(None);
+
+        let result = context.compile();
+        let doubled : extern "C" fn(*mut i32) = unsafe { result.function("doubled").unwrap() };
+        let mut out = [0; 4];
+        doubled(out.as_mut_ptr());
+        assert_eq!(out, [2, 4, 10, 8]);
+    }
+
+    #[test]
+    fn fixed_size_array_types() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "last_square", false);
+        let block = fun.new_block("entry");
+        let squares = fun.new_local(None, context.new_type::<[i32; 4]>(), "squares");
+        for i in 0..4 {
+            block.add_assignment(None, squares.index(None, context.new_rvalue_from_int(int_ty, i)),
+                                 context.new_rvalue_from_int(int_ty, i * i));
+        }
+        block.end_with_return(None, squares.index(None, context.new_rvalue_from_int(int_ty, 3)));
+        assert_eq!(fun.estimated_stack_bytes(), 16);
+
+        let result = context.compile();
+        let last_square : extern "C" fn() -> i32 = unsafe { result.function("last_square").unwrap() };
+        assert_eq!(last_square(), 9);
+    }
+
+    #[test]
+    fn tuple_types() {
+        let context = Context::default();
+        let pair_ty = context.new_type::<(i32, f64)>();
+        assert_eq!(unsafe { types::get_ptr(&pair_ty) }, unsafe { types::get_ptr(&context.new_type::<(i32, f64)>()) });
+        assert!(pair_ty.field(2).is_none());
+        let x = context.new_parameter(None, context.new_type::<i32>(), "x");
+        let fun = context.new_function(None, FunctionType::Exported, pair_ty, &[x], "halve", false);
+        let block = fun.new_block("entry");
+        let pair = fun.new_local(None, pair_ty, "pair");
+        block.add_assignment(None, pair.access_field(None, pair_ty.field(0).unwrap()), x);
+        let half = context.new_binary_op(None, BinaryOp::Divide, context.new_type::<f64>(),
+                                         context.new_cast(None, x, context.new_type::<f64>()),
+                                         context.new_rvalue_from_double(context.new_type::<f64>(), 2.0));
+        block.add_assignment(None, pair.access_path(None, "_1").unwrap(), half);
+        block.end_with_return(None, pair);
+
+        #[repr(C)]
+        #[derive(PartialEq, Debug)]
+        struct Pair(i32, f64);
+        let result = context.compile();
+        let halve : extern "C" fn(i32) -> Pair = unsafe { result.function("halve").unwrap() };
+        assert_eq!(halve(3), Pair(3, 1.5));
+    }
+
+    #[test]
+    fn function_pointer_types() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let callback_ty = context.new_type::<extern "C" fn(i32) -> i32>();
+        let callback = context.new_parameter(None, callback_ty, "callback");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[callback], "call_with_two", false);
+        let two = context.new_rvalue_from_int(int_ty, 2);
+        fun.new_block("entry").end_with_return(None, context.new_call_through_ptr(None, callback, &[two]));
+
+        extern "C" fn triple(x: i32) -> i32 { x * 3 }
+        let result = context.compile();
+        let call_with_two : extern "C" fn(extern "C" fn(i32) -> i32) -> i32 =
+            unsafe { result.function("call_with_two").unwrap() };
+        assert_eq!(call_with_two(triple), 6);
+    }
+
+    #[test]
+    fn typed_pointer_types() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let p = context.new_parameter(None, context.new_type::<*const i32>(), "p");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[p], "load", false);
+        fun.new_block("entry").end_with_return(None, p.to_rvalue().dereference(None));
+        let callback_ty = context.new_type::<Option<extern "C" fn()>>();
+        let null = context.new_null(callback_ty);
+        assert_eq!(format!("{:?}", null.get_type()), format!("{:?}", context.new_type::<extern "C" fn()>()));
+
+        let result = context.compile();
+        let load : extern "C" fn(std::ptr::NonNull<i32>) -> i32 = unsafe { result.function("load").unwrap() };
+        let mut value = 42;
+        assert_eq!(load(std::ptr::NonNull::from(&mut value)), 42);
+    }
+
+    #[test]
+    fn field_metadata() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let count = context.new_field(None, int_ty, "count");
+        let flags = context.new_bitfield(None, context.new_type::<u32>(), 3, "flags");
+        assert_eq!(count.name(), "count");
+        assert_eq!(flags.name(), "flags");
+        assert_eq!(unsafe { types::get_ptr(&count.get_type()) }, unsafe { types::get_ptr(&int_ty) });
+
+        let header = context.new_struct_type(None, "header", &[count, flags]);
+        assert_eq!(header.get_field_count(), 2);
+        assert!(header.get_field(2).is_none());
+        let found = header.get_field_by_name("flags").unwrap();
+        assert_eq!(unsafe { field::get_ptr(&found) }, unsafe { field::get_ptr(&flags) });
+        assert!(header.get_field_by_name("missing").is_none());
+    }
+
+    #[test]
+    fn type_predicates() {
+        let context = Context::default();
+        let double_ty = context.new_type::<f64>().make_const();
+        let int_ty = context.new_type::<i32>();
+        let uint_ty = context.new_type::<u32>();
+        assert!(double_ty.is_floating_point() && !int_ty.is_floating_point());
+        assert!(int_ty.is_signed() && !uint_ty.is_signed() && !double_ty.is_signed());
+        assert!(int_ty.make_pointer().make_volatile().is_pointer() && !int_ty.is_pointer());
+        assert!(context.new_type::<*const u8>().is_pointer());
+        assert!(context.new_type::<()>().is_void() && !int_ty.is_void());
+    }
+
+    #[test]
+    fn type_alignment() {
+        let context = Context::default();
+        assert_eq!(context.new_type::<u16>().get_alignment(), Ok(2));
+        let x = context.new_field(None, context.new_type::<u8>(), "x");
+        let y = context.new_field(None, context.new_type::<f64>(), "y");
+        let pair = context.new_struct_type(None, "pair", &[x, y]).as_type();
+        assert_eq!(pair.make_const().get_alignment(), Ok(mem::align_of::<f64>()));
+        let opaque = context.new_opaque_struct_type(None, "opaque");
+        assert_eq!(opaque.as_type().get_alignment(), Err(UnknownLayout));
+    }
+
+    #[test]
+    fn opaque_struct_completion() {
+        let context = Context::default();
+        let node = context.new_opaque_struct_type(None, "node");
+        let next = context.new_field(None, node.as_type().make_pointer(), "next");
+        let value = context.new_field(None, context.new_type::<i32>(), "value");
+        let node = node.set_fields(None, &[next, value]);
+        assert_eq!(node.get_field_count(), 2);
+        assert!(node.get_field_by_name("next").is_some());
+    }
+
+    #[test]
+    fn complex_arithmetic() {
+        let context = Context::default();
+        let complex_ty = context.new_c_type(CType::ComplexDouble);
+        let double_ty = context.new_type::<f64>();
+        let product = context.new_binary_op(None, BinaryOp::Mult, complex_ty,
+                                            context.new_rvalue_from_complex(complex_ty, 1.0, 2.0),
+                                            context.new_rvalue_from_complex(complex_ty, 3.0, 4.0));
+        for &(name, imag) in &[("real", false), ("imag", true)] {
+            let fun = context.new_function(None, FunctionType::Exported, double_ty, &[], name, false);
+            let part = if imag {
+                context.new_complex_imag(None, product)
+            } else {
+                context.new_complex_real(None, product)
+            };
+            fun.new_block("entry").end_with_return(None, part);
+        }
+
+        let result = context.compile();
+        let real : extern "C" fn() -> f64 = unsafe { result.function("real").unwrap() };
+        let imag : extern "C" fn() -> f64 = unsafe { result.function("imag").unwrap() };
+        assert_eq!((real(), imag()), (-5.0, 10.0));
+    }
+
+    #[test]
+    fn vector_select_by_mask() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let vec_ty = int_ty.make_vector(4);
+        let mask_ty = context.new_vector_mask_type(vec_ty).unwrap();
+        assert!(context.new_vector_mask_type(int_ty).is_none());
+        let out = context.new_parameter(None, int_ty.make_pointer(), "out");
+        let fun = context.new_function(None, FunctionType::Exported, context.new_type::<()>(), &[out],
+                                       "blend", false);
+        let vector = |values: [i32; 4], ty| {
+            let elements : Vec<_> = values.iter().map(|&i| context.new_rvalue_from_int(int_ty, i)).collect();
+            context.new_rvalue_from_vector(None, ty, &elements)
+        };
+        let mask = vector([-1, 0, -1, 0], mask_ty);
+        let blended = context.new_vector_select(None, mask, vector([1, 2, 3, 4], vec_ty), vector([5, 6, 7, 8], vec_ty));
+        let block = fun.new_block("entry");
+        let out_vec = context.new_cast(None, out, vec_ty.make_pointer()).dereference(None);
+        block.add_assignment(None, out_vec, blended);
+        block.end_with_void_return(None);
+
+        let result = context.compile();
+        let blend : extern "C" fn(*mut i32) = unsafe { result.function("blend").unwrap() };
+        let mut out = [0; 4];
+        blend(out.as_mut_ptr());
+        assert_eq!(out, [1, 6, 3, 8]);
+    }
+
+    #[test]
+    #[cfg(feature = "master")]
+    fn convert_vector_widens_lanes() {
+        let context = Context::default();
+        let byte_ty = context.new_type::<u8>();
+        let short_ty = context.new_type::<u16>();
+        let out = context.new_parameter(None, short_ty.make_pointer(), "out");
+        let fun = context.new_function(None, FunctionType::Exported, context.new_type::<()>(), &[out],
+                                       "widen", false);
+        let elements : Vec<_> = (0..16).map(|i| context.new_rvalue_from_int(byte_ty, 250 + i % 6)).collect();
+        let bytes = context.new_rvalue_from_vector(None, byte_ty.make_vector(16), &elements);
+        let shorts_ty = short_ty.make_vector(16);
+        let block = fun.new_block("entry");
+        let out_vec = context.new_cast(None, out, shorts_ty.make_pointer()).dereference(None);
+        block.add_assignment(None, out_vec, context.convert_vector(None, bytes, shorts_ty));
+        block.end_with_void_return(None);
+
+        let result = context.compile();
+        let widen : extern "C" fn(*mut u16) = unsafe { result.function("widen").unwrap() };
+        let mut out = [0; 16];
+        widen(out.as_mut_ptr());
+        assert_eq!(out[..6], [250, 251, 252, 253, 254, 255]);
+    }
+
+    #[test]
+    fn dot_lists_statements() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "count", false);
+        let entry = fun.new_block("entry");
+        let exit = fun.new_block("exit");
+        let local = fun.new_local(None, int_ty, "i");
+        let loc = context.new_location("count.src", 3, 5);
+        entry.add_assignment(Some(loc), local, context.new_rvalue_one(int_ty));
+        entry.end_with_jump(None, exit);
+        exit.end_with_return(None, local);
+
+        let dot = fun.to_dot();
+        assert!(dot.contains("i = (int)1;  [count.src:3:5]"));
+        assert!(dot.contains("b0 -> b1;"));
+    }
+
+    #[test]
+    fn imported_closure_outlives_context() {
+        let total = ::std::rc::Rc::new(::std::cell::Cell::new(0));
+        let result = {
+            let context = Context::default();
+            let int_ty = context.new_type::<i32>();
+            let counted = total.clone();
+            let add = context.import_closure("add", Box::new(move |x: i32| {
+                counted.set(counted.get() + x);
+                counted.get()
+            }) as Box<dyn Fn(i32) -> i32>);
+            let param = context.new_parameter(None, int_ty, "x");
+            let fun = context.new_function(None, FunctionType::Exported, int_ty, &[param], "add_twice", false);
+            let block = fun.new_block("entry");
+            block.add_eval(None, context.new_call(None, add, &[param.to_rvalue()]));
+            block.end_with_return(None, context.new_call(None, add, &[param.to_rvalue()]));
+            context.compile()
+        };
+        let add_twice = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("add_twice") }.unwrap();
+        assert_eq!(add_twice(5), 10);
+        assert_eq!(total.get(), 10);
+        drop(result);
+        assert_eq!(::std::rc::Rc::strong_count(&total), 1);
+    }
+
+    #[test]
+    fn registered_symbols() {
+        extern "C" fn scale(x: i32) -> i32 {
+            x * 3
+        }
+        static mut OFFSET: i32 = 4;
+
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        context.register_symbol("scale", scale as extern "C" fn(i32) -> i32);
+        context.register_symbol("offset", ::std::ptr::addr_of_mut!(OFFSET));
+
+        let child = context.new_child_context();
+        let (scale, offset) = match (child.lookup_symbol("scale"), child.lookup_symbol("offset")) {
+            (Some(Symbol::Function(scale)), Some(Symbol::Global(offset))) => (scale, offset),
+            symbols => panic!("unexpected symbols {:?}", symbols)
+        };
+        assert!(child.lookup_symbol("missing").is_none());
+        let param = child.new_parameter(None, int_ty, "x");
+        let fun = child.new_function(None, FunctionType::Exported, int_ty, &[param], "scale_and_offset", false);
+        let scaled = child.new_call_through_ptr(None, scale, &[param.to_rvalue()]);
+        fun.new_block("entry").end_with_return(None, child.new_binary_op(None, BinaryOp::Plus, int_ty,
+                                                                         scaled, offset.to_rvalue()));
+        let result = child.compile();
+        let scale_and_offset = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("scale_and_offset") };
+        assert_eq!(scale_and_offset.unwrap()(2), 10);
+    }
+
+    #[test]
+    fn link_contexts_into_library() {
+        let lib = Context::default();
+        let user = Context::default();
+        let int_ty = lib.new_type::<i32>();
+        let x = lib.new_parameter(None, int_ty, "x");
+        let square = lib.new_function(None, FunctionType::Exported, int_ty, &[x], "square", false);
+        square.new_block("entry").end_with_return(None, lib.new_binary_op(None, BinaryOp::Mult, int_ty, x, x));
+
+        let int_ty = user.new_type::<i32>();
+        let x = user.new_parameter(None, int_ty, "x");
+        let square = user.new_function(None, FunctionType::Extern, int_ty, &[x], "square", false);
+        let four = user.new_function(None, FunctionType::Exported, int_ty, &[], "four", false);
+        let two = user.new_rvalue_from_int(int_ty, 2);
+        four.new_block("entry").end_with_return(None, user.new_call(None, square, &[two]));
+
+        let path = ::std::env::temp_dir().join("gccjit_rs_group.so");
+        user.compile_group_to_file(&[&lib], OutputKind::DynamicLibrary, path.to_str().unwrap()).unwrap();
+        assert!(path.exists());
+        let err = user.compile_group_to_file(&[&lib], OutputKind::ObjectFile, path.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidInput);
+        let _ = ::std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn closures_cant_be_compiled_to_a_group() {
+        let lib = Context::default();
+        let user = Context::default();
+        let get = lib.import_closure("get", Box::new(|| 1) as Box<dyn Fn() -> i32>);
+        let fun = lib.new_function(None, FunctionType::Exported, lib.new_type::<i32>(), &[], "call_get", false);
+        fun.new_block("entry").end_with_return(None, lib.new_call(None, get, &[]));
+
+        let path = ::std::env::temp_dir().join("gccjit_rs_closure_group.so");
+        let err = user.compile_group_to_file(&[&lib], OutputKind::DynamicLibrary, path.to_str().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), ::std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    #[should_panic(expected = "imported closures")]
+    fn closures_cant_be_compiled_to_a_file() {
+        let context = Context::default();
+        context.import_closure("get", Box::new(|| 1) as Box<dyn Fn() -> i32>);
+        let child = context.new_child_context();
+        let path = ::std::env::temp_dir().join("gccjit_rs_closure.o");
+        child.compile_to_file(OutputKind::ObjectFile, path.to_str().unwrap());
+    }
+
+    #[test]
+    fn compile_with_dumps() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "dumped", false);
+        fun.new_block("entry").end_with_return(None, context.new_rvalue_from_int(int_ty, 42));
+
+        let (result, dumps) = context.compile_with_dumps(["tree-optimized", "rtl-expand"]);
+        let dumped = unsafe { result.function::<extern "C" fn() -> i32, _>("dumped") }.unwrap();
+        assert_eq!(dumped(), 42);
+        assert!(dumps.get("tree-optimized").unwrap().contains("dumped"));
+        assert_eq!(dumps.passes().count(), 2);
+    }
+
+    #[test]
+    fn pretty_print_listing() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        let value = context.new_field(None, int_ty, "value");
+        let cell = context.new_struct_type(None, "cell", &[value]).as_type();
+        context.new_global(None, GlobalKind::Internal, cell, "current");
+        let param = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[param], "twice", false);
+        let block = fun.new_block("entry");
+        block.end_with_return(None, context.new_binary_op(None, BinaryOp::Mult, int_ty,
+                                                          param.to_rvalue(), context.new_rvalue_from_int(int_ty, 2)));
+
+        let listing = context.pretty_print();
+        assert!(listing.contains("struct cell {\n    int value;\n};"));
+        assert!(listing.contains("internal struct cell current;"));
+        assert!(listing.contains("exported int twice(int x)\n{\n  entry:\n    return x * (int)2;\n}"));
+    }
+
+    #[test]
+    fn dump_all_functions_to_dot() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let void_ty = context.new_type::<()>();
+        let abort = context.new_function(None, FunctionType::Extern, void_ty, &[], "abort", false);
+        for name in &["first", "second"] {
+            let fun = context.new_function(None, FunctionType::Exported, void_ty, &[], name, false);
+            let block = fun.new_block("entry");
+            block.add_eval(None, context.new_call(None, abort, &[]));
+            block.end_with_void_return(None);
+        }
+
+        let dir = ::std::env::temp_dir().join("gccjit_rs_dot");
+        context.dump_all_to_dot(&dir).unwrap();
+        let index = ::std::fs::read_to_string(dir.join("index.dot")).unwrap();
+        assert!(index.contains("URL=\"first.dot\"") && index.contains("URL=\"second.dot\""));
+        assert!(!index.contains("abort"));
+        assert!(::std::fs::read_to_string(dir.join("second.dot")).unwrap().contains("digraph \"second\""));
+    }
+
+    #[test]
+    fn block_default_location() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "located", false);
+        let entry = fun.new_block("entry");
+        let local = fun.new_local(None, int_ty, "i");
+        entry.set_default_location(Some(context.new_location("located.src", 1, 1)));
+        entry.add_assignment(None, local, context.new_rvalue_one(int_ty));
+        entry.end_with_return(Some(context.new_location("located.src", 2, 3)), local);
+
+        let dot = fun.to_dot();
+        assert!(dot.contains("i = (int)1;  [located.src:1:1]"));
+        assert!(dot.contains("return i;  [located.src:2:3]"));
+    }
+
+    #[test]
+    fn drain_new_errors() {
+        let context = Context::default();
+        let void_ty = context.new_type::<()>();
+        let fun = context.new_function(None, FunctionType::Exported, void_ty, &[], "twice", false);
+        let block = fun.new_block("entry");
+        block.end_with_void_return(None);
+        assert_eq!(context.drain_new_errors().count(), 0);
+
+        block.end_with_void_return(None);
+        assert_eq!(context.drain_new_errors().count(), 1);
+        assert_eq!(context.drain_new_errors().count(), 0);
+
+        block.add_comment(None, "too late");
+        let errors : Vec<_> = context.drain_new_errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("gcc_jit_block_add_comment"));
+    }
+
+    #[test]
+    fn global_attributes() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let global = context.new_global(None, GlobalKind::Exported, int_ty, "config_value");
+        global.add_attribute(VarAttribute::Section(".data.config"));
+        global.add_attribute(VarAttribute::Visibility(Visibility::Hidden));
+
+        let asm = ::testing::assembly(&context).unwrap();
+        assert!(asm.contains(".data.config"));
+        assert!(asm.contains(".hidden\tconfig_value"));
+    }
+
+    #[test]
+    fn call_macro_mixes_arguments() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let params = [context.new_parameter(None, int_ty, "a"), context.new_parameter(None, int_ty, "b")];
+        let add = context.new_function(None, FunctionType::Internal, int_ty, &params, "add", false);
+        add.new_block("entry").end_with_return(None, add.get_param(0).to_rvalue() + add.get_param(1).to_rvalue());
+
+        let parameter = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "add_two", false);
+        let block = fun.new_block("entry");
+        let sum = call!(context, add, fun.get_param(0), context.new_rvalue_one(int_ty));
+        let local = fun.new_local(None, int_ty, "sum");
+        block.add_assignment(None, local, sum);
+        let one = context.new_rvalue_one(int_ty);
+        block.end_with_return(None, context.new_call_with(None, add, &[&local, &one]));
+
+        let result = context.compile();
+        let add_two : extern "C" fn(i32) -> i32 = unsafe { mem::transmute(result.get_function("add_two")) };
+        assert_eq!(add_two(41), 43);
+    }
+
+    #[test]
+    #[should_panic]
+    fn call_macro_checks_arity() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Internal, int_ty, &[], "nullary", false);
+        call!(context, fun, context.new_rvalue_one(int_ty));
+    }
+
+    #[test]
+    fn access_nested_field_path() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let x = context.new_field(None, int_ty, "x");
+        let point = context.new_struct_type(None, "point", &[x]).as_type();
+        let origin = context.new_field(None, point, "origin");
+        let shape = context.new_struct_type(None, "shape", &[origin]).as_type();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "origin_x", false);
+        let block = fun.new_block("entry");
+        let local = fun.new_local(None, shape, "s");
+        block.add_assignment(None, local.access_path(None, "origin.x").unwrap(), context.new_rvalue_one(int_ty));
+        block.end_with_return(None, local.access_path(None, "origin.x").unwrap());
+
+        assert_eq!(local.access_path(None, "origin.y").unwrap_err(),
+                   FieldPathError::NoSuchField("origin.y".to_owned()));
+        assert_eq!(local.access_path(None, "origin.x.z").unwrap_err(),
+                   FieldPathError::NotAnAggregate("origin.x.z".to_owned()));
+
+        let result = context.compile();
+        let origin_x : extern "C" fn() -> i32 = unsafe { mem::transmute(result.get_function("origin_x")) };
+        assert_eq!(origin_x(), 1);
+    }
+
+    #[test]
+    fn arrow_path_dereferences_pointers() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let value = context.new_field(None, int_ty, "value");
+        let leaf = context.new_struct_type(None, "leaf", &[value]).as_type();
+        let child = context.new_field(None, leaf.make_pointer(), "child");
+        let node = context.new_struct_type(None, "node", &[child]).as_type();
+        let parameter = context.new_parameter(None, node.make_pointer(), "n");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[parameter], "child_value", false);
+        let n = fun.get_param(0).to_rvalue();
+        fun.new_block("entry").end_with_return(None, n.arrow_path(None, "child.value").unwrap());
+
+        assert!(n.arrow(None, child).is_ok());
+        assert_eq!(n.arrow(None, value).unwrap_err(), FieldPathError::NoSuchField("value".to_owned()));
+        let local = fun.new_local(None, node, "local");
+        assert_eq!(local.to_rvalue().arrow_path(None, "child").unwrap_err(),
+                   FieldPathError::NotAPointer("child".to_owned()));
+
+        let result = context.compile();
+        let child_value : extern "C" fn(*const [*const i32; 1]) -> i32 =
+            unsafe { mem::transmute(result.get_function("child_value")) };
+        let five = 5;
+        assert_eq!(child_value(&[&five]), 5);
+    }
+
+    #[test]
+    fn large_stack_frame() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let char_ty = context.new_type::<u8>();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "big_frame", false);
+        fun.new_local(None, context.new_array_type(None, char_ty, 1000), "buffer");
+        let local = fun.new_local(None, int_ty, "x");
+        fun.new_block("entry").end_with_return(None, local);
+        assert_eq!(fun.estimated_stack_bytes(), 1004);
+
+        context.set_stack_warning_threshold(Some(512));
+        let violations = context.verify().unwrap_err();
+        assert_eq!(violations[0].kind, ViolationKind::LargeStackFrame);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn multiversioned_function() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_multiversioned_function(None, FunctionType::Exported, int_ty,
+                                                      &[(int_ty, "x")], "double_it",
+                                                      &["avx2,fma", "sse4.2"], |fun, _| {
+            let x = fun.get_param(0).to_rvalue();
+            let doubled = context.new_binary_op(None, BinaryOp::Plus, int_ty, x, x);
+            fun.new_block("entry").end_with_return(None, doubled);
+        });
+        assert!(fun.is_some());
+        let result = context.compile();
+        let double_it : extern "C" fn(i32) -> i32 = unsafe {
+            mem::transmute(result.get_function("double_it"))
+        };
+        assert_eq!(double_it(21), 42);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "can't be used with context")]
+    fn objects_of_other_contexts_are_rejected() {
+        let context = Context::default();
+        let other = Context::default();
+        assert!(other.id() > context.id());
+        let int_ty = other.new_type::<i32>();
+        context.new_global(None, GlobalKind::Exported, int_ty, "foreign");
+    }
+
+    #[test]
+    fn child_contexts_are_counted_until_dropped() {
+        let context = Context::default();
+        context.set_tracking(true);
+        let int_ty = context.new_type::<i32>();
+        for _ in 0..3 {
+            let child = context.new_child_context();
+            let fun = child.new_function(None, FunctionType::Exported, int_ty, &[], "zero", false);
+            fun.new_block("entry").end_with_return(None, child.new_rvalue_zero(int_ty));
+            assert_eq!(context.stats().live_children, 1);
+            assert_eq!(child.stats().functions, 1);
+            assert_eq!(child.stats().statements, 1);
+        }
+        let stats = context.stats();
+        assert_eq!(stats.live_children, 0);
+        assert_eq!(stats.functions, 0);
+        assert_eq!(stats.types, 1);
+    }
+
+    #[test]
+    fn tracking_is_opt_in() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "zero", false);
+        fun.new_block("entry").end_with_return(None, context.new_rvalue_zero(int_ty));
+        assert_eq!(context.stats().statements, 0);
+        assert!(context.stats().functions == 1 && context.stats().types == 1);
+        let violations = context.verify().unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].kind, ViolationKind::TrackingDisabled);
+
+        let child = context.new_child_context();
+        assert!(!child.config().tracking);
+        context.set_tracking(true);
+        let child = context.new_child_context();
+        assert!(child.config().tracking);
+    }
+
+    #[test]
+    fn child_contexts_can_use_parent_objects() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let child = context.new_child_context();
+        child.new_global(None, GlobalKind::Exported, int_ty, "inherited");
+        assert!(child.id() > context.id());
+    }
+
+    #[test]
+    fn checked_arithmetic_reports_overflow() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let byte_ty = context.new_type::<i8>();
+        let bool_ty = context.new_type::<bool>();
+        let a = context.new_parameter(None, int_ty, "a");
+        let b = context.new_parameter(None, int_ty, "b");
+        let add = context.new_function(None, FunctionType::Exported, bool_ty, &[a, b], "add_overflows", false);
+        let block = add.new_block("entry");
+        let sum = add.new_local(None, int_ty, "sum");
+        let overflowed = block.add_checked_add(None, sum, a, b);
+        block.end_with_return(None, overflowed);
+
+        let x = context.new_parameter(None, byte_ty, "x");
+        let y = context.new_parameter(None, byte_ty, "y");
+        let mul = context.new_function(None, FunctionType::Exported, bool_ty, &[x, y], "mul_overflows", false);
+        let block = mul.new_block("entry");
+        let product = mul.new_local(None, byte_ty, "product");
+        let overflowed = block.add_checked_mul(None, product, x, y);
+        block.end_with_return(None, overflowed);
+
+        let result = context.compile();
+        let add = unsafe { result.function::<extern "C" fn(i32, i32) -> bool, _>("add_overflows") }.unwrap();
+        let mul = unsafe { result.function::<extern "C" fn(i8, i8) -> bool, _>("mul_overflows") }.unwrap();
+        assert!(!add(1, 2));
+        assert!(add(i32::MAX, 1));
+        assert!(!mul(-8, 16));
+        assert!(mul(16, 16));
+    }
+
+    #[test]
+    fn expected_conditions_keep_their_value() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let x = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[x], "sign", false);
+        let entry = fun.new_block("entry");
+        let positive = fun.new_block("positive");
+        let other = fun.new_block("other");
+        let unlikely = fun.new_block("unlikely");
+        let negative = fun.new_block("negative");
+        let zero = context.new_rvalue_zero(int_ty);
+        entry.end_with_conditional_likely(None, context.new_comparison(None, ComparisonOp::GreaterThan, x, zero),
+                                          positive, other);
+        positive.end_with_return(None, context.new_rvalue_one(int_ty));
+        let is_zero = context.new_comparison(None, ComparisonOp::Equals, x, zero);
+        other.end_with_conditional(None, context.expect(None, is_zero, false), unlikely, negative);
+        unlikely.end_with_return(None, zero);
+        negative.end_with_return(None, context.new_rvalue_from_int(int_ty, -1));
+
+        let result = context.compile();
+        let sign = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("sign") }.unwrap();
+        assert_eq!(sign(5), 1);
+        assert_eq!(sign(0), 0);
+        assert_eq!(sign(-5), -1);
+    }
+
+    #[test]
+    fn prefetch_before_load() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let ptr = context.new_parameter(None, int_ty.make_pointer(), "ptr");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[ptr], "load", false);
+        let block = fun.new_block("entry");
+        block.add_prefetch(None, ptr, PrefetchAccess::Read, PrefetchLocality::High);
+        block.end_with_return(None, ptr.to_rvalue().dereference(None));
+
+        let result = context.compile();
+        let load = unsafe { result.function::<extern "C" fn(*const i32) -> i32, _>("load") }.unwrap();
+        assert_eq!(load(&7), 7);
+    }
+
+    #[test]
+    fn impossible_paths_need_no_return_value() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let x = context.new_parameter(None, int_ty, "x");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[x], "checked", false);
+        let entry = fun.new_block("entry");
+        let in_range = fun.new_block("in_range");
+        let out_of_range = fun.new_block("out_of_range");
+        let ten = context.new_rvalue_from_int(int_ty, 10);
+        entry.end_with_conditional(None, context.new_comparison(None, ComparisonOp::LessThan, x, ten),
+                                   in_range, out_of_range);
+        in_range.end_with_return(None, x);
+        out_of_range.end_with_trap(None);
+
+        let y = context.new_parameter(None, int_ty, "y");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[y], "assumed", false);
+        let entry = fun.new_block("entry");
+        let zero = fun.new_block("zero");
+        let never = fun.new_block("never");
+        let is_zero = context.new_comparison(None, ComparisonOp::Equals, y, context.new_rvalue_zero(int_ty));
+        entry.end_with_conditional(None, is_zero, zero, never);
+        zero.end_with_return(None, y);
+        never.end_with_unreachable(None);
+
+        let result = context.compile();
+        let checked = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("checked") }.unwrap();
+        let assumed = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("assumed") }.unwrap();
+        assert_eq!(checked(3), 3);
+        assert_eq!(assumed(0), 0);
+    }
+
+    #[test]
+    fn stack_allocations_are_usable() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let n = context.new_parameter(None, int_ty, "n");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[n], "stack_sum", false);
+        let block = fun.new_block("entry");
+        let buffer = fun.new_local(None, int_ty.make_pointer(), "buffer");
+        let size = n.to_rvalue() * context.new_rvalue_from_int(int_ty, mem::size_of::<i32>() as i32);
+        let allocation = fun.new_stack_allocation(None, size, Some(64));
+        block.add_assignment(None, buffer, context.new_cast(None, allocation, int_ty.make_pointer()));
+        let last = n.to_rvalue() - context.new_rvalue_one(int_ty);
+        block.add_assignment(None, buffer.to_rvalue().index(None, context.new_rvalue_zero(int_ty)), n);
+        block.add_assignment(None, buffer.to_rvalue().index(None, last), n);
+        let first = buffer.to_rvalue().index(None, context.new_rvalue_zero(int_ty)).to_rvalue();
+        block.end_with_return(None, first + buffer.to_rvalue().index(None, last).to_rvalue());
+
+        let result = context.compile();
+        let stack_sum = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("stack_sum") }.unwrap();
+        assert_eq!(stack_sum(100), 200);
+    }
+
+    #[test]
+    fn longjmp_returns_to_setjmp() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let libc = context.libc();
+        let value = context.new_parameter(None, int_ty, "value");
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[value], "escape", false);
+        let entry = fun.new_block("entry");
+        let jump = fun.new_block("jump");
+        let done = fun.new_block("done");
+        let env = fun.new_local(None, libc.jmp_buf(), "env");
+        let returned = fun.new_local(None, int_ty, "returned");
+        entry.add_assignment(None, returned, context.new_call(None, libc.setjmp(), &[env.get_address(None)]));
+        let first = context.new_comparison(None, ComparisonOp::Equals, returned, context.new_rvalue_zero(int_ty));
+        entry.end_with_conditional(None, first, jump, done);
+        jump.end_with_noreturn_call(None, context.new_call(None, libc.longjmp(), &[env.get_address(None), value.to_rvalue()]));
+        done.end_with_return(None, returned);
+
+        let result = context.compile();
+        let escape = unsafe { result.function::<extern "C" fn(i32) -> i32, _>("escape") }.unwrap();
+        assert_eq!(escape(5), 5);
+        assert_eq!(escape(0), 1);
+    }
+
     /* Uncomment these tests periodically to remind yourself of
      * 1) why rust is awesome and 2) make sure that you've set up
      * lifetimes correctly so that these invariant violations are
@@ -0,0 +1,92 @@
+//! Plain-data descriptions of functions, globals, and structs, for
+//! frontends that declare many entities at once, such as compiler
+//! backends translating a whole module. The descriptions can be built
+//! and tested separately from the context and then declared in bulk with
+//! Context::declare_functions, declare_globals, and declare_structs.
+
+use context::{Context, GlobalKind};
+use function::{Function, FunctionType};
+use location::Location;
+use lvalue::LValue;
+use structs::Struct;
+use types::Type;
+
+/// A function to be declared with Context::declare_functions. The
+/// parameters are given as types and names.
+#[derive(Copy, Clone)]
+pub struct FnDecl<'a, 'n> {
+    pub loc: Option<Location<'a>>,
+    pub kind: FunctionType,
+    pub return_type: Type<'a>,
+    pub params: &'n [(Type<'a>, &'n str)],
+    pub name: &'n str,
+    pub is_variadic: bool
+}
+
+/// A global to be declared with Context::declare_globals.
+#[derive(Copy, Clone)]
+pub struct GlobalDecl<'a, 'n> {
+    pub loc: Option<Location<'a>>,
+    pub kind: GlobalKind,
+    pub ty: Type<'a>,
+    pub name: &'n str
+}
+
+/// The type of a field of a StructDecl.
+#[derive(Copy, Clone)]
+pub enum DeclType<'a> {
+    Type(Type<'a>),
+    /// A pointer to the struct at the given index of the declarations,
+    /// which lets declared structs refer to each other and to themselves.
+    PointerTo(usize)
+}
+
+/// A struct to be declared with Context::declare_structs. The fields are
+/// given as types and names.
+#[derive(Copy, Clone)]
+pub struct StructDecl<'a, 'n> {
+    pub loc: Option<Location<'a>>,
+    pub name: &'n str,
+    pub fields: &'n [(DeclType<'a>, &'n str)]
+}
+
+pub fn functions<'a, 'ctx>(ctx: &'a Context<'ctx>, decls: &[FnDecl<'a, '_>]) -> Vec<Function<'a>> {
+    decls.iter()
+        .map(|decl| {
+            let params : Vec<_> = decl.params.iter()
+                .map(|&(ty, name)| ctx.new_parameter(decl.loc, ty, name))
+                .collect();
+            ctx.new_function(decl.loc, decl.kind, decl.return_type, &params, decl.name, decl.is_variadic)
+        })
+        .collect()
+}
+
+pub fn globals<'a, 'ctx>(ctx: &'a Context<'ctx>, decls: &[GlobalDecl<'a, '_>]) -> Vec<LValue<'a>> {
+    decls.iter()
+        .map(|decl| ctx.new_global(decl.loc, decl.kind, decl.ty, decl.name))
+        .collect()
+}
+
+/// Declares every struct as opaque first, so that fields can point to
+/// any of them, and then fills in the fields.
+///
+/// # Panics
+/// Panics if a DeclType::PointerTo index is out of range.
+pub fn structs<'a, 'ctx>(ctx: &'a Context<'ctx>, decls: &[StructDecl<'a, '_>]) -> Vec<Struct<'a>> {
+    let opaque : Vec<_> = decls.iter()
+        .map(|decl| ctx.new_opaque_struct_type(decl.loc, decl.name))
+        .collect();
+    let types : Vec<_> = opaque.iter().map(|st| st.as_type()).collect();
+    decls.iter().zip(opaque).map(|(decl, st)| {
+        let fields : Vec<_> = decl.fields.iter()
+            .map(|&(ty, name)| {
+                let ty = match ty {
+                    DeclType::Type(ty) => ty,
+                    DeclType::PointerTo(idx) => types[idx].make_pointer()
+                };
+                ctx.new_field(decl.loc, ty, name)
+            })
+            .collect();
+        st.set_fields(decl.loc, &fields)
+    }).collect()
+}
@@ -0,0 +1,180 @@
+//! Graphviz output for functions, built from the statements recorded in
+//! the tracking registry. Unlike gcc_jit_function_dump_to_dot, every
+//! block lists its statements along with their source locations.
+
+use gccjit_sys;
+
+use block::BinaryOp;
+use emit::debug_string;
+use function::FunctionType;
+use tracking::{Registry, CaseInfo, ContextInfo, FunctionInfo, Statement, StatementInfo};
+
+/// Renders every function defined in a context, returning the name of the
+/// file for each graph along with the graph. The first graph is an index
+/// with a node for each function that links to the function's file.
+pub fn context_graphs(reg: &Registry, info: &ContextInfo) -> Vec<(String, String)> {
+    let mut index = "digraph functions {\n".to_owned();
+    index.push_str("    node [shape=box, fontname=monospace];\n");
+    let mut graphs = vec![];
+    for func in info.functions.iter().filter(|f| f.kind != FunctionType::Extern) {
+        let file = format!("{}.dot", func.name);
+        index.push_str(&format!("    f{} [label=\"{}\", URL=\"{}\"];\n",
+                                graphs.len(), escape(&func.name), escape(&file)));
+        graphs.push((file, function_graph(reg, func)));
+    }
+    index.push_str("}\n");
+    graphs.insert(0, ("index.dot".to_owned(), index));
+    graphs
+}
+
+pub fn function_graph(reg: &Registry, func: &FunctionInfo) -> String {
+    let mut out = format!("digraph \"{}\" {{\n", escape(&func.name));
+    out.push_str("    node [shape=box, fontname=monospace];\n");
+    for (idx, &block_ptr) in func.blocks.iter().enumerate() {
+        let block = match reg.block(block_ptr) {
+            Some(block) => block,
+            None => continue
+        };
+        let mut label = format!("{}:\\l", escape(&block.name));
+        for stmt in block.statements.iter() {
+            label.push_str(&format!("  {}\\l", escape(&statement_text(stmt))));
+        }
+        out.push_str(&format!("    b{} [label=\"{}\"];\n", idx, label));
+        for stmt in block.statements.iter() {
+            match stmt.kind {
+                Statement::Jump(target) => edge(&mut out, func, idx, target, None),
+                Statement::Conditional(_, on_true, on_false) => {
+                    edge(&mut out, func, idx, on_true, Some("true"));
+                    edge(&mut out, func, idx, on_false, Some("false"));
+                }
+                Statement::Switch(_, default_block, ref cases) => {
+                    for case in cases.iter() {
+                        edge(&mut out, func, idx, case.dest, Some(&case_label(reg, case)));
+                    }
+                    edge(&mut out, func, idx, default_block, Some("default"));
+                }
+                #[cfg(feature = "master")]
+                Statement::TryCatch(try_block, catch_block) => {
+                    edge(&mut out, func, idx, try_block, Some("try"));
+                    edge(&mut out, func, idx, catch_block, Some("catch"));
+                }
+                #[cfg(feature = "master")]
+                Statement::TryFinally(try_block, finally_block) => {
+                    edge(&mut out, func, idx, try_block, Some("try"));
+                    edge(&mut out, func, idx, finally_block, Some("finally"));
+                }
+                _ => {}
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn edge(out: &mut String,
+        func: &FunctionInfo,
+        from: usize,
+        target: *mut gccjit_sys::gcc_jit_block,
+        label: Option<&str>) {
+    // jumps to blocks of other functions are left out of the graph.
+    if let Some(to) = func.blocks.iter().position(|&b| b == target) {
+        match label {
+            Some(label) => out.push_str(&format!("    b{} -> b{} [label=\"{}\"];\n", from, to, label)),
+            None => out.push_str(&format!("    b{} -> b{};\n", from, to))
+        }
+    }
+}
+
+fn case_label(reg: &Registry, info: &CaseInfo) -> String {
+    match (reg.int_constant(info.min), reg.int_constant(info.max)) {
+        (Some(min), Some(max)) if min == max => format!("{}", min),
+        (Some(min), Some(max)) => format!("{}...{}", min, max),
+        _ => "case".to_owned()
+    }
+}
+
+/// Describes a case of a switch the way gccjit does.
+pub(crate) fn case_text(case: &CaseInfo) -> String {
+    unsafe {
+        let rvalue = |ptr| debug_string(gccjit_sys::gcc_jit_rvalue_as_object(ptr));
+        let block = debug_string(gccjit_sys::gcc_jit_block_as_object(case.dest));
+        if case.min == case.max {
+            format!("case {}: goto {};", rvalue(case.min), block)
+        } else {
+            format!("case {} ... {}: goto {};", rvalue(case.min), rvalue(case.max), block)
+        }
+    }
+}
+
+pub(crate) fn statement_text(stmt: &StatementInfo) -> String {
+    let text = unsafe {
+        let rvalue = |ptr| debug_string(gccjit_sys::gcc_jit_rvalue_as_object(ptr));
+        let lvalue = |ptr| debug_string(gccjit_sys::gcc_jit_lvalue_as_object(ptr));
+        let block = |ptr| debug_string(gccjit_sys::gcc_jit_block_as_object(ptr));
+        match stmt.kind {
+            Statement::Eval(value) => format!("(void){};", rvalue(value)),
+            Statement::Assignment(target, value) => format!("{} = {};", lvalue(target), rvalue(value)),
+            Statement::AssignmentOp(target, op, value) => {
+                format!("{} {}= {};", lvalue(target), binary_op_str(op), rvalue(value))
+            }
+            Statement::Comment(ref text) => format!("/* {} */", text),
+            Statement::Jump(target) => format!("goto {};", block(target)),
+            Statement::Conditional(cond, on_true, on_false) => {
+                format!("if ({}) goto {}; else goto {};", rvalue(cond), block(on_true), block(on_false))
+            }
+            Statement::Return(value) => format!("return {};", rvalue(value)),
+            Statement::VoidReturn => "return;".to_owned(),
+            Statement::Switch(expr, default_block, ref cases) => {
+                let cases : Vec<_> = cases.iter().map(case_text).collect();
+                format!("switch ({}) {{ {} default: goto {}; }}", rvalue(expr), cases.join(" "), block(default_block))
+            }
+            #[cfg(feature = "master")]
+            Statement::TryCatch(try_block, catch_block) => {
+                format!("try {{ {} }} catch {{ {} }}", block(try_block), block(catch_block))
+            }
+            #[cfg(feature = "master")]
+            Statement::TryFinally(try_block, finally_block) => {
+                format!("try {{ {} }} finally {{ {} }}", block(try_block), block(finally_block))
+            }
+        }
+    };
+    if stmt.loc.is_null() {
+        text
+    } else {
+        let loc = unsafe { debug_string(gccjit_sys::gcc_jit_location_as_object(stmt.loc)) };
+        format!("{}  [{}]", text, loc)
+    }
+}
+
+fn binary_op_str(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Plus => "+",
+        BinaryOp::Minus => "-",
+        BinaryOp::Mult => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::BitwiseAnd => "&",
+        BinaryOp::BitwiseXor => "^",
+        BinaryOp::BitwiseOr => "|",
+        BinaryOp::LogicalAnd => "&&",
+        BinaryOp::LogicalOr => "||",
+        BinaryOp::LShift => "<<",
+        BinaryOp::RShift => ">>"
+    }
+}
+
+/// Escapes a string for use inside a quoted Graphviz string.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\l"),
+            _ => out.push(c)
+        }
+    }
+    out
+}
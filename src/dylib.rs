@@ -0,0 +1,151 @@
+//! Loading the output of a context as a shared library, for code that
+//! should stay loaded independently of the CompileResult lifetime rules.
+//! Libraries are loaded with the platform's dlopen.
+
+use std::any::Any;
+use std::env;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use context::{Context, OutputKind};
+
+const RTLD_NOW: c_int = 2;
+
+#[cfg_attr(target_os = "linux", link(name = "dl"))]
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+    fn dlerror() -> *mut c_char;
+}
+
+/// A shared library compiled from a context and loaded into the process,
+/// created with Context::compile_to_loaded_library or by the cache module.
+/// The library is unloaded when this is dropped, and its file deleted
+/// unless it belongs to a cache.
+#[derive(Debug)]
+pub struct LoadedLibrary {
+    handle: *mut c_void,
+    path: PathBuf,
+    temporary: bool,
+    /// The closures imported into the context that the library can call,
+    /// which are dropped after the library is unloaded.
+    closures: Vec<Rc<dyn Any>>
+}
+
+/// A function or global of a LoadedLibrary, which can't outlive it.
+#[derive(Debug)]
+pub struct LoadedSymbol<'lib, T> {
+    value: T,
+    marker: PhantomData<&'lib LoadedLibrary>
+}
+
+impl<'lib, T> Deref for LoadedSymbol<'lib, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl LoadedLibrary {
+    /// Gets the path the library was written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets an exported function of the library as a function pointer of
+    /// type T, such as extern "C" fn(i32) -> i32, or None if the library
+    /// exports no symbol with the given name.
+    ///
+    /// # Safety
+    /// This function is unsafe because the caller must ensure that T is an
+    /// extern "C" function pointer type whose signature matches the one
+    /// that the function was declared with.
+    ///
+    /// # Panics
+    /// Panics if T isn't the size of a pointer.
+    pub unsafe fn function<T: Copy>(&self, name: &str) -> Option<LoadedSymbol<'_, T>> {
+        assert_eq!(mem::size_of::<T>(), mem::size_of::<*mut c_void>(),
+                   "LoadedLibrary::function must be used with a function pointer type");
+        let ptr = self.symbol(name);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(LoadedSymbol { value: mem::transmute_copy(&ptr), marker: PhantomData })
+        }
+    }
+
+    /// Gets a pointer to an exported global of the library, or None if the
+    /// library exports no symbol with the given name.
+    pub fn global<T>(&self, name: &str) -> Option<LoadedSymbol<'_, *mut T>> {
+        let ptr = self.symbol(name);
+        if ptr.is_null() {
+            None
+        } else {
+            Some(LoadedSymbol { value: ptr as *mut T, marker: PhantomData })
+        }
+    }
+
+    fn symbol(&self, name: &str) -> *mut c_void {
+        let cstr = CString::new(name).unwrap();
+        unsafe { dlsym(self.handle, cstr.as_ptr()) }
+    }
+}
+
+impl Drop for LoadedLibrary {
+    fn drop(&mut self) {
+        unsafe {
+            dlclose(self.handle);
+        }
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+pub fn compile_and_load(ctx: &Context) -> io::Result<LoadedLibrary> {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!("gccjit-library-{}-{}.so",
+                                            process::id(),
+                                            NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    ctx.compile_to_file_unchecked(OutputKind::DynamicLibrary, &path.to_string_lossy());
+    match load(&path, true) {
+        Ok(mut library) => {
+            library.closures = ctx.closures();
+            Ok(library)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&path);
+            Err(err)
+        }
+    }
+}
+
+/// Loads the shared library at the given path, which is deleted when the
+/// library is unloaded if it is temporary.
+pub fn load(path: &Path, temporary: bool) -> io::Result<LoadedLibrary> {
+    let cstr = CString::new(path.to_string_lossy().into_owned()).unwrap();
+    let handle = unsafe { dlopen(cstr.as_ptr(), RTLD_NOW) };
+    if handle.is_null() {
+        let message = unsafe {
+            let err = dlerror();
+            if err.is_null() {
+                "failed to load the compiled library".to_owned()
+            } else {
+                CStr::from_ptr(err).to_string_lossy().into_owned()
+            }
+        };
+        return Err(io::Error::other(message));
+    }
+    Ok(LoadedLibrary { handle, path: path.to_owned(), temporary, closures: Vec::new() })
+}
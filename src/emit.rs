@@ -0,0 +1,448 @@
+//! Generation of declarations of the symbols that a context exports, so
+//! that the outputs of compile_to_file can be used from other languages.
+
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::path::Path;
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types::*;
+
+use abi::is_void;
+use context::GlobalKind;
+use function::FunctionType;
+use tracking::{self, ContextInfo, Registry, TypeKind};
+
+/// Generates a C header declaring the exported functions and globals of a
+/// context, along with the structs and unions that their signatures use.
+pub fn c_header(ctx: *mut gccjit_sys::gcc_jit_context, path: &Path) -> String {
+    let guard = include_guard(path);
+    tracking::with_registry(|reg| {
+        let info = match reg.get_context(ctx) {
+            Some(info) => info,
+            None => return empty_header(&guard)
+        };
+        let mut out = String::new();
+        out.push_str("/* Generated by gccjit.rs. */\n");
+        out.push_str(&format!("#ifndef {}\n#define {}\n\n", guard, guard));
+        out.push_str("#include <stdbool.h>\n#include <stddef.h>\n#include <stdio.h>\n\n");
+        out.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n");
+
+        let aggregates = exported_aggregates(reg, info);
+        for &ty in &aggregates {
+            out.push_str(&format!("{};\n", type_name(ty)));
+        }
+        if !aggregates.is_empty() {
+            out.push('\n');
+        }
+        for &ty in &aggregates {
+            let fields = match *reg.type_kind(ty).unwrap() {
+                TypeKind::Struct(ref fields) | TypeKind::Union(ref fields) => fields,
+                _ => continue
+            };
+            out.push_str(&format!("{} {{\n", type_name(ty)));
+            for &field in fields {
+                let name = unsafe { debug_string(gccjit_sys::gcc_jit_field_as_object(field)) };
+                let decl = match (reg.field_type(field), reg.field_width(field)) {
+                    (Some(field_ty), Some(width)) => format!("{} : {}", c_decl(reg, field_ty, name), width),
+                    (Some(field_ty), None) => c_decl(reg, field_ty, name),
+                    (None, _) => name
+                };
+                out.push_str(&format!("    {};\n", decl));
+            }
+            out.push_str("};\n\n");
+        }
+
+        for func in info.functions.iter().filter(|f| f.kind == FunctionType::Exported) {
+            let mut params : Vec<_> = func.params.iter()
+                .map(|&param| unsafe {
+                    let name = debug_string(gccjit_sys::gcc_jit_param_as_object(param));
+                    c_decl(reg, param_type(param), name)
+                })
+                .collect();
+            if func.is_variadic {
+                params.push("...".to_owned());
+            }
+            if params.is_empty() {
+                params.push("void".to_owned());
+            }
+            let declarator = format!("{}({})", func.name, params.join(", "));
+            out.push_str(&format!("{};\n", c_decl(reg, func.return_type, declarator)));
+        }
+        for global in info.globals.iter().filter(|g| g.kind == GlobalKind::Exported) {
+            out.push_str(&format!("extern {};\n", c_decl(reg, global.ty, global.name.clone())));
+        }
+
+        out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n");
+        out.push_str(&format!("#endif /* {} */\n", guard));
+        out
+    })
+}
+
+/// Generates Rust bindings for the exported functions and globals of a
+/// context: definitions of the structs and unions that they use, extern
+/// declarations in a ffi module, and wrapper functions around them.
+pub fn rust_bindings(ctx: *mut gccjit_sys::gcc_jit_context) -> String {
+    tracking::with_registry(|reg| {
+        let mut out = String::new();
+        out.push_str("// Generated by gccjit.rs.\n\n");
+        let info = match reg.get_context(ctx) {
+            Some(info) => info,
+            None => return out
+        };
+
+        for ty in exported_aggregates(reg, info) {
+            let name = aggregate_name(ty);
+            out.push_str("#[repr(C)]\n#[allow(non_camel_case_types, non_snake_case)]\n");
+            let (keyword, fields) = match *reg.type_kind(ty).unwrap() {
+                TypeKind::Struct(ref fields) => ("struct", fields),
+                TypeKind::Union(ref fields) => ("union", fields),
+                _ => {
+                    out.push_str(&format!("pub struct {} {{\n    _private: [u8; 0]\n}}\n\n", name));
+                    continue;
+                }
+            };
+            // Rust has no bitfields, so structs with them are left opaque.
+            let fields : Option<Vec<_>> = fields.iter()
+                .enumerate()
+                .map(|(idx, &field)| {
+                    if reg.field_width(field).is_some() {
+                        return None;
+                    }
+                    let name = unsafe { debug_string(gccjit_sys::gcc_jit_field_as_object(field)) };
+                    let ty = rust_type(reg, reg.field_type(field)?)?;
+                    Some(format!("    pub {}: {},\n", rust_ident(&name, idx), ty))
+                })
+                .collect();
+            match fields {
+                Some(fields) => {
+                    out.push_str("#[derive(Copy, Clone)]\n");
+                    out.push_str(&format!("pub {} {} {{\n{}}}\n\n", keyword, name, fields.concat()));
+                }
+                None => {
+                    out.push_str(&format!("pub struct {} {{\n    _private: [u8; 0]\n}}\n\n", name));
+                }
+            }
+        }
+
+        // (name, parameters, return type, whether the function is variadic)
+        let mut functions = vec![];
+        let mut skipped = vec![];
+        for func in info.functions.iter().filter(|f| f.kind == FunctionType::Exported) {
+            let params : Option<Vec<_>> = func.params.iter()
+                .enumerate()
+                .map(|(idx, &param)| unsafe {
+                    let name = debug_string(gccjit_sys::gcc_jit_param_as_object(param));
+                    rust_type(reg, param_type(param)).map(|ty| (rust_ident(&name, idx), ty))
+                })
+                .collect();
+            let ret = if is_void(reg, func.return_type) {
+                Some(None)
+            } else {
+                rust_type(reg, func.return_type).map(Some)
+            };
+            match (params, ret) {
+                (Some(params), Some(ret)) => functions.push((func.name.clone(), params, ret, func.is_variadic)),
+                _ => skipped.push(func.name.clone())
+            }
+        }
+        let mut globals = vec![];
+        for global in info.globals.iter().filter(|g| g.kind == GlobalKind::Exported) {
+            match rust_type(reg, global.ty) {
+                Some(ty) => globals.push((global.name.clone(), ty)),
+                None => skipped.push(global.name.clone())
+            }
+        }
+        for name in &skipped {
+            out.push_str(&format!("// {} is skipped because its type has no Rust equivalent.\n", name));
+        }
+        if !skipped.is_empty() {
+            out.push('\n');
+        }
+
+        out.push_str("pub mod ffi {\n    #[allow(unused_imports)]\n    use super::*;\n\n");
+        out.push_str("    #[allow(non_snake_case, non_upper_case_globals)]\n    unsafe extern \"C\" {\n");
+        for &(ref name, ref params, ref ret, variadic) in &functions {
+            let mut params : Vec<_> = params.iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            if variadic {
+                params.push("...".to_owned());
+            }
+            out.push_str(&link_name(name));
+            out.push_str(&format!("        pub fn {}({}){};\n", rust_ident(name, 0), params.join(", "), return_suffix(ret)));
+        }
+        for (name, ty) in &globals {
+            out.push_str(&link_name(name));
+            out.push_str(&format!("        pub static mut {}: {};\n", rust_ident(name, 0), ty));
+        }
+        out.push_str("    }\n}\n");
+
+        // functions taking pointers can't be called safely, and variadic
+        // functions can't be wrapped without fixing their arguments.
+        for &(ref name, ref params, ref ret, variadic) in &functions {
+            if variadic {
+                continue;
+            }
+            let name = rust_ident(name, 0);
+            let takes_pointers = params.iter().any(|(_, ty)| ty.starts_with('*'));
+            let decls : Vec<_> = params.iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty))
+                .collect();
+            let args : Vec<_> = params.iter().map(|(name, _)| name.clone()).collect();
+            out.push_str("\n#[allow(non_snake_case)]\n");
+            out.push_str(&format!("pub {}fn {}({}){} {{\n    unsafe {{ ffi::{}({}) }}\n}}\n",
+                                  if takes_pointers { "unsafe " } else { "" },
+                                  name, decls.join(", "), return_suffix(ret),
+                                  name, args.join(", ")));
+        }
+        for (name, ty) in &globals {
+            let name = rust_ident(name, 0);
+            out.push_str("\n#[allow(non_snake_case, unused_unsafe)]\n");
+            out.push_str(&format!("pub fn {}_ptr() -> *mut {} {{\n    unsafe {{ ::std::ptr::addr_of_mut!(ffi::{}) }}\n}}\n",
+                                  name, ty, name));
+        }
+        out
+    })
+}
+
+/// Keeps the symbol name of a declaration whose name had to be changed to
+/// be a valid Rust identifier.
+fn link_name(name: &str) -> String {
+    if rust_ident(name, 0) == name {
+        String::new()
+    } else {
+        format!("        #[link_name = \"{}\"]\n", name)
+    }
+}
+
+fn return_suffix(ret: &Option<String>) -> String {
+    match *ret {
+        Some(ref ty) => format!(" -> {}", ty),
+        None => String::new()
+    }
+}
+
+/// Spells out the Rust type corresponding to a type, or None if there is
+/// no equivalent.
+fn rust_type(reg: &Registry, ty: *mut gccjit_sys::gcc_jit_type) -> Option<String> {
+    match *reg.type_kind(ty)? {
+        TypeKind::Basic(ref kind) => rust_basic_name(kind).map(|s| s.to_owned()),
+        TypeKind::Pointer(pointee) => {
+            let (mutability, pointee) = match *reg.type_kind(pointee)? {
+                TypeKind::Const(base) => ("const", base),
+                _ => ("mut", pointee)
+            };
+            let pointee = if is_void(reg, pointee) {
+                "::std::os::raw::c_void".to_owned()
+            } else {
+                rust_type(reg, pointee)?
+            };
+            Some(format!("*{} {}", mutability, pointee))
+        }
+        TypeKind::Const(base) | TypeKind::Volatile(base) => rust_type(reg, base),
+        TypeKind::Array(elem, count) => Some(format!("[{}; {}]", rust_type(reg, elem)?, count)),
+        TypeKind::Vector(..) => None,
+        TypeKind::Struct(_) | TypeKind::Union(_) | TypeKind::Opaque => Some(aggregate_name(ty))
+    }
+}
+
+fn rust_basic_name(kind: &gccjit_sys::gcc_jit_types) -> Option<&'static str> {
+    let name = match *kind {
+        GCC_JIT_TYPE_VOID_PTR |
+        GCC_JIT_TYPE_FILE_PTR => "*mut ::std::os::raw::c_void",
+        GCC_JIT_TYPE_BOOL => "bool",
+        GCC_JIT_TYPE_CHAR => "::std::os::raw::c_char",
+        GCC_JIT_TYPE_SIGNED_CHAR => "::std::os::raw::c_schar",
+        GCC_JIT_TYPE_UNSIGNED_CHAR => "::std::os::raw::c_uchar",
+        GCC_JIT_TYPE_SHORT => "::std::os::raw::c_short",
+        GCC_JIT_TYPE_UNSIGNED_SHORT => "::std::os::raw::c_ushort",
+        GCC_JIT_TYPE_INT => "::std::os::raw::c_int",
+        GCC_JIT_TYPE_UNSIGNED_INT => "::std::os::raw::c_uint",
+        GCC_JIT_TYPE_LONG => "::std::os::raw::c_long",
+        GCC_JIT_TYPE_UNSIGNED_LONG => "::std::os::raw::c_ulong",
+        GCC_JIT_TYPE_LONG_LONG => "::std::os::raw::c_longlong",
+        GCC_JIT_TYPE_UNSIGNED_LONG_LONG => "::std::os::raw::c_ulonglong",
+        GCC_JIT_TYPE_FLOAT => "f32",
+        GCC_JIT_TYPE_DOUBLE => "f64",
+        GCC_JIT_TYPE_CONST_CHAR_PTR => "*const ::std::os::raw::c_char",
+        GCC_JIT_TYPE_SIZE_T => "usize",
+        GCC_JIT_TYPE_VOID |
+        GCC_JIT_TYPE_LONG_DOUBLE |
+        GCC_JIT_TYPE_COMPLEX_FLOAT |
+        GCC_JIT_TYPE_COMPLEX_DOUBLE |
+        GCC_JIT_TYPE_COMPLEX_LONG_DOUBLE => return None
+    };
+    Some(name)
+}
+
+/// Gets the tag of a struct or union, without the struct or union keyword.
+fn aggregate_name(ty: *mut gccjit_sys::gcc_jit_type) -> String {
+    let name = type_name(ty);
+    let tag = name.trim_start_matches("struct ").trim_start_matches("union ");
+    rust_ident(tag, 0)
+}
+
+/// Turns a C identifier into a Rust one, falling back to argN when the
+/// name can't be used.
+fn rust_ident(name: &str, idx: usize) -> String {
+    const KEYWORDS: &[&str] = &["as", "break", "const", "continue", "crate", "else", "enum",
+                                "extern", "false", "fn", "for", "if", "impl", "in", "let",
+                                "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+                                "self", "Self", "static", "struct", "super", "trait", "true",
+                                "type", "unsafe", "use", "where", "while", "async", "await",
+                                "dyn", "abstract", "become", "box", "do", "final", "macro",
+                                "override", "priv", "typeof", "unsized", "virtual", "yield",
+                                "try", "gen"];
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') &&
+        name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') &&
+        name != "_";
+    if !valid {
+        format!("arg{}", idx)
+    } else if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+fn empty_header(guard: &str) -> String {
+    format!("/* Generated by gccjit.rs. */\n#ifndef {}\n#define {}\n#endif /* {} */\n",
+            guard, guard, guard)
+}
+
+/// Derives an include guard such as FOO_BAR_H from a path like foo-bar.h.
+fn include_guard(path: &Path) -> String {
+    let stem = path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut guard : String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if guard.chars().next().is_none_or(|c| c.is_ascii_digit()) {
+        guard.insert(0, '_');
+    }
+    guard.push_str("_H");
+    guard
+}
+
+/// Collects the structs and unions used by the exported symbols of a
+/// context, ordered so that every aggregate comes after the aggregates
+/// that it contains by value.
+pub(crate) fn exported_aggregates(reg: &Registry, info: &ContextInfo) -> Vec<*mut gccjit_sys::gcc_jit_type> {
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    for func in info.functions.iter().filter(|f| f.kind == FunctionType::Exported) {
+        visit(reg, func.return_type, &mut seen, &mut order);
+        for &param in &func.params {
+            visit(reg, unsafe { param_type(param) }, &mut seen, &mut order);
+        }
+    }
+    for global in info.globals.iter().filter(|g| g.kind == GlobalKind::Exported) {
+        visit(reg, global.ty, &mut seen, &mut order);
+    }
+    order
+}
+
+fn visit(reg: &Registry,
+         ty: *mut gccjit_sys::gcc_jit_type,
+         seen: &mut HashSet<*mut gccjit_sys::gcc_jit_type>,
+         order: &mut Vec<*mut gccjit_sys::gcc_jit_type>) {
+    let ty = reg.unqualified(ty);
+    match reg.type_kind(ty) {
+        Some(&TypeKind::Pointer(pointee)) | Some(&TypeKind::Array(pointee, _)) => {
+            visit(reg, pointee, seen, order)
+        }
+        Some(&TypeKind::Struct(ref fields)) | Some(&TypeKind::Union(ref fields)) if seen.insert(ty) => {
+            for &field in fields {
+                if let Some(field_ty) = reg.field_type(field) {
+                    visit(reg, field_ty, seen, order);
+                }
+            }
+            order.push(ty);
+        }
+        Some(&TypeKind::Opaque) if seen.insert(ty) => order.push(ty),
+        _ => {}
+    }
+}
+
+/// Spells out a C declaration of the given name with the given type.
+pub(crate) fn c_decl(reg: &Registry, ty: *mut gccjit_sys::gcc_jit_type, declarator: String) -> String {
+    let join = |base: &str, declarator: String| {
+        if declarator.is_empty() {
+            base.to_owned()
+        } else if base.ends_with('*') {
+            format!("{}{}", base, declarator)
+        } else {
+            format!("{} {}", base, declarator)
+        }
+    };
+    match reg.type_kind(ty) {
+        Some(TypeKind::Basic(kind)) => join(c_basic_name(kind), declarator),
+        Some(&TypeKind::Pointer(pointee)) => {
+            let declarator = match reg.type_kind(reg.unqualified(pointee)) {
+                Some(&TypeKind::Array(..)) => format!("(*{})", declarator),
+                _ => format!("*{}", declarator)
+            };
+            c_decl(reg, pointee, declarator)
+        }
+        Some(&TypeKind::Const(base)) | Some(&TypeKind::Volatile(base)) => {
+            let qualifier = match reg.type_kind(ty) {
+                Some(&TypeKind::Const(_)) => "const",
+                _ => "volatile"
+            };
+            // qualifiers of pointers go after the star.
+            if let Some(&TypeKind::Pointer(_)) = reg.type_kind(base) {
+                c_decl(reg, base, join(qualifier, declarator))
+            } else {
+                format!("{} {}", qualifier, c_decl(reg, base, declarator))
+            }
+        }
+        Some(&TypeKind::Array(elem, count)) => {
+            c_decl(reg, elem, format!("{}[{}]", declarator, count))
+        }
+        _ => join(&type_name(ty), declarator)
+    }
+}
+
+fn c_basic_name(kind: &gccjit_sys::gcc_jit_types) -> &'static str {
+    match *kind {
+        GCC_JIT_TYPE_VOID => "void",
+        GCC_JIT_TYPE_VOID_PTR => "void *",
+        GCC_JIT_TYPE_BOOL => "bool",
+        GCC_JIT_TYPE_CHAR => "char",
+        GCC_JIT_TYPE_SIGNED_CHAR => "signed char",
+        GCC_JIT_TYPE_UNSIGNED_CHAR => "unsigned char",
+        GCC_JIT_TYPE_SHORT => "short",
+        GCC_JIT_TYPE_UNSIGNED_SHORT => "unsigned short",
+        GCC_JIT_TYPE_INT => "int",
+        GCC_JIT_TYPE_UNSIGNED_INT => "unsigned int",
+        GCC_JIT_TYPE_LONG => "long",
+        GCC_JIT_TYPE_UNSIGNED_LONG => "unsigned long",
+        GCC_JIT_TYPE_LONG_LONG => "long long",
+        GCC_JIT_TYPE_UNSIGNED_LONG_LONG => "unsigned long long",
+        GCC_JIT_TYPE_FLOAT => "float",
+        GCC_JIT_TYPE_DOUBLE => "double",
+        GCC_JIT_TYPE_LONG_DOUBLE => "long double",
+        GCC_JIT_TYPE_CONST_CHAR_PTR => "const char *",
+        GCC_JIT_TYPE_SIZE_T => "size_t",
+        GCC_JIT_TYPE_FILE_PTR => "FILE *",
+        GCC_JIT_TYPE_COMPLEX_FLOAT => "_Complex float",
+        GCC_JIT_TYPE_COMPLEX_DOUBLE => "_Complex double",
+        GCC_JIT_TYPE_COMPLEX_LONG_DOUBLE => "_Complex long double"
+    }
+}
+
+/// Gets the name gccjit uses for a type, such as "struct foo".
+pub(crate) fn type_name(ty: *mut gccjit_sys::gcc_jit_type) -> String {
+    unsafe { debug_string(gccjit_sys::gcc_jit_type_as_object(ty)) }
+}
+
+pub(crate) unsafe fn param_type(param: *mut gccjit_sys::gcc_jit_param) -> *mut gccjit_sys::gcc_jit_type {
+    gccjit_sys::gcc_jit_rvalue_get_type(gccjit_sys::gcc_jit_param_as_rvalue(param))
+}
+
+pub(crate) unsafe fn debug_string(obj: *mut gccjit_sys::gcc_jit_object) -> String {
+    let ptr = gccjit_sys::gcc_jit_object_get_debug_string(obj);
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+use rvalue::RValue;
+use types::Type;
+
+/// An EnumType is a C-style enumeration: an integer type along with a set
+/// of named constants of that type. gccjit has no enum types of its own,
+/// so values of an EnumType are simply values of its underlying type.
+#[derive(Clone)]
+pub struct EnumType<'ctx> {
+    name: String,
+    ty: Type<'ctx>,
+    variants: Vec<(String, RValue<'ctx>)>
+}
+
+impl<'ctx> EnumType<'ctx> {
+    /// Gets the name that this enum was created with.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the underlying integer type of this enum.
+    pub fn as_type(&self) -> Type<'ctx> {
+        self.ty
+    }
+
+    /// Gets the constant for the variant with the given name, if there
+    /// is one.
+    pub fn variant<S: AsRef<str>>(&self, name: S) -> Option<RValue<'ctx>> {
+        self.variants.iter()
+            .find(|&(variant, _)| variant == name.as_ref())
+            .map(|&(_, value)| value)
+    }
+
+    /// Gets the names and constants of every variant, in the order that
+    /// they were given.
+    pub fn variants(&self) -> &[(String, RValue<'ctx>)] {
+        &self.variants
+    }
+}
+
+impl<'ctx> fmt::Debug for EnumType<'ctx> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "enum {} : {:?}", self.name, self.ty)
+    }
+}
+
+pub fn new<'ctx>(name: String,
+                 ty: Type<'ctx>,
+                 variants: Vec<(String, RValue<'ctx>)>) -> EnumType<'ctx> {
+    EnumType {
+        name,
+        ty,
+        variants
+    }
+}
@@ -6,6 +6,8 @@ use std::fmt;
 use context::Context;
 use object::{ToObject, Object};
 use object;
+use tracking;
+use types::{self, Type};
 
 /// Field represents a field that composes structs or unions. A number of fields
 /// can be combined to create either a struct or a union.
@@ -15,6 +17,24 @@ pub struct Field<'ctx> {
     ptr: *mut gccjit_sys::gcc_jit_field
 }
 
+impl<'ctx> Field<'ctx> {
+    /// Gets the name that the field was created with.
+    pub fn name(&self) -> String {
+        let ptr = self.ptr;
+        tracking::with_registry(|reg| reg.field_name(ptr).map(|name| name.to_owned()))
+            .unwrap_or_else(|| format!("{:?}", self))
+    }
+
+    /// Gets the type that the field was created with.
+    pub fn get_type(&self) -> Type<'ctx> {
+        let ptr = self.ptr;
+        match tracking::with_registry(|reg| reg.field_type(ptr)) {
+            Some(ty) => unsafe { types::from_ptr(ty) },
+            None => panic!("the type of {:?} wasn't recorded", self)
+        }
+    }
+}
+
 impl<'ctx> ToObject<'ctx> for Field<'ctx> {
     fn to_object(&self) -> Object<'ctx> {
         unsafe {
@@ -1,5 +1,8 @@
 use std::marker::PhantomData;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::ptr;
 use context::Context;
 use gccjit_sys;
@@ -16,6 +19,13 @@ use location::Location;
 use location;
 use types::Type;
 use types;
+use abi;
+use attribute::{self, FnAttribute, VarAttribute, Visibility};
+use dot;
+use loops::{self, WhileLoop};
+use rvalue::{self, RValue, ToRValue};
+use scalarize::{self, Scalarized};
+use tracking::{self, BlockInfo, FunctionInfo, Registry};
 
 /// FunctionType informs gccjit what sort of function a new function will be.
 /// An exported function is a function that will be exported using the CompileResult
@@ -24,6 +34,7 @@ use types;
 /// is a function with external linkage, and always inline is a function that is
 /// always inlined wherever it is called and cannot be accessed outside of the jit.
 #[repr(C)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum FunctionType {
     /// Defines a function that is "exported" by the JIT and can be called from
     /// Rust.
@@ -40,6 +51,19 @@ pub enum FunctionType {
     AlwaysInline
 }
 
+/// InlineMode controls whether calls to a function are inlined, set with
+/// Function::set_inline_mode.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InlineMode {
+    /// Inlines the function wherever it is called, even when not
+    /// optimizing.
+    AlwaysInline,
+    /// Never inlines the function.
+    NoInline,
+    /// Hints that the function should be inlined, like C's inline.
+    Inline
+}
+
 /// Function is gccjit's representation of a function. Functions are constructed
 /// by constructing basic blocks and connecting them together. Locals are declared
 /// at the function level.
@@ -73,6 +97,128 @@ impl<'ctx> Function<'ctx> {
         }
     }
 
+    /// Adds a GNU C attribute to this function, such as noinline or
+    /// target("avx2"), to pass facts to the optimizer that it can't
+    /// infer on its own.
+    pub fn add_attribute(&self, attribute: FnAttribute) {
+        unsafe {
+            attribute::add_fn_attribute(self.ptr, attribute);
+        }
+    }
+
+    /// Whether a call to this function with the given number of arguments
+    /// has the right arity. Functions that weren't created through this
+    /// crate's Context::new_function accept any number of arguments.
+    pub fn accepts_argument_count(&self, count: usize) -> bool {
+        let func = self.ptr;
+        tracking::with_registry(|reg| {
+            match reg.function(func) {
+                Some(info) if info.is_variadic => count >= info.params.len(),
+                Some(info) => count == info.params.len(),
+                None => true
+            }
+        })
+    }
+
+    /// Sets the personality function that the unwinder uses to find the
+    /// handlers of this function's try/catch statements, such as
+    /// __gxx_personality_v0 for C++ exceptions.
+    #[cfg(feature = "master")]
+    pub fn set_personality_function(&self, personality: Function<'ctx>) {
+        unsafe {
+            gccjit_sys::gcc_jit_function_set_personality_function(self.ptr, personality.ptr);
+        }
+    }
+
+    /// Compiles this function for a different target than the rest of the
+    /// context, given as a comma-separated list of GNU C target options
+    /// such as "avx2,fma" or "arch=skylake". Callers must make sure the
+    /// host supports the target before calling the function.
+    pub fn set_target<S: AsRef<str>>(&self, target: S) {
+        self.add_attribute(FnAttribute::Target(target.as_ref()));
+    }
+
+    /// Makes this function a weak symbol, so that when the context is
+    /// compiled to an object file and linked into a larger program, a
+    /// strong definition elsewhere replaces it.
+    pub fn set_weak(&self) {
+        self.add_attribute(FnAttribute::Weak);
+    }
+
+    /// Makes this function run automatically when the code is loaded,
+    /// before main for executables, like the GNU C constructor attribute.
+    /// Constructors with lower priorities run first, and ones without a
+    /// priority run last; priorities 0 to 100 are reserved for the
+    /// implementation. The function must take no arguments. Only ELF
+    /// targets are supported.
+    pub fn set_constructor(&self, priority: Option<u16>) {
+        self.add_to_init_array(".init_array", "ctor", priority);
+    }
+
+    /// Makes this function run automatically when the code is unloaded or
+    /// the program exits, like the GNU C destructor attribute. Destructors
+    /// with lower priorities run last. See set_constructor.
+    pub fn set_destructor(&self, priority: Option<u16>) {
+        self.add_to_init_array(".fini_array", "dtor", priority);
+    }
+
+    /// Places a pointer to this function in one of the ELF sections that
+    /// the loader runs functions from. libgccjit has no constructor or
+    /// destructor attributes, so this is done with a hidden global. The
+    /// global is named after the id of the context, so that contexts that
+    /// are linked together don't define the same symbol, and it isn't
+    /// recorded, so write_c_header and write_rust_bindings leave it out.
+    fn add_to_init_array(&self, section: &str, prefix: &str, priority: Option<u16>) {
+        let section = match priority {
+            Some(priority) => format!("{}.{:05}", section, priority),
+            None => section.to_owned()
+        };
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let name = tracking::with_context(ctx, |info| {
+                let number = info.next_internal_global();
+                format!("__gccjit_{}_{}_{}", prefix, info.id, number)
+            });
+            let address = gccjit_sys::gcc_jit_function_get_address(self.ptr, ptr::null_mut());
+            let cname = CString::new(name).unwrap();
+            let global = gccjit_sys::gcc_jit_context_new_global(ctx,
+                                                                ptr::null_mut(),
+                                                                gccjit_sys::gcc_jit_global_kind::GCC_JIT_GLOBAL_EXPORTED,
+                                                                gccjit_sys::gcc_jit_rvalue_get_type(address),
+                                                                cname.as_ptr());
+            gccjit_sys::gcc_jit_global_set_initializer_rvalue(global, address);
+            let csection = CString::new(section).unwrap();
+            gccjit_sys::gcc_jit_lvalue_set_link_section(global, csection.as_ptr());
+            // the global is exported so that it isn't discarded as unused.
+            attribute::add_var_attribute(global, VarAttribute::Visibility(Visibility::Hidden));
+        }
+    }
+
+    /// Sets whether calls to this function are inlined. Unlike
+    /// FunctionType::AlwaysInline, this doesn't change the function's
+    /// linkage, so an exported function can be inlined into jitted
+    /// callers and still be called from Rust. Functions whose mode isn't
+    /// set are inlined as gcc's heuristics decide.
+    ///
+    /// # Panics
+    /// Panics if the mode was already set. The mode is added as an
+    /// attribute, which can't be removed again.
+    pub fn set_inline_mode(&self, mode: InlineMode) {
+        let func = self.ptr;
+        let previous = tracking::with_registry(|reg| {
+            reg.function_mut(func).and_then(|info| info.inline_mode.replace(mode))
+        });
+        if let Some(previous) = previous {
+            panic!("the inline mode of {:?} was already set to {:?}", self, previous);
+        }
+        let attribute = match mode {
+            InlineMode::AlwaysInline => FnAttribute::AlwaysInline,
+            InlineMode::NoInline => FnAttribute::NoInline,
+            InlineMode::Inline => FnAttribute::Inline
+        };
+        self.add_attribute(attribute);
+    }
+
     pub fn dump_to_dot<S: AsRef<str>>(&self, path: S) {
         unsafe {
             let cstr = CString::new(path.as_ref()).unwrap();
@@ -80,11 +226,64 @@ impl<'ctx> Function<'ctx> {
         }
     }
 
+    /// Renders the control flow graph of this function in Graphviz's dot
+    /// format. Unlike dump_to_dot, which uses gccjit's own renderer, each
+    /// block lists every statement added to it and the source location
+    /// it was added with. The statements, and so the edges between blocks,
+    /// are only known if tracking was enabled with Context::set_tracking.
+    pub fn to_dot(&self) -> String {
+        let func = self.ptr;
+        tracking::with_registry(|reg| {
+            reg.function(func).map_or_else(String::new, |info| dot::function_graph(reg, info))
+        })
+    }
+
+    /// Writes the graph rendered by to_dot to a file.
+    pub fn write_dot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_dot())
+    }
+
+    /// Starts a while loop by ending the given block with a jump to a new
+    /// loop header. The blocks of the loop are named after the given name.
+    pub fn new_while_loop<S: AsRef<str>>(&self,
+                                         loc: Option<Location<'ctx>>,
+                                         from: Block<'ctx>,
+                                         name: S) -> WhileLoop<'ctx> {
+        loops::new_while_loop(self, loc, from, name.as_ref())
+    }
+
+    /// Copies the fields of a struct value into locals of their own at the
+    /// end of the given block, named after the given name. Code that
+    /// updates the fields through those locals instead of a struct local
+    /// is easier for GCC to keep in registers; Scalarized::reassemble puts
+    /// the struct back together afterwards. Returns None if the value
+    /// isn't a struct created through this crate.
+    pub fn scalarize<S: AsRef<str>>(&self,
+                                    loc: Option<Location<'ctx>>,
+                                    block: Block<'ctx>,
+                                    value: RValue<'ctx>,
+                                    name: S) -> Option<Scalarized<'ctx>> {
+        scalarize::scalarize(*self, loc, block, value, name.as_ref())
+    }
+
     pub fn new_block<S: AsRef<str>>(&self, name: S) -> Block<'ctx> {
         unsafe {
             let cstr = CString::new(name.as_ref()).unwrap();
             let ptr = gccjit_sys::gcc_jit_function_new_block(self.ptr,
                                                              cstr.as_ptr());
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let func = self.ptr;
+            tracking::with_registry(|reg| {
+                if let Some(info) = reg.function_mut(func) {
+                    info.blocks.push(ptr);
+                }
+                reg.context(ctx).blocks.insert(ptr, BlockInfo {
+                    name: name.as_ref().to_owned(),
+                    function: func,
+                    statements: Vec::new(),
+                    default_location: ptr::null_mut()
+                });
+            });
             block::from_ptr(ptr)
         }
     }
@@ -103,9 +302,73 @@ impl<'ctx> Function<'ctx> {
                                                              loc_ptr,
                                                              types::get_ptr(&ty),
                                                              cstr.as_ptr());
+            let func = self.ptr;
+            tracking::with_registry(|reg| {
+                if let Some(info) = reg.function_mut(func) {
+                    info.locals.push(types::get_ptr(&ty));
+                }
+            });
             lvalue::from_ptr(ptr)
         }
     }
+
+    /// Allocates size bytes on this function's stack, like C's alloca, and
+    /// returns a void pointer to them. The memory is freed when the
+    /// function returns. If align is given, in bytes, the memory is aligned
+    /// to it with __builtin_alloca_with_align; otherwise it gets the
+    /// alignment of __builtin_alloca, which suits any type.
+    ///
+    /// Every evaluation of the returned RValue allocates again, so it
+    /// should be assigned to a local once, in a block of this function.
+    /// The allocations aren't counted by estimated_stack_bytes.
+    ///
+    /// # Panics
+    /// Panics if align isn't a power of two.
+    pub fn new_stack_allocation<S: ToRValue<'ctx>>(&self,
+                                                   loc: Option<Location<'ctx>>,
+                                                   size: S,
+                                                   align: Option<usize>) -> RValue<'ctx> {
+        let size = size.to_rvalue();
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let name = if align.is_some() { "__builtin_alloca_with_align" } else { "__builtin_alloca" };
+            let cname = CString::new(name).unwrap();
+            let builtin = gccjit_sys::gcc_jit_context_get_builtin_function(ctx, cname.as_ptr());
+            let size_ty = gccjit_sys::gcc_jit_rvalue_get_type(gccjit_sys::gcc_jit_param_as_rvalue(
+                gccjit_sys::gcc_jit_function_get_param(builtin, 0)));
+            let mut args = vec![gccjit_sys::gcc_jit_context_new_cast(ctx, loc_ptr, rvalue::get_ptr(&size), size_ty)];
+            if let Some(align) = align {
+                assert!(align.is_power_of_two(), "stack allocations can't be aligned to {} bytes", align);
+                // the builtin takes the alignment in bits.
+                args.push(gccjit_sys::gcc_jit_context_new_rvalue_from_long(ctx, size_ty, (align * 8) as _));
+            }
+            let call = gccjit_sys::gcc_jit_context_new_call(ctx, loc_ptr, builtin, args.len() as i32, args.as_mut_ptr());
+            rvalue::from_ptr(call)
+        }
+    }
+
+    /// Estimates the number of bytes of stack that this function's locals
+    /// occupy, laying them out one after another with the host's size and
+    /// alignment for each type. gcc may reuse or eliminate the storage of
+    /// locals, so this is an upper bound on what the locals need, not on
+    /// the size of the function's stack frame. Locals whose layout isn't
+    /// known aren't counted.
+    pub fn estimated_stack_bytes(&self) -> usize {
+        let func = self.ptr;
+        tracking::with_registry(|reg| {
+            reg.function(func).map_or(0, |info| stack_bytes(reg, info))
+        })
+    }
+}
+
+pub fn stack_bytes(reg: &Registry, func: &FunctionInfo) -> usize {
+    func.locals.iter()
+        .filter_map(|&ty| abi::size_and_align(reg, ty))
+        .fold(0, |offset, (size, align)| abi::round_up(offset, align) + size)
 }
 
 pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_function) -> Function<'ctx> {
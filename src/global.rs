@@ -0,0 +1,116 @@
+//! An optional process-wide context, for applications with a single JIT
+//! that would rather not thread a context lifetime through their code.
+//!
+//! The context is created the first time global is called and is never
+//! released. libgccjit contexts can't be used from several threads at
+//! once, so the global context can only be used from the thread that
+//! created it. Functions and types created from it can be registered by
+//! name and looked up again from anywhere on that thread.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, ThreadId};
+
+use context::{self, Context};
+use function::{self, Function};
+use object::{self, ToObject};
+use tracking;
+use types::{self, Type};
+
+struct Global {
+    /// The leaked context, stored as an address so that the static can be
+    /// shared between threads even though only one of them may use it.
+    context: usize,
+    thread: ThreadId,
+    functions: Mutex<HashMap<String, usize>>,
+    types: Mutex<HashMap<String, usize>>
+}
+
+static GLOBAL: OnceLock<Global> = OnceLock::new();
+
+fn get() -> &'static Global {
+    let global = GLOBAL.get_or_init(|| {
+        let context : &'static Context<'static> = Box::leak(Box::new(Context::default()));
+        Global {
+            context: context as *const Context<'static> as usize,
+            thread: thread::current().id(),
+            functions: Mutex::new(HashMap::new()),
+            types: Mutex::new(HashMap::new())
+        }
+    });
+    if global.thread != thread::current().id() {
+        panic!("the global context can only be used from the thread that created it");
+    }
+    global
+}
+
+/// Gets the process-wide context, creating it on first use.
+///
+/// # Panics
+/// Panics if called from a thread other than the one that first called it.
+pub fn global() -> &'static Context<'static> {
+    unsafe { &*(get().context as *const Context<'static>) }
+}
+
+/// Panics unless obj was created from the global context.
+fn check_global<'a, O: ToObject<'a>>(obj: &O) {
+    let owner = tracking::context_of(unsafe { object::get_ptr(&obj.to_object()) });
+    if owner != unsafe { context::get_ptr(global()) } {
+        panic!("{:?} wasn't created from the global context", obj.to_object());
+    }
+}
+
+/// Registers a function of the global context under a name, replacing any
+/// function already registered under it.
+///
+/// # Panics
+/// Panics if the function wasn't created from the global context.
+pub fn register_function<S: Into<String>>(name: S, func: Function<'static>) {
+    check_global(&func);
+    let ptr = unsafe { function::get_ptr(&func) } as usize;
+    get().functions.lock().unwrap().insert(name.into(), ptr);
+}
+
+/// Looks up a function registered with register_function.
+pub fn lookup_function(name: &str) -> Option<Function<'static>> {
+    let ptr = *get().functions.lock().unwrap().get(name)?;
+    Some(unsafe { function::from_ptr(ptr as *mut _) })
+}
+
+/// Registers a type of the global context under a name, replacing any
+/// type already registered under it.
+///
+/// # Panics
+/// Panics if the type wasn't created from the global context.
+pub fn register_type<S: Into<String>>(name: S, ty: Type<'static>) {
+    check_global(&ty);
+    let ptr = unsafe { types::get_ptr(&ty) } as usize;
+    get().types.lock().unwrap().insert(name.into(), ptr);
+}
+
+/// Looks up a type registered with register_type.
+pub fn lookup_type(name: &str) -> Option<Type<'static>> {
+    let ptr = *get().types.lock().unwrap().get(name)?;
+    Some(unsafe { types::from_ptr(ptr as *mut _) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use function::FunctionType;
+
+    // the global context is bound to the thread that creates it, so this
+    // is the only test that may use it.
+    #[test]
+    fn register_and_lookup() {
+        let int_ty = global().new_type::<i32>();
+        register_type("int", int_ty);
+        let fun = global().new_function(None, FunctionType::Exported, int_ty, &[], "answer", false);
+        register_function("answer", fun);
+
+        assert_eq!(format!("{:?}", lookup_type("int").unwrap()), "int");
+        assert_eq!(format!("{:?}", lookup_function("answer").unwrap()), "answer");
+        assert!(lookup_function("question").is_none());
+        assert!(thread::spawn(|| global().id()).join().is_err());
+    }
+}
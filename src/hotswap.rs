@@ -0,0 +1,179 @@
+//! Functions that can be replaced while they're being called, for
+//! long-running programs that recompile a function, say with more
+//! optimization or for changed code, in a fresh context.
+//!
+//! A HotSwap holds the current version of a function. Callers get the
+//! function through a guard, which keeps the version it was taken from
+//! alive, and replacing the function retires the old version. Each version
+//! counts its own guards, so the CompileResult of a retired version is
+//! released as soon as its last guard is dropped, even while guards of
+//! newer versions are being taken and dropped all the time.
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+use context::CompileResult;
+
+/// A function that can be replaced by a version compiled later, even
+/// while other threads are calling it. F is the extern "C" function
+/// pointer type of the function.
+pub struct HotSwap<F: Copy> {
+    name: String,
+    current: RwLock<Arc<Version>>,
+    /// The replaced versions, which are alive while guards refer to them.
+    retired: Mutex<Vec<Weak<Version>>>,
+    /// F is only ever made from the pointers of versions, so it doesn't
+    /// decide whether a HotSwap can be shared between threads.
+    marker: PhantomData<fn() -> F>
+}
+
+/// A version of the function, along with the result that contains it.
+struct Version {
+    func: *mut (),
+    /// Kept only to keep the code of func alive.
+    _result: CompileResult
+}
+
+// a version is only released once no guard can still be using its code,
+// and results with imported closures, whose reference counts aren't
+// thread-safe, are rejected.
+unsafe impl Send for Version {}
+unsafe impl Sync for Version {}
+
+/// The function of a HotSwap, which keeps the version it refers to alive.
+pub struct HotSwapGuard<'a, F: Copy> {
+    marker: PhantomData<&'a HotSwap<F>>,
+    /// Kept only to hold on to the version.
+    _version: Arc<Version>,
+    func: F
+}
+
+impl<F: Copy> HotSwap<F> {
+    /// Creates a HotSwap of the function with the given name in a result,
+    /// or returns None if the result has no such function.
+    ///
+    /// # Safety
+    /// This function is unsafe because the caller must ensure that F is an
+    /// extern "C" function pointer type whose signature matches the one
+    /// the function was declared with, here and in every result that
+    /// replaces it.
+    ///
+    /// # Panics
+    /// Panics if F isn't the size of a pointer, or if the result was
+    /// compiled from a context with imported closures.
+    pub unsafe fn new<S: Into<String>>(result: CompileResult, name: S) -> Option<HotSwap<F>> {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<*mut ()>(),
+                   "HotSwap must be used with a function pointer type");
+        check_result(&result);
+        let name = name.into();
+        let func = result.get_function(&name);
+        if func.is_null() {
+            return None;
+        }
+        Some(HotSwap {
+            name,
+            current: RwLock::new(Arc::new(Version { func, _result: result })),
+            retired: Mutex::new(vec![]),
+            marker: PhantomData
+        })
+    }
+
+    /// Gets the current version of the function. The version stays alive
+    /// while the guard does, even if the function is replaced meanwhile.
+    pub fn get(&self) -> HotSwapGuard<'_, F> {
+        let version = self.current.read().unwrap().clone();
+        let func = unsafe { mem::transmute_copy(&version.func) };
+        HotSwapGuard { marker: PhantomData, _version: version, func }
+    }
+
+    /// Replaces the function with the function of the same name in a new
+    /// result. Returns false, and keeps the current version, if the result
+    /// has no such function.
+    ///
+    /// # Panics
+    /// Panics if the result was compiled from a context with imported
+    /// closures.
+    pub fn replace(&self, result: CompileResult) -> bool {
+        check_result(&result);
+        let func = result.get_function(&self.name);
+        if func.is_null() {
+            return false;
+        }
+        let version = Arc::new(Version { func, _result: result });
+        let old = mem::replace(&mut *self.current.write().unwrap(), version);
+        let mut retired = self.retired.lock().unwrap();
+        retired.retain(|version| version.strong_count() > 0);
+        retired.push(Arc::downgrade(&old));
+        // the old version is released here unless a guard still has it.
+        drop(old);
+        true
+    }
+
+    /// Gets the number of replaced versions that are still kept alive by
+    /// guards.
+    pub fn retired_count(&self) -> usize {
+        let mut retired = self.retired.lock().unwrap();
+        retired.retain(|version| version.strong_count() > 0);
+        retired.len()
+    }
+}
+
+impl<'a, F: Copy> Deref for HotSwapGuard<'a, F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.func
+    }
+}
+
+fn check_result(result: &CompileResult) {
+    if result.has_closures() {
+        panic!("HotSwap can't hold results that call imported closures");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::Context;
+    use function::FunctionType;
+
+    fn compile_constant(value: i32) -> CompileResult {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "version", false);
+        fun.new_block("entry").end_with_return(None, context.new_rvalue_from_int(int_ty, value));
+        context.compile()
+    }
+
+    #[test]
+    fn replaced_version_lives_until_guard_drops() {
+        let swap = unsafe { HotSwap::<extern "C" fn() -> i32>::new(compile_constant(1), "version") }.unwrap();
+        let old = swap.get();
+        assert!(swap.replace(compile_constant(2)));
+        assert_eq!(swap.retired_count(), 1);
+        assert_eq!((*old)(), 1);
+        assert_eq!((*swap.get())(), 2);
+        drop(old);
+        assert_eq!(swap.retired_count(), 0);
+        assert!(!swap.replace(Context::default().compile()));
+    }
+
+    #[test]
+    fn retired_version_is_released_while_newer_guards_are_alive() {
+        let swap = unsafe { HotSwap::<extern "C" fn() -> i32>::new(compile_constant(1), "version") }.unwrap();
+        let first = swap.get();
+        assert!(swap.replace(compile_constant(2)));
+        let second = swap.get();
+        drop(first);
+        assert_eq!(swap.retired_count(), 0);
+        assert!(swap.replace(compile_constant(3)));
+        let third = swap.get();
+        assert_eq!(swap.retired_count(), 1);
+        drop(second);
+        assert_eq!(swap.retired_count(), 0);
+        assert_eq!((*third)(), 3);
+    }
+}
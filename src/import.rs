@@ -0,0 +1,159 @@
+use std::os::raw::c_void;
+
+use context::Context;
+use lvalue::LValue;
+use rvalue::RValue;
+use types::{Type, Typeable};
+
+/// ImportableFn is a trait for Rust function pointer types that can be
+/// called from jitted code. The parameter and return types of the function
+/// must all be Typeable, so that the corresponding gccjit signature can be
+/// derived from the Rust one. This trait is implemented for `extern "C"`
+/// function pointers (safe or unsafe) of up to eight parameters.
+pub trait ImportableFn: Copy {
+    /// Gets the gccjit type of the value returned by this function.
+    fn return_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a>;
+
+    /// Gets the gccjit types of the parameters of this function, in order.
+    fn param_types<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Vec<Type<'a>>;
+
+    /// Gets the address of this function.
+    fn as_ptr(self) -> *mut ();
+}
+
+macro_rules! importable_fn_def {
+    ($($arg:ident),*) => {
+        impl<R: Typeable $(, $arg: Typeable)*> ImportableFn for extern "C" fn($($arg),*) -> R {
+            fn return_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                <R as Typeable>::get_type(ctx)
+            }
+
+            #[allow(unused_variables)]
+            fn param_types<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Vec<Type<'a>> {
+                vec![$(<$arg as Typeable>::get_type(ctx)),*]
+            }
+
+            fn as_ptr(self) -> *mut () {
+                self as *mut ()
+            }
+        }
+
+        impl<R: Typeable $(, $arg: Typeable)*> ImportableFn for unsafe extern "C" fn($($arg),*) -> R {
+            fn return_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                <R as Typeable>::get_type(ctx)
+            }
+
+            #[allow(unused_variables)]
+            fn param_types<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Vec<Type<'a>> {
+                vec![$(<$arg as Typeable>::get_type(ctx)),*]
+            }
+
+            fn as_ptr(self) -> *mut () {
+                self as *mut ()
+            }
+        }
+    }
+}
+
+importable_fn_def!();
+importable_fn_def!(A);
+importable_fn_def!(A, B);
+importable_fn_def!(A, B, C);
+importable_fn_def!(A, B, C, D);
+importable_fn_def!(A, B, C, D, E);
+importable_fn_def!(A, B, C, D, E, F);
+importable_fn_def!(A, B, C, D, E, F, G);
+importable_fn_def!(A, B, C, D, E, F, G, H);
+
+/// ImportableClosure is a trait for closure types that can be imported
+/// with Context::import_closure, so that jitted code can call them. It is
+/// implemented for `dyn Fn` types of up to eight parameters whose
+/// parameter and return types are all Typeable.
+///
+/// A jitted call goes through a trampoline, an extern "C" function that
+/// takes a pointer to the boxed closure followed by the closure's
+/// parameters, and calls the closure. A panic in the closure aborts the
+/// process, since it can't unwind through jitted code.
+pub trait ImportableClosure {
+    /// Gets the gccjit type of the value returned by this closure.
+    fn return_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a>;
+
+    /// Gets the gccjit types of the parameters of this closure, in order.
+    fn param_types<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Vec<Type<'a>>;
+
+    /// Gets the address of the trampoline, which must be passed a pointer
+    /// to a Box<Self>.
+    fn trampoline() -> *mut ();
+}
+
+macro_rules! importable_closure_def {
+    ($($arg:ident: $ty:ident),*) => {
+        impl<R: Typeable $(, $ty: Typeable)*> ImportableClosure for dyn Fn($($ty),*) -> R {
+            fn return_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                <R as Typeable>::get_type(ctx)
+            }
+
+            #[allow(unused_variables)]
+            fn param_types<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Vec<Type<'a>> {
+                vec![$(<$ty as Typeable>::get_type(ctx)),*]
+            }
+
+            fn trampoline() -> *mut () {
+                extern "C" fn trampoline<R $(, $ty)*>(env: *const c_void $(, $arg: $ty)*) -> R {
+                    let closure = unsafe { &*(env as *const Box<dyn Fn($($ty),*) -> R>) };
+                    closure($($arg),*)
+                }
+                trampoline::<R $(, $ty)*> as extern "C" fn(*const c_void $(, $ty)*) -> R as *mut ()
+            }
+        }
+    }
+}
+
+importable_closure_def!();
+importable_closure_def!(a: A);
+importable_closure_def!(a: A, b: B);
+importable_closure_def!(a: A, b: B, c: C);
+importable_closure_def!(a: A, b: B, c: C, d: D);
+importable_closure_def!(a: A, b: B, c: C, d: D, e: E);
+importable_closure_def!(a: A, b: B, c: C, d: D, e: E, f: F);
+importable_closure_def!(a: A, b: B, c: C, d: D, e: E, f: F, g: G);
+importable_closure_def!(a: A, b: B, c: C, d: D, e: E, f: F, g: G, h: H);
+
+/// A host symbol imported with Context::register_symbol.
+#[derive(Copy, Clone, Debug)]
+pub enum Symbol<'ctx> {
+    /// A pointer to a host function, which can be called with
+    /// Context::new_call_through_ptr.
+    Function(RValue<'ctx>),
+    /// A host global, accessed through its address.
+    Global(LValue<'ctx>)
+}
+
+/// HostSymbol is a trait for the host values that Context::register_symbol
+/// can import: the function pointers that ImportableFn is implemented for,
+/// and pointers to Typeable values, which are imported as globals.
+pub trait HostSymbol {
+    /// Creates the symbol's value in the given context.
+    fn import<'a, 'ctx>(self, ctx: &'a Context<'ctx>) -> Symbol<'a>;
+}
+
+impl<F: ImportableFn> HostSymbol for F {
+    fn import<'a, 'ctx>(self, ctx: &'a Context<'ctx>) -> Symbol<'a> {
+        let ptr_ty = ctx.new_function_pointer_type(None, F::return_type(ctx), &F::param_types(ctx), false);
+        Symbol::Function(ctx.new_rvalue_from_ptr(ptr_ty, self.as_ptr()))
+    }
+}
+
+impl<T: Typeable> HostSymbol for *mut T {
+    fn import<'a, 'ctx>(self, ctx: &'a Context<'ctx>) -> Symbol<'a> {
+        let ptr_ty = <*mut T as Typeable>::get_type(ctx);
+        Symbol::Global(ctx.new_rvalue_from_ptr(ptr_ty, self as *mut ()).dereference(None))
+    }
+}
+
+impl<T: Typeable> HostSymbol for *const T {
+    fn import<'a, 'ctx>(self, ctx: &'a Context<'ctx>) -> Symbol<'a> {
+        let ptr_ty = <*const T as Typeable>::get_type(ctx);
+        Symbol::Global(ctx.new_rvalue_from_ptr(ptr_ty, self as *mut ()).dereference(None))
+    }
+}
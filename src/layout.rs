@@ -0,0 +1,141 @@
+//! Checks that a struct type built in gccjit is laid out like the Rust
+//! type that jitted code shares it with, so that a field added to one but
+//! not the other, or a change of field type, is caught instead of
+//! silently corrupting memory.
+//!
+//! The Rust side is described with the rust_layout! macro, which records
+//! the size, alignment, and field offsets of a type:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! struct Point { x: i32, y: f64 }
+//!
+//! let point_ty = ctx.new_struct_type(None, "point", &[x, y]);
+//! layout::check(point_ty.as_type(), &rust_layout!(Point { x, y })).unwrap();
+//! ```
+//!
+//! The gccjit side is computed from the fields that the struct was built
+//! with, following the C layout rules of the host.
+
+use std::error::Error;
+use std::fmt;
+
+use abi;
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// The size, alignment, and named field offsets of a Rust type. Usually
+/// built with rust_layout!.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RustLayout {
+    pub size: usize,
+    pub align: usize,
+    pub fields: Vec<(&'static str, usize)>
+}
+
+/// A difference between the layout of a gccjit struct and a Rust type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LayoutMismatch {
+    /// The layout of the gccjit type isn't known, because it isn't a
+    /// struct created through this crate or it contains bitfields or
+    /// types of unknown size.
+    Unknown,
+    Size { jit: usize, rust: usize },
+    Align { jit: usize, rust: usize },
+    /// The gccjit struct has no field with the name of this field of the
+    /// Rust type.
+    MissingField(String),
+    FieldOffset { name: String, jit: usize, rust: usize }
+}
+
+impl fmt::Display for LayoutMismatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            LayoutMismatch::Unknown => write!(fmt, "the layout of the struct isn't known"),
+            LayoutMismatch::Size { jit, rust } => {
+                write!(fmt, "the struct is {} bytes, but the Rust type is {}", jit, rust)
+            }
+            LayoutMismatch::Align { jit, rust } => {
+                write!(fmt, "the struct is aligned to {} bytes, but the Rust type to {}", jit, rust)
+            }
+            LayoutMismatch::MissingField(ref name) => write!(fmt, "the struct has no field `{}`", name),
+            LayoutMismatch::FieldOffset { ref name, jit, rust } => {
+                write!(fmt, "field `{}` is at offset {} in the struct, but {} in the Rust type", name, jit, rust)
+            }
+        }
+    }
+}
+
+impl Error for LayoutMismatch {}
+
+/// Compares the layout of a gccjit struct type with a Rust type, and
+/// returns every difference that is found. Fields are matched up by name;
+/// fields of the struct that the Rust layout doesn't list are ignored.
+pub fn check(ty: Type, rust: &RustLayout) -> Result<(), Vec<LayoutMismatch>> {
+    let ty = unsafe { types::get_ptr(&ty) };
+    let mismatches = tracking::with_registry(|reg| {
+        let ty = reg.unqualified(ty);
+        let fields = match reg.type_kind(ty) {
+            Some(TypeKind::Struct(fields)) => fields,
+            _ => return vec![LayoutMismatch::Unknown]
+        };
+        let (size, align, offsets) = match (abi::size_and_align(reg, ty), abi::field_offsets(reg, fields)) {
+            (Some((size, align)), Some(offsets)) => (size, align, offsets),
+            _ => return vec![LayoutMismatch::Unknown]
+        };
+        let mut mismatches = vec![];
+        if size != rust.size {
+            mismatches.push(LayoutMismatch::Size { jit: size, rust: rust.size });
+        }
+        if align != rust.align {
+            mismatches.push(LayoutMismatch::Align { jit: align, rust: rust.align });
+        }
+        for &(name, rust_offset) in rust.fields.iter() {
+            match fields.iter().position(|&field| reg.field_name(field) == Some(name)) {
+                Some(idx) if offsets[idx] != rust_offset => {
+                    mismatches.push(LayoutMismatch::FieldOffset {
+                        name: name.to_owned(),
+                        jit: offsets[idx],
+                        rust: rust_offset
+                    });
+                }
+                Some(_) => {}
+                None => mismatches.push(LayoutMismatch::MissingField(name.to_owned()))
+            }
+        }
+        mismatches
+    });
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::Context;
+
+    #[repr(C)]
+    struct Point {
+        x: i32,
+        y: f64
+    }
+
+    #[test]
+    fn reports_mismatched_fields() {
+        let context = Context::default();
+        let x = context.new_field(None, context.new_type::<i32>(), "x");
+        let y = context.new_field(None, context.new_type::<f64>(), "y");
+        let point = context.new_struct_type(None, "point", &[x, y]).as_type();
+        assert!(check(point, &rust_layout!(Point { x, y })).is_ok());
+
+        let x = context.new_field(None, context.new_type::<i64>(), "x");
+        let z = context.new_field(None, context.new_type::<f64>(), "z");
+        let drifted = context.new_struct_type(None, "drifted", &[x, z]).as_type();
+        let mismatches = check(drifted, &rust_layout!(Point { x, y })).unwrap_err();
+        assert_eq!(mismatches, [LayoutMismatch::MissingField("y".to_owned())]);
+        assert!(check(context.new_type::<i32>(), &rust_layout!(Point { x })).is_err());
+    }
+}
@@ -18,31 +18,105 @@
 
 extern crate gccjit_sys;
 
+#[macro_use]
+mod macros;
 mod types;
 mod context;
+mod config;
 mod object;
 mod location;
 mod field;
+mod case;
 mod structs;
+mod enums;
 mod lvalue;
+mod path;
 mod rvalue;
 mod parameter;
 mod function;
+mod attribute;
 mod block;
+mod import;
+mod decl;
+mod rooted;
+mod global;
+pub mod abi;
+pub mod atomics;
+#[cfg(all(feature = "dylib", unix))]
+pub mod cache;
+pub mod layout;
+pub mod reproducer;
+pub mod testing;
+pub mod tiered;
+mod tracking;
+mod verify;
+mod varargs;
+mod emit;
+mod dot;
+mod pretty;
+mod math;
+mod bits;
+mod libc;
+mod loops;
+mod scalarize;
+mod adapter;
+mod checked;
+mod hotswap;
+mod parallel;
+mod pool;
+mod vector;
+mod complex;
+mod multiversion;
+mod select;
+mod value;
+#[cfg(feature = "ffi-call")]
+mod call;
+#[cfg(all(feature = "dylib", unix))]
+mod dylib;
 
 pub use context::Context;
 pub use context::OptimizationLevel;
-pub use context::CompileResult;
+pub use context::{CompileDumps, CompileResult};
 pub use context::OutputKind;
+pub use context::GlobalKind;
+pub use context::JitGlobal;
+pub use config::{ArgError, ContextConfig, DebugFormat, DebugLevel, Lowering, PicLevel, Profile, Sanitizer, StackProtector};
 pub use location::Location;
 pub use object::Object;
 pub use object::ToObject;
-pub use types::Type;
+pub use types::{Type, UnknownLayout};
 pub use types::Typeable;
 pub use field::Field;
-pub use structs::Struct;
+pub use case::Case;
+pub use structs::{OpaqueStruct, Struct};
+pub use enums::EnumType;
 pub use lvalue::{LValue, ToLValue};
+pub use path::FieldPathError;
 pub use rvalue::{RValue, ToRValue};
 pub use parameter::Parameter;
-pub use function::{Function, FunctionType};
+pub use function::{Function, FunctionType, InlineMode};
+pub use attribute::{FnAttribute, VarAttribute, Visibility};
+pub use import::{HostSymbol, ImportableClosure, ImportableFn, Symbol};
+pub use decl::{DeclType, FnDecl, GlobalDecl, StructDecl};
+pub use rooted::{ContextOwner, Rooted, Rootable, Resolve};
+pub use global::{global, register_function, lookup_function, register_type, lookup_type};
 pub use block::{Block, BinaryOp, UnaryOp, ComparisonOp};
+pub use block::{PrefetchAccess, PrefetchLocality};
+pub use verify::{Violation, ViolationKind};
+pub use tracking::ContextStats;
+pub use varargs::VaList;
+pub use math::BuiltinMath;
+pub use bits::BuiltinBits;
+pub use libc::Libc;
+pub use loops::WhileLoop;
+pub use scalarize::Scalarized;
+pub use adapter::{AdapterArg, AdapterStrategy};
+pub use hotswap::{HotSwap, HotSwapGuard};
+pub use parallel::ParallelCompiler;
+pub use pool::ContextPool;
+pub use complex::CType;
+pub use value::JitValue;
+#[cfg(feature = "ffi-call")]
+pub use call::{CallError, CallType, Signature};
+#[cfg(all(feature = "dylib", unix))]
+pub use dylib::{LoadedLibrary, LoadedSymbol};
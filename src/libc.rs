@@ -0,0 +1,146 @@
+use std::os::raw::c_int;
+
+use attribute::FnAttribute;
+use context::{self, Context};
+use function::{self, Function, FunctionType};
+use tracking;
+use types::Type;
+
+/// Libc declares commonly used functions of the C library, such as
+/// putchar and malloc, with their C signatures, so that jitted code can
+/// call them. It is created by Context::libc.
+///
+/// Every function is declared at most once per context; asking for it
+/// again returns the same declaration.
+#[derive(Copy, Clone)]
+pub struct Libc<'ctx> {
+    ctx: &'ctx Context<'ctx>
+}
+
+impl<'ctx> Libc<'ctx> {
+    /// int getchar(void)
+    pub fn getchar(&self) -> Function<'ctx> {
+        self.declare("getchar", self.int(), &[], false)
+    }
+
+    /// int putchar(int c)
+    pub fn putchar(&self) -> Function<'ctx> {
+        self.declare("putchar", self.int(), &[(self.int(), "c")], false)
+    }
+
+    /// int puts(const char *s)
+    pub fn puts(&self) -> Function<'ctx> {
+        self.declare("puts", self.int(), &[(self.string(), "s")], false)
+    }
+
+    /// int printf(const char *format, ...)
+    pub fn printf(&self) -> Function<'ctx> {
+        self.declare("printf", self.int(), &[(self.string(), "format")], true)
+    }
+
+    /// void *malloc(size_t size)
+    pub fn malloc(&self) -> Function<'ctx> {
+        self.declare("malloc", self.void_ptr(), &[(self.size_t(), "size")], false)
+    }
+
+    /// void *calloc(size_t count, size_t size)
+    pub fn calloc(&self) -> Function<'ctx> {
+        self.declare("calloc", self.void_ptr(), &[(self.size_t(), "count"), (self.size_t(), "size")], false)
+    }
+
+    /// void free(void *ptr)
+    pub fn free(&self) -> Function<'ctx> {
+        self.declare("free", self.ctx.new_type::<()>(), &[(self.void_ptr(), "ptr")], false)
+    }
+
+    /// void abort(void)
+    pub fn abort(&self) -> Function<'ctx> {
+        self.declare("abort", self.ctx.new_type::<()>(), &[], false)
+    }
+
+    /// void exit(int status)
+    pub fn exit(&self) -> Function<'ctx> {
+        self.declare("exit", self.ctx.new_type::<()>(), &[(self.int(), "status")], false)
+    }
+
+    /// The jmp_buf type, which setjmp saves the state it returns to in.
+    /// Its size comes from the host's C library where it is known, and
+    /// is a generous 1024 bytes elsewhere.
+    pub fn jmp_buf(&self) -> Type<'ctx> {
+        let words = JMP_BUF_BYTES / 8;
+        self.ctx.new_array_type(None, self.ctx.new_type::<u64>(), words as i32)
+    }
+
+    /// int setjmp(jmp_buf env)
+    ///
+    /// Returns 0 when called, and again with the value passed to longjmp
+    /// each time longjmp jumps back to it. Locals that are changed after
+    /// calling setjmp and read after longjmp returns to it must be
+    /// volatile. env is a pointer to a jmp_buf, as from LValue::get_address.
+    pub fn setjmp(&self) -> Function<'ctx> {
+        let setjmp = self.declare("setjmp", self.int(), &[(self.void_ptr(), "env")], false);
+        setjmp.add_attribute(FnAttribute::ReturnsTwice);
+        setjmp
+    }
+
+    /// void longjmp(jmp_buf env, int val)
+    ///
+    /// Jumps back to where setjmp saved env, making setjmp return val, or 1
+    /// if val is 0. The function that called setjmp must not have returned.
+    /// Calls to longjmp can end a block with Block::end_with_noreturn_call.
+    pub fn longjmp(&self) -> Function<'ctx> {
+        self.declare("longjmp", self.ctx.new_type::<()>(), &[(self.void_ptr(), "env"), (self.int(), "val")], false)
+    }
+
+    fn int(&self) -> Type<'ctx> {
+        self.ctx.new_type::<c_int>()
+    }
+
+    fn size_t(&self) -> Type<'ctx> {
+        self.ctx.new_type::<usize>()
+    }
+
+    fn string(&self) -> Type<'ctx> {
+        self.ctx.new_type::<char>().make_const().make_pointer()
+    }
+
+    fn void_ptr(&self) -> Type<'ctx> {
+        self.ctx.new_type::<*mut ()>()
+    }
+
+    fn declare(&self,
+               name: &str,
+               return_ty: Type<'ctx>,
+               params: &[(Type<'ctx>, &str)],
+               is_variadic: bool) -> Function<'ctx> {
+        let ctx = unsafe { context::get_ptr(self.ctx) };
+        let existing = tracking::with_context(ctx, |info| {
+            info.functions.iter()
+                .find(|func| func.kind == FunctionType::Extern && func.name == name)
+                .map(|func| func.ptr)
+        });
+        if let Some(ptr) = existing {
+            return unsafe { function::from_ptr(ptr) };
+        }
+        let params : Vec<_> = params.iter()
+            .map(|&(ty, name)| self.ctx.new_parameter(None, ty, name))
+            .collect();
+        self.ctx.new_function(None, FunctionType::Extern, return_ty, &params, name, is_variadic)
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const JMP_BUF_BYTES: usize = 200;
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+const JMP_BUF_BYTES: usize = 312;
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+const JMP_BUF_BYTES: usize = 152;
+#[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+const JMP_BUF_BYTES: usize = 192;
+#[cfg(not(any(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")),
+              all(target_os = "macos", any(target_arch = "x86_64", target_arch = "aarch64")))))]
+const JMP_BUF_BYTES: usize = 1024;
+
+pub fn new<'ctx>(ctx: &'ctx Context<'ctx>) -> Libc<'ctx> {
+    Libc { ctx }
+}
@@ -0,0 +1,46 @@
+use block::Block;
+use function::Function;
+use location::Location;
+use rvalue::ToRValue;
+
+/// The blocks of a while loop, created by Function::new_while_loop. The
+/// loop is built in three steps: test ends the header block with the
+/// loop condition, code is generated starting at the body block, and
+/// close jumps from the end of the body back to the header and gives the
+/// block where code generation continues after the loop.
+#[derive(Copy, Clone, Debug)]
+pub struct WhileLoop<'ctx> {
+    /// The block that evaluates the condition, run before every iteration.
+    pub header: Block<'ctx>,
+    /// The first block of the loop body.
+    pub body: Block<'ctx>,
+    /// The block that is run once the condition is false.
+    pub exit: Block<'ctx>
+}
+
+impl<'ctx> WhileLoop<'ctx> {
+    /// Ends the header with a branch to the body if cond is true, and to
+    /// the exit otherwise.
+    pub fn test<T: ToRValue<'ctx>>(&self, loc: Option<Location<'ctx>>, cond: T) {
+        self.header.end_with_conditional(loc, cond, self.body, self.exit);
+    }
+
+    /// Ends the last block of the body, which is the body block itself
+    /// unless the body branches, with a jump back to the header. Returns
+    /// the exit block.
+    pub fn close(&self, loc: Option<Location<'ctx>>, last: Block<'ctx>) -> Block<'ctx> {
+        last.end_with_jump(loc, self.header);
+        self.exit
+    }
+}
+
+pub fn new_while_loop<'ctx>(func: &Function<'ctx>,
+                            loc: Option<Location<'ctx>>,
+                            from: Block<'ctx>,
+                            name: &str) -> WhileLoop<'ctx> {
+    let header = func.new_block(format!("{}_header", name));
+    let body = func.new_block(format!("{}_body", name));
+    let exit = func.new_block(format!("{}_exit", name));
+    from.end_with_jump(loc, header);
+    WhileLoop { header, body, exit }
+}
@@ -11,6 +11,10 @@ use field::Field;
 use field;
 use location::Location;
 use location;
+use types;
+use tracking::{self, FieldAccessInfo, TypeKind};
+use attribute::{self, VarAttribute};
+use path::{self, FieldPathError};
 
 /// An LValue in gccjit represents a value that has a concrete
 /// location in memory. A LValue can be converted into an RValue
@@ -49,6 +53,18 @@ impl<'ctx> ToLValue<'ctx> for LValue<'ctx> {
     }
 }
 
+impl<'ctx, T: ToLValue<'ctx> + ?Sized> ToLValue<'ctx> for &T {
+    fn to_lvalue(&self) -> LValue<'ctx> {
+        (**self).to_lvalue()
+    }
+}
+
+impl<'ctx, T: ToLValue<'ctx> + ?Sized> ToLValue<'ctx> for Box<T> {
+    fn to_lvalue(&self) -> LValue<'ctx> {
+        (**self).to_lvalue()
+    }
+}
+
 impl<'ctx> ToRValue<'ctx> for LValue<'ctx> {
     fn to_rvalue(&self) -> RValue<'ctx> {
         unsafe {
@@ -59,6 +75,30 @@ impl<'ctx> ToRValue<'ctx> for LValue<'ctx> {
 }
 
 impl<'ctx> LValue<'ctx> {
+    /// Adds a GNU C attribute to this variable, such as
+    /// visibility("hidden") or section(".data.hot").
+    pub fn add_attribute(&self, attribute: VarAttribute) {
+        unsafe {
+            attribute::add_var_attribute(self.ptr, attribute);
+        }
+    }
+
+    /// Given an LValue x of a struct or union type and a path of field
+    /// names such as "a.b.c", gets an LValue for C's x.a.b.c.
+    pub fn access_path(&self,
+                       loc: Option<Location<'ctx>>,
+                       path: &str) -> Result<LValue<'ctx>, FieldPathError> {
+        path::access_path(loc, Some(*self), self.to_rvalue(), path, false)
+    }
+
+    /// Given an LValue x that is an array or a pointer and an index i,
+    /// gets an LValue for C's x[i].
+    pub fn index<I: ToRValue<'ctx>>(&self,
+                                    loc: Option<Location<'ctx>>,
+                                    index: I) -> LValue<'ctx> {
+        self.to_rvalue().index(loc, index)
+    }
+
     /// Given an LValue x and a Field f, gets an LValue for the field
     /// access x.f.
     pub fn access_field(&self,
@@ -72,10 +112,45 @@ impl<'ctx> LValue<'ctx> {
             let ptr = gccjit_sys::gcc_jit_lvalue_access_field(self.ptr,
                                                               loc_ptr,
                                                               field::get_ptr(&field));
+            tracking::record_field_access(object::get_ptr(&self.to_object()), FieldAccessInfo {
+                loc: loc_ptr,
+                value_type: types::get_ptr(&self.to_rvalue().get_type()),
+                field: field::get_ptr(&field),
+                through_pointer: false
+            });
             from_ptr(ptr)
         }
     }
 
+    /// Given an LValue x of a vector type and an index i, gets an LValue
+    /// for element i of x, by indexing x through a pointer to its element
+    /// type.
+    ///
+    /// # Panics
+    /// Panics if x isn't of a vector type created with Type::make_vector.
+    pub fn vector_element<I: ToRValue<'ctx>>(&self,
+                                             loc: Option<Location<'ctx>>,
+                                             index: I) -> LValue<'ctx> {
+        let ty = self.to_rvalue().get_type();
+        let elem_ty = match types::vector_element_type(ty) {
+            Some(elem_ty) => elem_ty,
+            None => panic!("vector_element needs a vector, not a value of type {:?}", ty)
+        };
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let address = self.get_address(loc);
+            let elem_ptr = gccjit_sys::gcc_jit_context_new_cast(ctx,
+                                                                loc_ptr,
+                                                                rvalue::get_ptr(&address),
+                                                                types::get_ptr(&elem_ty.make_pointer()));
+            rvalue::from_ptr(elem_ptr).index(loc, index)
+        }
+    }
+
     /// Given an LValue x, returns the RValue address of x, akin to C's &x.
     pub fn get_address(&self,
                        loc: Option<Location<'ctx>>) -> RValue<'ctx> {
@@ -86,7 +161,10 @@ impl<'ctx> LValue<'ctx> {
         unsafe {
             let ptr = gccjit_sys::gcc_jit_lvalue_get_address(self.ptr,
                                                              loc_ptr);
-            rvalue::from_ptr(ptr)
+            let pointee = types::get_ptr(&self.to_rvalue().get_type());
+            let address = rvalue::from_ptr(ptr);
+            types::record(address.get_type(), TypeKind::Pointer(pointee));
+            address
         }
     }
 }
@@ -0,0 +1,41 @@
+/// Builds a call to a function, converting each argument with ToRValue so
+/// that parameters, lvalues, and rvalues can be mixed freely:
+///
+/// ```ignore
+/// let sum = call!(ctx, add, fun.get_param(0), local, ctx.new_rvalue_one(int_ty));
+/// ```
+///
+/// A location can be given before the function with `loc = ...`. Panics if
+/// the number of arguments doesn't match the number of parameters that the
+/// function was declared with.
+#[macro_export]
+macro_rules! call {
+    ($ctx:expr, loc = $loc:expr, $func:expr $(, $arg:expr)* $(,)*) => {{
+        let func = $func;
+        let args = [$($crate::ToRValue::to_rvalue(&$arg)),*];
+        if !func.accepts_argument_count(args.len()) {
+            panic!("{:?} can't be called with {} arguments", func, args.len());
+        }
+        $ctx.new_call($loc, func, &args)
+    }};
+    ($ctx:expr, $func:expr $(, $arg:expr)* $(,)*) => {
+        call!($ctx, loc = None, $func $(, $arg)*)
+    };
+}
+
+/// Describes the layout of a Rust type for layout::check, given the type
+/// and the names of the fields to compare:
+///
+/// ```ignore
+/// let layout = rust_layout!(Point { x, y });
+/// ```
+#[macro_export]
+macro_rules! rust_layout {
+    ($ty:ty { $($field:ident),* $(,)* }) => {
+        $crate::layout::RustLayout {
+            size: ::std::mem::size_of::<$ty>(),
+            align: ::std::mem::align_of::<$ty>(),
+            fields: vec![$((stringify!($field), ::std::mem::offset_of!($ty, $field))),*]
+        }
+    };
+}
@@ -0,0 +1,81 @@
+use context::Context;
+use function::{self, Function};
+use gccjit_sys::gcc_jit_types::*;
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// BuiltinMath looks up GCC's math builtins, such as __builtin_sqrtf and
+/// __builtin_sqrt, by the floating point type that they operate on. It is
+/// created by Context::builtin_math.
+///
+/// Every lookup returns None if the type isn't float, double, or long
+/// double (possibly qualified), or if libgccjit doesn't provide the
+/// builtin.
+#[derive(Copy, Clone)]
+pub struct BuiltinMath<'ctx> {
+    ctx: &'ctx Context<'ctx>
+}
+
+impl<'ctx> BuiltinMath<'ctx> {
+    /// The square root of a value.
+    pub fn sqrt(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("sqrt", ty)
+    }
+
+    /// The sine of a value in radians.
+    pub fn sin(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("sin", ty)
+    }
+
+    /// The cosine of a value in radians.
+    pub fn cos(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("cos", ty)
+    }
+
+    /// Raises the first argument to the power of the second.
+    pub fn pow(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("pow", ty)
+    }
+
+    /// The absolute value of a value.
+    pub fn fabs(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("fabs", ty)
+    }
+
+    /// Computes x * y + z with a single rounding.
+    pub fn fma(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("fma", ty)
+    }
+
+    /// Rounds a value towards negative infinity.
+    pub fn floor(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("floor", ty)
+    }
+
+    /// Rounds a value towards positive infinity.
+    pub fn ceil(&self, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        self.resolve("ceil", ty)
+    }
+
+    fn resolve(&self, name: &str, ty: Type<'ctx>) -> Option<Function<'ctx>> {
+        let ty = unsafe { types::get_ptr(&ty) };
+        let suffix = tracking::with_registry(|reg| {
+            match *reg.type_kind(reg.unqualified(ty))? {
+                TypeKind::Basic(GCC_JIT_TYPE_FLOAT) => Some("f"),
+                TypeKind::Basic(GCC_JIT_TYPE_DOUBLE) => Some(""),
+                TypeKind::Basic(GCC_JIT_TYPE_LONG_DOUBLE) => Some("l"),
+                _ => None
+            }
+        })?;
+        let func = self.ctx.get_builtin_function(format!("__builtin_{}{}", name, suffix));
+        if unsafe { function::get_ptr(&func) }.is_null() {
+            None
+        } else {
+            Some(func)
+        }
+    }
+}
+
+pub fn new<'ctx>(ctx: &'ctx Context<'ctx>) -> BuiltinMath<'ctx> {
+    BuiltinMath { ctx }
+}
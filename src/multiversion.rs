@@ -0,0 +1,118 @@
+//! Function multi-versioning: one clone of a function body per target,
+//! plus a resolver that picks the best clone for the CPU it runs on.
+//!
+//! The resolver tests the targets in the order they were given with
+//! __builtin_cpu_supports (or __builtin_cpu_is for "arch=" targets) and
+//! calls the first clone whose target is supported, falling back to a
+//! clone compiled for the context's default target.
+
+use abi;
+use block::{Block, BinaryOp, ComparisonOp};
+use context::Context;
+use function::{self, Function, FunctionType};
+use location::Location;
+use parameter::Parameter;
+use rvalue::{RValue, ToRValue};
+use tracking;
+use types::{self, Type};
+
+#[allow(clippy::too_many_arguments)]
+pub fn new<'a, 'ctx, F>(ctx: &'a Context<'ctx>,
+                        loc: Option<Location<'a>>,
+                        kind: FunctionType,
+                        return_ty: Type<'a>,
+                        params: &[(Type<'a>, &str)],
+                        name: &str,
+                        targets: &[&str],
+                        mut build: F) -> Option<Function<'a>>
+    where F: FnMut(Function<'a>, Option<&str>)
+{
+    let supports = builtin(ctx, "__builtin_cpu_supports")?;
+    let is = builtin(ctx, "__builtin_cpu_is")?;
+    let new_params = || -> Vec<Parameter<'a>> {
+        params.iter().map(|&(ty, name)| ctx.new_parameter(loc, ty, name)).collect()
+    };
+    let new_clone = |suffix: &str| {
+        ctx.new_function(loc, FunctionType::Internal, return_ty, &new_params(),
+                         format!("{}_{}", name, suffix), false)
+    };
+
+    let mut clones = Vec::with_capacity(targets.len());
+    for &target in targets {
+        let clone = new_clone(&mangle(target));
+        clone.set_target(target);
+        build(clone, Some(target));
+        clones.push((target, clone));
+    }
+    let default = new_clone("default");
+    build(default, None);
+
+    let resolver = ctx.new_function(loc, kind, return_ty, &new_params(), name, false);
+    let args : Vec<RValue<'a>> = (0..params.len())
+        .map(|idx| resolver.get_param(idx as i32).to_rvalue())
+        .collect();
+    let returns_void = tracking::with_registry(|reg| {
+        abi::is_void(reg, unsafe { types::get_ptr(&return_ty) })
+    });
+    let bool_ty = ctx.new_type::<bool>();
+    let mut block = resolver.new_block("entry");
+    for (idx, &(target, clone)) in clones.iter().enumerate() {
+        let mut cond = None;
+        for feature in target.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            let (check, arg) = match feature.strip_prefix("arch=") {
+                Some(arch) => (is, arch),
+                None => (supports, feature)
+            };
+            let call = ctx.new_call(loc, check, &[ctx.new_string_literal(arg)]);
+            let supported = ctx.new_comparison(loc, ComparisonOp::NotEquals, call,
+                                               ctx.new_rvalue_zero(call.get_type()));
+            cond = Some(match cond {
+                Some(prev) => ctx.new_binary_op(loc, BinaryOp::LogicalAnd, bool_ty, prev, supported),
+                None => supported
+            });
+        }
+        let cond = match cond {
+            Some(cond) => cond,
+            None => continue
+        };
+        let on_true = resolver.new_block(format!("use_{}", idx));
+        let on_false = resolver.new_block(format!("check_{}", idx + 1));
+        block.end_with_conditional(loc, cond, on_true, on_false);
+        dispatch(ctx, loc, on_true, clone, &args, returns_void);
+        block = on_false;
+    }
+    dispatch(ctx, loc, block, default, &args, returns_void);
+    Some(resolver)
+}
+
+fn builtin<'a, 'ctx>(ctx: &'a Context<'ctx>, name: &str) -> Option<Function<'a>> {
+    let builtin = ctx.get_builtin_function(name);
+    if unsafe { function::get_ptr(&builtin) }.is_null() {
+        None
+    } else {
+        Some(builtin)
+    }
+}
+
+fn dispatch<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                      loc: Option<Location<'a>>,
+                      block: Block<'a>,
+                      clone: Function<'a>,
+                      args: &[RValue<'a>],
+                      returns_void: bool) {
+    let call = ctx.new_call(loc, clone, args);
+    if returns_void {
+        block.add_eval(loc, call);
+        block.end_with_void_return(loc);
+    } else {
+        block.end_with_return(loc, call);
+    }
+}
+
+/// Turns a target such as "arch=haswell" or "avx2,fma" into something
+/// that can be used in a symbol name.
+fn mangle(target: &str) -> String {
+    target.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
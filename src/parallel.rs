@@ -0,0 +1,138 @@
+//! Compiling independent pieces of code on several threads.
+//!
+//! libgccjit allows different contexts to be used on different threads at
+//! the same time, but a context and everything created from it must stay
+//! on one thread. So each piece of work is built on a context of its own,
+//! created on the worker thread that compiles it; child contexts can't be
+//! used, since they borrow their parent. The records this crate keeps
+//! about contexts are per thread, so building the contexts runs in
+//! parallel. libgccjit holds a process-wide lock while it runs gcc,
+//! though, so the compilations themselves are serialized, apart from the
+//! parts of compiling that happen outside of that lock.
+
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+
+use context::{CompileResult, Context};
+
+/// Compiles pieces of work on a pool of threads, each on a fresh context.
+#[derive(Copy, Clone, Debug)]
+pub struct ParallelCompiler {
+    threads: usize
+}
+
+/// A CompileResult on its way back from a worker thread.
+struct SendResult(CompileResult);
+
+// results with imported closures, whose reference counts aren't
+// thread-safe, are dropped on the worker thread instead of being sent.
+unsafe impl Send for SendResult {}
+
+impl Default for ParallelCompiler {
+    fn default() -> ParallelCompiler {
+        ParallelCompiler::new()
+    }
+}
+
+impl ParallelCompiler {
+    /// Creates a compiler that uses as many threads as the machine can run
+    /// in parallel.
+    pub fn new() -> ParallelCompiler {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        ParallelCompiler { threads }
+    }
+
+    /// Sets the number of threads to compile on.
+    ///
+    /// # Panics
+    /// Panics if the number of threads is zero.
+    pub fn threads(self, threads: usize) -> ParallelCompiler {
+        assert!(threads > 0, "ParallelCompiler needs at least one thread");
+        ParallelCompiler { threads }
+    }
+
+    /// Builds each piece of work with the given closure on a new context,
+    /// compiles it, and returns the results in the order of the work.
+    /// Contexts are built and compiled on the worker threads; a context is
+    /// never seen by more than one thread.
+    ///
+    /// # Panics
+    /// Panics if any piece of work imports a closure with
+    /// Context::import_closure, since the closure would then be kept alive
+    /// by a result on another thread than the one it was created on.
+    pub fn compile<T, B>(&self, work: Vec<T>, build: B) -> Vec<CompileResult>
+        where T: Send,
+              B: Fn(&Context, T) + Sync {
+        let count = work.len();
+        let queue = Mutex::new(work.into_iter().enumerate());
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(count) {
+                let sender = sender.clone();
+                let (queue, build) = (&queue, &build);
+                scope.spawn(move || {
+                    loop {
+                        let next = queue.lock().unwrap().next();
+                        let (idx, item) = match next {
+                            Some(next) => next,
+                            None => break
+                        };
+                        let ctx = Context::default();
+                        build(&ctx, item);
+                        let result = ctx.compile();
+                        drop(ctx);
+                        let result = if result.has_closures() {
+                            None
+                        } else {
+                            Some(SendResult(result))
+                        };
+                        if sender.send((idx, result)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        drop(sender);
+        let mut results : Vec<Option<CompileResult>> = (0..count).map(|_| None).collect();
+        for (idx, result) in receiver {
+            match result {
+                Some(SendResult(result)) => results[idx] = Some(result),
+                None => panic!("ParallelCompiler can't return results that call imported closures")
+            }
+        }
+        results.into_iter().map(|result| result.unwrap()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use function::FunctionType;
+
+    #[test]
+    fn results_are_in_work_order() {
+        let results = ParallelCompiler::new().threads(3).compile((0..8).collect(), |ctx, value: i32| {
+            let int_ty = ctx.new_type::<i32>();
+            let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[], "value", false);
+            fun.new_block("entry").end_with_return(None, ctx.new_rvalue_from_int(int_ty, value));
+        });
+        for (expected, result) in results.iter().enumerate() {
+            let value = unsafe { result.function::<extern "C" fn() -> i32, _>("value") }.unwrap();
+            assert_eq!(value(), expected as i32);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "imported closures")]
+    fn imported_closures_are_rejected() {
+        ParallelCompiler::new().threads(2).compile((0..2).collect(), |ctx, value: i32| {
+            let int_ty = ctx.new_type::<i32>();
+            let shared = ::std::rc::Rc::new(value);
+            let get = ctx.import_closure("get", Box::new(move || *shared) as Box<dyn Fn() -> i32>);
+            let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[], "value", false);
+            fun.new_block("entry").end_with_return(None, ctx.new_call(None, get, &[]));
+        });
+    }
+}
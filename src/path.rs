@@ -0,0 +1,133 @@
+use std::error::Error;
+use std::fmt;
+
+use gccjit_sys;
+
+use field::{self, Field};
+use location::Location;
+use lvalue::LValue;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types;
+
+/// The reasons that a field path such as "a.b.c" can't be resolved. Each
+/// variant holds the prefix of the path up to and including the segment
+/// that couldn't be resolved.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FieldPathError {
+    /// The struct or union has no field with the given name.
+    NoSuchField(String),
+    /// The value isn't a struct or union, or it is one that wasn't
+    /// created through this library.
+    NotAnAggregate(String),
+    /// The field is accessed through a value that isn't a pointer.
+    NotAPointer(String)
+}
+
+impl fmt::Display for FieldPathError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            FieldPathError::NoSuchField(ref path) => write!(fmt, "no field named `{}`", path),
+            FieldPathError::NotAnAggregate(ref path) => {
+                write!(fmt, "`{}` is accessed on a value that isn't a struct or union", path)
+            }
+            FieldPathError::NotAPointer(ref path) => {
+                write!(fmt, "`{}` is accessed through a value that isn't a pointer", path)
+            }
+        }
+    }
+}
+
+impl Error for FieldPathError {}
+
+/// Resolves a field path starting at a value, which is accessed as an
+/// lvalue if one is given and as the rvalue otherwise. If auto_deref is
+/// set, hops through pointers to structs or unions dereference them, as
+/// C's -> does.
+pub fn access_path<'ctx>(loc: Option<Location<'ctx>>,
+                         lvalue: Option<LValue<'ctx>>,
+                         value: RValue<'ctx>,
+                         path: &str,
+                         auto_deref: bool) -> Result<LValue<'ctx>, FieldPathError> {
+    let mut ty = unsafe { types::get_ptr(&value.get_type()) };
+    let mut current = lvalue;
+    let mut end = 0;
+    for name in path.split('.') {
+        end += name.len();
+        let prefix = &path[..end];
+        end += 1;
+        let pointee = if auto_deref { pointee(ty) } else { None };
+        current = Some(match pointee {
+            Some(pointee) => {
+                let (field, field_ty) = find_field(pointee, name, prefix)?;
+                ty = field_ty;
+                current.map_or(value, |lvalue| lvalue.to_rvalue()).dereference_field(loc, field)
+            }
+            None => {
+                let (field, field_ty) = find_field(ty, name, prefix)?;
+                ty = field_ty;
+                match current {
+                    Some(lvalue) => lvalue.access_field(loc, field),
+                    None => value.access_field(loc, field)
+                }
+            }
+        });
+    }
+    // split always yields at least one segment, so a field was accessed.
+    Ok(current.unwrap())
+}
+
+/// Like access_path with auto_deref, but the value itself must be a
+/// pointer.
+pub fn arrow_path<'ctx>(loc: Option<Location<'ctx>>,
+                        value: RValue<'ctx>,
+                        path: &str) -> Result<LValue<'ctx>, FieldPathError> {
+    if pointee(unsafe { types::get_ptr(&value.get_type()) }).is_none() {
+        let first = path.split('.').next().unwrap_or("");
+        return Err(FieldPathError::NotAPointer(first.to_owned()));
+    }
+    access_path(loc, None, value, path, true)
+}
+
+/// Checks that a value points to a struct or union that contains the
+/// given field, then accesses the field through it.
+pub fn arrow<'ctx>(loc: Option<Location<'ctx>>,
+                   value: RValue<'ctx>,
+                   field: Field<'ctx>) -> Result<LValue<'ctx>, FieldPathError> {
+    let ty = unsafe { types::get_ptr(&value.get_type()) };
+    let field_ptr = unsafe { field::get_ptr(&field) };
+    let name = tracking::with_registry(|reg| reg.field_name(field_ptr).unwrap_or("<unknown>").to_owned());
+    let pointee = pointee(ty).ok_or_else(|| FieldPathError::NotAPointer(name.clone()))?;
+    let contained = tracking::with_registry(|reg| reg.field_owner(field_ptr) == Some(reg.unqualified(pointee)));
+    if !contained {
+        return Err(FieldPathError::NoSuchField(name));
+    }
+    Ok(value.dereference_field(loc, field))
+}
+
+/// The type that a pointer type points to, if it is a tracked pointer.
+fn pointee(ty: *mut gccjit_sys::gcc_jit_type) -> Option<*mut gccjit_sys::gcc_jit_type> {
+    tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(ty)) {
+            Some(&TypeKind::Pointer(pointee)) => Some(pointee),
+            _ => None
+        }
+    })
+}
+
+/// Finds the field with the given name in a struct or union type, along
+/// with the field's type.
+fn find_field<'ctx>(ty: *mut gccjit_sys::gcc_jit_type,
+                    name: &str,
+                    prefix: &str) -> Result<(Field<'ctx>, *mut gccjit_sys::gcc_jit_type), FieldPathError> {
+    tracking::with_registry(|reg| {
+        let fields = match reg.type_kind(reg.unqualified(ty)) {
+            Some(TypeKind::Struct(fields)) | Some(TypeKind::Union(fields)) => fields,
+            _ => return Err(FieldPathError::NotAnAggregate(prefix.to_owned()))
+        };
+        fields.iter()
+            .find(|&&field| reg.field_name(field) == Some(name))
+            .and_then(|&field| Some((unsafe { field::from_ptr(field) }, reg.field_type(field)?)))
+            .ok_or_else(|| FieldPathError::NoSuchField(prefix.to_owned()))
+    })
+}
@@ -0,0 +1,72 @@
+//! Reusing the setup of contexts between compilations.
+//!
+//! libgccjit has no way to remove what was added to a context, so a
+//! context can't be handed back and reused for unrelated code. What a
+//! ContextPool reuses instead is a base context that is set up once, with
+//! options, common types, and imported runtime symbols. Each compilation
+//! gets a child context of the base, which inherits the options and can
+//! use everything created on the base, and is discarded when it's dropped.
+
+use context::Context;
+
+/// Hands out contexts that share the setup of a base context.
+pub struct ContextPool {
+    base: Context<'static>
+}
+
+impl ContextPool {
+    /// Creates a pool whose base context is set up by the given closure,
+    /// which runs once.
+    pub fn new<F: FnOnce(&Context<'static>)>(setup: F) -> ContextPool {
+        let base = Context::default();
+        setup(&base);
+        ContextPool { base }
+    }
+
+    /// Gets the base context, for looking up what the setup created, such
+    /// as with Context::lookup_symbol.
+    pub fn base(&self) -> &Context<'static> {
+        &self.base
+    }
+
+    /// Gets a new context for a compilation, which is a child of the base
+    /// context and so sees its options, types, functions, and symbols.
+    pub fn get(&self) -> Context<'_> {
+        self.base.new_child_context()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::OptimizationLevel;
+    use function::FunctionType;
+    use import::Symbol;
+
+    extern "C" fn double(x: i32) -> i32 {
+        x * 2
+    }
+
+    #[test]
+    fn pooled_contexts_share_setup() {
+        let pool = ContextPool::new(|ctx| {
+            ctx.set_optimization_level(OptimizationLevel::Limited);
+            ctx.register_symbol("double", double as extern "C" fn(i32) -> i32);
+        });
+        for value in 0..3 {
+            let ctx = pool.get();
+            assert_eq!(ctx.config().optimization_level, OptimizationLevel::Limited);
+            let double = match ctx.lookup_symbol("double") {
+                Some(Symbol::Function(double)) => double,
+                _ => panic!("the setup's symbol isn't visible")
+            };
+            let int_ty = ctx.new_type::<i32>();
+            let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[], "doubled", false);
+            let arg = ctx.new_rvalue_from_int(int_ty, value);
+            fun.new_block("entry").end_with_return(None, ctx.new_call_through_ptr(None, double, &[arg]));
+            let result = ctx.compile();
+            let doubled = unsafe { result.function::<extern "C" fn() -> i32, _>("doubled") }.unwrap();
+            assert_eq!(doubled(), value * 2);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! A readable listing of everything recorded about a context, in a C-like
+//! syntax of this library's own, so that what a frontend has built can be
+//! looked at without compiling it or reading gcc's dump formats.
+
+use gccjit_sys;
+
+use context::GlobalKind;
+use dot::statement_text;
+use emit::{c_decl, debug_string, param_type, type_name};
+use function::FunctionType;
+use tracking::{ContextInfo, Registry, TypeKind};
+
+pub fn listing(reg: &Registry, info: &ContextInfo) -> String {
+    let mut out = String::new();
+
+    let mut aggregates : Vec<_> = info.types.iter()
+        .filter_map(|(&ty, kind)| match *kind {
+            TypeKind::Struct(ref fields) | TypeKind::Union(ref fields) => Some((type_name(ty), Some(fields))),
+            TypeKind::Opaque => Some((type_name(ty), None)),
+            _ => None
+        })
+        .collect();
+    // the registry doesn't know what order the types were created in, so
+    // they're listed by name to keep the listing stable.
+    aggregates.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, fields) in aggregates {
+        let fields = match fields {
+            Some(fields) => fields,
+            None => {
+                out.push_str(&format!("{};\n\n", name));
+                continue;
+            }
+        };
+        out.push_str(&format!("{} {{\n", name));
+        for &field in fields {
+            let field_name = unsafe { debug_string(gccjit_sys::gcc_jit_field_as_object(field)) };
+            let decl = match (reg.field_type(field), reg.field_width(field)) {
+                (Some(field_ty), Some(width)) => format!("{} : {}", c_decl(reg, field_ty, field_name), width),
+                (Some(field_ty), None) => c_decl(reg, field_ty, field_name),
+                (None, _) => field_name
+            };
+            out.push_str(&format!("    {};\n", decl));
+        }
+        out.push_str("};\n\n");
+    }
+
+    for global in info.globals.iter() {
+        let kind = match global.kind {
+            GlobalKind::Exported => "exported",
+            GlobalKind::Internal => "internal",
+            GlobalKind::Imported => "imported"
+        };
+        out.push_str(&format!("{} {};\n", kind, c_decl(reg, global.ty, global.name.clone())));
+    }
+    if !info.globals.is_empty() {
+        out.push('\n');
+    }
+
+    for func in info.functions.iter() {
+        let kind = match func.kind {
+            FunctionType::Exported => "exported",
+            FunctionType::Internal => "internal",
+            FunctionType::Extern => "extern",
+            FunctionType::AlwaysInline => "always_inline"
+        };
+        let mut params : Vec<_> = func.params.iter()
+            .map(|&param| unsafe {
+                let name = debug_string(gccjit_sys::gcc_jit_param_as_object(param));
+                c_decl(reg, param_type(param), name)
+            })
+            .collect();
+        if func.is_variadic {
+            params.push("...".to_owned());
+        }
+        let declarator = format!("{}({})", func.name, params.join(", "));
+        out.push_str(&format!("{} {}", kind, c_decl(reg, func.return_type, declarator)));
+        if func.kind == FunctionType::Extern {
+            out.push_str(";\n\n");
+            continue;
+        }
+        out.push_str("\n{\n");
+        for block in func.blocks.iter().filter_map(|&block| reg.block(block)) {
+            out.push_str(&format!("  {}:\n", block.name));
+            for stmt in block.statements.iter() {
+                out.push_str(&format!("    {}\n", statement_text(stmt)));
+            }
+        }
+        out.push_str("}\n\n");
+    }
+    out
+}
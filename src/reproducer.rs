@@ -0,0 +1,134 @@
+//! Regression tests from reproducers. Context::dump_reproducer_to_file
+//! writes a C program that makes the same calls into libgccjit as the
+//! context was built with, which is how bugs in this library or in
+//! libgccjit are usually reported. The functions here build and run such a
+//! program, so that a reproducer attached to a bug report can be checked
+//! into a test directory and kept passing.
+//!
+//! The reproducer is compiled with the compiler named by the CC
+//! environment variable, or cc, and linked against libgccjit. Set
+//! LIBRARY_PATH and CPATH if libgccjit isn't installed where the compiler
+//! looks by default.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::{self, Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use context::Context;
+
+/// What went wrong building or running a reproducer.
+#[derive(Debug)]
+pub enum ReproducerError {
+    /// The compiler couldn't be started, or a file couldn't be written.
+    Io(io::Error),
+    /// The reproducer didn't compile. Holds what the compiler printed.
+    Build(String),
+    /// The reproducer exited unsuccessfully, such as by crashing inside
+    /// libgccjit. Holds the exit status and what the program printed.
+    Failed(ExitStatus, String),
+    /// libgccjit reported errors while the reproducer ran. Holds what the
+    /// program printed.
+    JitErrors(String)
+}
+
+impl fmt::Display for ReproducerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ReproducerError::Io(ref err) => write!(fmt, "{}", err),
+            ReproducerError::Build(ref output) => write!(fmt, "the reproducer didn't compile:\n{}", output),
+            ReproducerError::Failed(status, ref output) => {
+                write!(fmt, "the reproducer failed with {}:\n{}", status, output)
+            }
+            ReproducerError::JitErrors(ref output) => write!(fmt, "libgccjit reported errors:\n{}", output)
+        }
+    }
+}
+
+impl Error for ReproducerError {}
+
+impl From<io::Error> for ReproducerError {
+    fn from(err: io::Error) -> ReproducerError {
+        ReproducerError::Io(err)
+    }
+}
+
+/// Compiles the reproducer at the given path and runs it, returning what
+/// it printed to stdout.
+pub fn run<P: AsRef<Path>>(path: P) -> Result<String, ReproducerError> {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let exe = env::temp_dir().join(format!("gccjit-reproducer-{}-{}",
+                                           process::id(),
+                                           NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    let cc = env::var_os("CC").unwrap_or_else(|| "cc".into());
+    let build = Command::new(cc)
+        .arg(path.as_ref())
+        .arg("-o")
+        .arg(&exe)
+        .arg("-lgccjit")
+        .output()?;
+    if !build.status.success() {
+        return Err(ReproducerError::Build(String::from_utf8_lossy(&build.stderr).into_owned()));
+    }
+    let output = Command::new(&exe).output();
+    let _ = fs::remove_file(&exe);
+    let output = output?;
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    let printed = format!("{}{}", stdout, String::from_utf8_lossy(&output.stderr));
+    if !output.status.success() {
+        Err(ReproducerError::Failed(output.status, printed))
+    } else if printed.contains("libgccjit.so: error:") {
+        Err(ReproducerError::JitErrors(printed))
+    } else {
+        Ok(stdout)
+    }
+}
+
+/// Writes the reproducer of a context to the given path and runs it.
+pub fn save_and_run<P: AsRef<Path>>(ctx: &Context, path: P) -> Result<String, ReproducerError> {
+    ctx.dump_reproducer_to_file(path.as_ref().to_string_lossy());
+    run(path)
+}
+
+/// Runs every reproducer, with the extension .c, in the given directory.
+///
+/// # Panics
+/// Panics with the output of every reproducer that fails, or if the
+/// directory can't be read.
+pub fn assert_all_pass<P: AsRef<Path>>(dir: P) {
+    let dir = dir.as_ref();
+    let mut paths : Vec<_> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("failed to read {}: {}", dir.display(), err))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+        .collect();
+    paths.sort();
+    let failures : Vec<_> = paths.iter()
+        .filter_map(|path| run(path).err().map(|err| format!("{}: {}", path.display(), err)))
+        .collect();
+    if !failures.is_empty() {
+        panic!("{} of {} reproducers failed:\n{}", failures.len(), paths.len(), failures.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use function::FunctionType;
+
+    #[test]
+    fn saved_reproducer_runs() {
+        let context = Context::default();
+        let int_ty = context.new_type::<i32>();
+        let fun = context.new_function(None, FunctionType::Exported, int_ty, &[], "answer", false);
+        fun.new_block("entry").end_with_return(None, context.new_rvalue_from_int(int_ty, 42));
+
+        let path = env::temp_dir().join("gccjit_rs_reproducer.c");
+        save_and_run(&context, &path).unwrap();
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,200 @@
+//! Handles to gccjit objects that don't borrow their context.
+//!
+//! Every wrapper type borrows the context that created it, which makes
+//! them hard to keep in the long-lived data structures of a frontend,
+//! such as a table from source-level types to gccjit types that lives
+//! alongside the context. A ContextOwner owns a context and roots objects
+//! created from it as Rooted handles, which carry no lifetime and can be
+//! stored anywhere. Handles are turned back into typed wrappers by the
+//! owner that rooted them, which ties the wrappers to a borrow of the
+//! owner again.
+//!
+//! ```ignore
+//! let owner = ContextOwner::new();
+//! let int_ty : Rooted<Type<'static>> = owner.root(owner.context().new_type::<i32>());
+//! // later, and possibly somewhere else entirely
+//! let ty = owner.get(&int_ty);
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use block::{self, Block};
+use context::{self, Context};
+use field::{self, Field};
+use function::{self, Function};
+use lvalue::{self, LValue};
+use object::{self, ToObject};
+use parameter::{self, Parameter};
+use rvalue::{self, RValue};
+use structs::{self, Struct};
+use tracking;
+use types::{self, Type};
+
+/// Owns a context, and roots and resolves handles to objects created from
+/// it. The context is released when the owner is dropped.
+pub struct ContextOwner {
+    context: Context<'static>
+}
+
+impl ContextOwner {
+    /// Acquires a new context to own.
+    pub fn new() -> ContextOwner {
+        ContextOwner {
+            context: Context::default()
+        }
+    }
+
+    /// The owned context, for creating objects.
+    pub fn context(&self) -> &Context<'static> {
+        &self.context
+    }
+
+    /// Turns an object created from the owned context into a handle that
+    /// doesn't borrow the owner.
+    ///
+    /// # Panics
+    /// Panics if the object was created from a different context.
+    pub fn root<'a, T: Rootable<'a>>(&'a self, value: T) -> Rooted<T::Static> {
+        let owner = tracking::context_of(unsafe { object::get_ptr(&value.to_object()) });
+        if owner != unsafe { context::get_ptr(&self.context) } {
+            panic!("{:?} wasn't created from the context of this owner", value.to_object());
+        }
+        Rooted {
+            ptr: value.into_raw(),
+            context_id: self.context.id(),
+            marker: PhantomData
+        }
+    }
+
+    /// Turns a handle rooted by this owner back into the object it refers
+    /// to.
+    ///
+    /// # Panics
+    /// Panics if the handle was rooted by a different owner.
+    pub fn get<'a, T: Resolve<'a>>(&'a self, rooted: &Rooted<T>) -> T::Output {
+        let id = self.context.id();
+        if rooted.context_id != id {
+            panic!("handle rooted by the owner of context #{} resolved by the owner of context #{}",
+                   rooted.context_id, id);
+        }
+        unsafe { T::from_raw(rooted.ptr) }
+    }
+}
+
+impl Default for ContextOwner {
+    fn default() -> ContextOwner {
+        ContextOwner::new()
+    }
+}
+
+/// A handle to an object of a context owned by a ContextOwner, written
+/// with the 'static version of the wrapper type, such as
+/// Rooted<Type<'static>>. Resolve it with ContextOwner::get.
+pub struct Rooted<T> {
+    ptr: *mut (),
+    context_id: u64,
+    marker: PhantomData<T>
+}
+
+impl<T> Clone for Rooted<T> {
+    fn clone(&self) -> Rooted<T> {
+        *self
+    }
+}
+
+impl<T> Copy for Rooted<T> {}
+
+impl<T> PartialEq for Rooted<T> {
+    fn eq(&self, other: &Rooted<T>) -> bool {
+        self.ptr == other.ptr && self.context_id == other.context_id
+    }
+}
+
+impl<T> Eq for Rooted<T> {}
+
+impl<T> fmt::Debug for Rooted<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "Rooted({:p} in context #{})", self.ptr, self.context_id)
+    }
+}
+
+/// Wrapper types that can be rooted with ContextOwner::root.
+pub trait Rootable<'a>: Copy + ToObject<'a> {
+    /// The 'static version of the type, which names the kind of handle.
+    type Static;
+
+    #[doc(hidden)]
+    fn into_raw(self) -> *mut ();
+}
+
+/// The 'static versions of the wrapper types, which ContextOwner::get
+/// resolves handles to.
+pub trait Resolve<'a> {
+    /// The wrapper type, borrowing the owner.
+    type Output;
+
+    #[doc(hidden)]
+    unsafe fn from_raw(ptr: *mut ()) -> Self::Output;
+}
+
+macro_rules! rootable {
+    ($($ty:ident => $module:ident),*) => {
+        $(
+            impl<'a> Rootable<'a> for $ty<'a> {
+                type Static = $ty<'static>;
+
+                fn into_raw(self) -> *mut () {
+                    unsafe { $module::get_ptr(&self) as *mut () }
+                }
+            }
+
+            impl<'a> Resolve<'a> for $ty<'static> {
+                type Output = $ty<'a>;
+
+                unsafe fn from_raw(ptr: *mut ()) -> $ty<'a> {
+                    $module::from_ptr(ptr as *mut _)
+                }
+            }
+        )*
+    }
+}
+
+rootable!(Type => types,
+          Struct => structs,
+          Field => field,
+          Function => function,
+          Parameter => parameter,
+          Block => block,
+          LValue => lvalue,
+          RValue => rvalue);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Frontend {
+        owner: ContextOwner,
+        int_ty: Rooted<Type<'static>>
+    }
+
+    #[test]
+    fn rooted_handles_resolve() {
+        let owner = ContextOwner::new();
+        let int_ty = owner.root(owner.context().new_type::<i32>());
+        let frontend = Frontend { owner, int_ty };
+
+        let ty = frontend.owner.get(&frontend.int_ty);
+        let value = frontend.owner.context().new_rvalue_zero(ty);
+        assert_eq!(format!("{:?}", value), "(int)0");
+    }
+
+    #[test]
+    #[should_panic(expected = "resolved by the owner of context")]
+    fn handles_of_other_owners_are_rejected() {
+        let first = ContextOwner::new();
+        let second = ContextOwner::new();
+        let int_ty = first.root(first.context().new_type::<i32>());
+        second.get(&int_ty);
+    }
+}
@@ -16,6 +16,9 @@ use lvalue;
 use location::Location;
 use location;
 use block::BinaryOp;
+use tracking::{self, FieldAccessInfo};
+use path::{self, FieldPathError};
+use bits;
 
 /// An RValue is a value that may or may not have a storage address in gccjit.
 /// RValues can be dereferenced, used for field accesses, and are the parameters
@@ -53,6 +56,18 @@ impl<'ctx> ToRValue<'ctx> for RValue<'ctx> {
     }
 }
 
+impl<'ctx, T: ToRValue<'ctx> + ?Sized> ToRValue<'ctx> for &T {
+    fn to_rvalue(&self) -> RValue<'ctx> {
+        (**self).to_rvalue()
+    }
+}
+
+impl<'ctx, T: ToRValue<'ctx> + ?Sized> ToRValue<'ctx> for Box<T> {
+    fn to_rvalue(&self) -> RValue<'ctx> {
+        (**self).to_rvalue()
+    }
+}
+
 macro_rules! binary_operator_for {
     ($ty:ty, $name:ident, $op:expr) => {
         impl<'ctx> $ty for RValue<'ctx> {
@@ -111,6 +126,12 @@ impl<'ctx> RValue<'ctx> {
             let ptr = gccjit_sys::gcc_jit_rvalue_access_field(self.ptr,
                                                               loc_ptr,
                                                               field::get_ptr(&field));
+            tracking::record_field_access(object::get_ptr(&self.to_object()), FieldAccessInfo {
+                loc: loc_ptr,
+                value_type: types::get_ptr(&self.get_type()),
+                field: field::get_ptr(&field),
+                through_pointer: false
+            });
             lvalue::from_ptr(ptr)
         }
     }
@@ -128,6 +149,12 @@ impl<'ctx> RValue<'ctx> {
             let ptr = gccjit_sys::gcc_jit_rvalue_dereference_field(self.ptr,
                                                                    loc_ptr,
                                                                    field::get_ptr(&field));
+            tracking::record_field_access(object::get_ptr(&self.to_object()), FieldAccessInfo {
+                loc: loc_ptr,
+                value_type: types::get_ptr(&self.get_type()),
+                field: field::get_ptr(&field),
+                through_pointer: true
+            });
             lvalue::from_ptr(ptr)
         }
     }
@@ -146,6 +173,87 @@ impl<'ctx> RValue<'ctx> {
             lvalue::from_ptr(ptr)
         }
     }
+
+    /// Given an RValue x of a struct or union type and a path of field
+    /// names such as "a.b.c", returns an LValue representing C's x.a.b.c.
+    /// Only fields of structs and unions created through this library can
+    /// be found by name.
+    pub fn access_path(&self,
+                       loc: Option<Location<'ctx>>,
+                       path: &str) -> Result<LValue<'ctx>, FieldPathError> {
+        path::access_path(loc, None, *self, path, false)
+    }
+
+    /// Given an RValue x that points to a struct or union and a Field f,
+    /// returns an LValue representing C's x->f. Unlike dereference_field,
+    /// this checks that x is a pointer to a type that contains f.
+    pub fn arrow(&self,
+                 loc: Option<Location<'ctx>>,
+                 field: Field<'ctx>) -> Result<LValue<'ctx>, FieldPathError> {
+        path::arrow(loc, *self, field)
+    }
+
+    /// Given an RValue x that points to a struct or union and a path of
+    /// field names such as "a.b", returns an LValue representing C's x->a.b,
+    /// or x->a->b if a is itself a pointer to a struct or union.
+    pub fn arrow_path(&self,
+                      loc: Option<Location<'ctx>>,
+                      path: &str) -> Result<LValue<'ctx>, FieldPathError> {
+        path::arrow_path(loc, *self, path)
+    }
+
+    /// Reverses the order of the bytes of an integer, converting it between
+    /// big and little endian. This uses __builtin_bswap16, 32, or 64,
+    /// depending on the size of the integer, and is the value itself for
+    /// one byte integers. Returns None if this isn't an integer of one,
+    /// two, four, or eight bytes, or if libgccjit doesn't provide the
+    /// builtin.
+    pub fn byte_swap(&self, loc: Option<Location<'ctx>>) -> Option<RValue<'ctx>> {
+        bits::byte_swap(loc, *self)
+    }
+
+    /// Given an RValue x that is an array or a pointer and an index i,
+    /// returns an LValue representing C's x[i]. This is the same as
+    /// Context::new_array_access.
+    pub fn index<I: ToRValue<'ctx>>(&self,
+                                    loc: Option<Location<'ctx>>,
+                                    index: I) -> LValue<'ctx> {
+        let idx_rvalue = index.to_rvalue();
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let ptr = gccjit_sys::gcc_jit_context_new_array_access(ctx,
+                                                                   loc_ptr,
+                                                                   self.ptr,
+                                                                   idx_rvalue.ptr);
+            lvalue::from_ptr(ptr)
+        }
+    }
+
+    /// Given an RValue x of a vector type and an index i, gets an LValue
+    /// for element i of x. Unlike LValue::vector_element, x doesn't need
+    /// to be stored anywhere.
+    #[cfg(feature = "master")]
+    pub fn vector_element<I: ToRValue<'ctx>>(&self,
+                                             loc: Option<Location<'ctx>>,
+                                             index: I) -> LValue<'ctx> {
+        let idx_rvalue = index.to_rvalue();
+        let loc_ptr = match loc {
+            Some(loc) => unsafe { location::get_ptr(&loc) },
+            None => ptr::null_mut()
+        };
+        unsafe {
+            let ctx = tracking::context_of(object::get_ptr(&self.to_object()));
+            let ptr = gccjit_sys::gcc_jit_context_new_vector_access(ctx,
+                                                                    loc_ptr,
+                                                                    self.ptr,
+                                                                    idx_rvalue.ptr);
+            lvalue::from_ptr(ptr)
+        }
+    }
 }
 
 pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_rvalue) -> RValue<'ctx> {
@@ -0,0 +1,81 @@
+//! Scalar replacement of small structs. A frontend that keeps a struct in
+//! a local and updates it field by field, say in a loop, can instead keep
+//! each field in a local of its own, which GCC keeps in registers even
+//! where its own scalar replacement of aggregates gives up, such as when
+//! the struct's address escapes or it is copied as a whole. The struct is
+//! put back together once the individual fields are no longer updated.
+
+use block::Block;
+use field::{self, Field};
+use function::Function;
+use location::Location;
+use lvalue::LValue;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// The locals holding the fields of a struct value, created by
+/// Function::scalarize.
+#[derive(Clone, Debug)]
+pub struct Scalarized<'ctx> {
+    func: Function<'ctx>,
+    ty: Type<'ctx>,
+    name: String,
+    fields: Vec<(Field<'ctx>, LValue<'ctx>)>
+}
+
+impl<'ctx> Scalarized<'ctx> {
+    /// Gets the local holding the given field.
+    ///
+    /// # Panics
+    /// Panics if the field isn't a field of the scalarized struct.
+    pub fn field(&self, field: Field<'ctx>) -> LValue<'ctx> {
+        let ptr = unsafe { field::get_ptr(&field) };
+        self.fields.iter()
+            .find(|&&(f, _)| unsafe { field::get_ptr(&f) } == ptr)
+            .map(|&(_, local)| local)
+            .unwrap_or_else(|| panic!("{:?} isn't a field of {:?}", field, self.ty))
+    }
+
+    /// Gets the locals holding the fields, in the order of the struct's
+    /// fields.
+    pub fn locals(&self) -> Vec<LValue<'ctx>> {
+        self.fields.iter().map(|&(_, local)| local).collect()
+    }
+
+    /// Copies the fields back into a new local of the struct type at the
+    /// end of the given block, and returns that local.
+    pub fn reassemble(&self, loc: Option<Location<'ctx>>, block: Block<'ctx>) -> LValue<'ctx> {
+        let local = self.func.new_local(loc, self.ty, format!("{}_value", self.name));
+        for &(field, value) in self.fields.iter() {
+            block.add_assignment(loc, local.access_field(loc, field), value.to_rvalue());
+        }
+        local
+    }
+}
+
+pub fn scalarize<'ctx>(func: Function<'ctx>,
+                       loc: Option<Location<'ctx>>,
+                       block: Block<'ctx>,
+                       value: RValue<'ctx>,
+                       name: &str) -> Option<Scalarized<'ctx>> {
+    let ty = value.get_type();
+    let fields = tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(unsafe { types::get_ptr(&ty) })) {
+            Some(TypeKind::Struct(fields)) => {
+                fields.iter()
+                    .map(|&f| Some((f, reg.field_name(f)?.to_owned(), reg.field_type(f)?)))
+                    .collect::<Option<Vec<_>>>()
+            }
+            _ => None
+        }
+    })?;
+    let fields = fields.into_iter().map(|(field, field_name, field_ty)| {
+        let field = unsafe { field::from_ptr(field) };
+        let local = func.new_local(loc, unsafe { types::from_ptr(field_ty) },
+                                   format!("{}_{}", name, field_name));
+        block.add_assignment(loc, local, value.access_field(loc, field));
+        (field, local)
+    }).collect();
+    Some(Scalarized { func, ty, name: name.to_owned(), fields })
+}
@@ -0,0 +1,60 @@
+//! Lowering of Context::new_select, which picks one of two values
+//! depending on a condition, either with a branch or with arithmetic
+//! depending on the Lowering set on the context.
+
+use gccjit_sys::gcc_jit_types::*;
+
+use abi::{self, Scalar};
+use block::{BinaryOp, Block, UnaryOp};
+use config::Lowering;
+use context::Context;
+use location::Location;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+pub fn select<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                        loc: Option<Location<'a>>,
+                        block: Block<'a>,
+                        cond: RValue<'a>,
+                        on_true: RValue<'a>,
+                        on_false: RValue<'a>) -> (Block<'a>, RValue<'a>) {
+    let ty = on_true.get_type();
+    if ctx.config().lowering == Lowering::Branchless && is_integer(ty) {
+        // mask is all ones when cond is true and all zeros otherwise.
+        let mask = ctx.new_unary_op(loc, UnaryOp::Minus, ty, ctx.new_cast(loc, cond, ty));
+        let not_mask = ctx.new_unary_op(loc, UnaryOp::BitwiseNegate, ty, mask);
+        let picked_true = ctx.new_binary_op(loc, BinaryOp::BitwiseAnd, ty, on_true, mask);
+        let picked_false = ctx.new_binary_op(loc, BinaryOp::BitwiseAnd, ty, on_false, not_mask);
+        return (block, ctx.new_binary_op(loc, BinaryOp::BitwiseOr, ty, picked_true, picked_false));
+    }
+    let func = block.get_function();
+    let result = func.new_local(loc, ty, "select_result");
+    let true_block = func.new_block("select_true");
+    let false_block = func.new_block("select_false");
+    let join = func.new_block("select_join");
+    block.end_with_conditional(loc, cond, true_block, false_block);
+    true_block.add_assignment(loc, result, on_true);
+    true_block.end_with_jump(loc, join);
+    false_block.add_assignment(loc, result, on_false);
+    false_block.end_with_jump(loc, join);
+    (join, result.to_rvalue())
+}
+
+/// Whether bitwise operations can be done on values of a type. Pointers
+/// and bools can't be, so selects between them always branch.
+fn is_integer(ty: Type) -> bool {
+    let ty = unsafe { types::get_ptr(&ty) };
+    tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(ty)) {
+            Some(&TypeKind::Basic(GCC_JIT_TYPE_BOOL)) |
+            Some(&TypeKind::Basic(GCC_JIT_TYPE_VOID_PTR)) |
+            Some(&TypeKind::Basic(GCC_JIT_TYPE_CONST_CHAR_PTR)) |
+            Some(&TypeKind::Basic(GCC_JIT_TYPE_FILE_PTR)) => false,
+            Some(TypeKind::Basic(kind)) => {
+                matches!(abi::basic_layout(kind), Some((size, _, Scalar::Integer)) if size > 0)
+            }
+            _ => false
+        }
+    })
+}
@@ -12,6 +12,8 @@ use types;
 use location::Location;
 use location;
 use object::{ToObject, Object};
+use object;
+use tracking;
 
 /// A Struct is gccjit's representation of a composite type. Despite the name,
 /// Struct can represent either a struct, an union, or an opaque named type.
@@ -21,6 +23,40 @@ pub struct Struct<'ctx> {
     ptr: *mut gccjit_sys::gcc_jit_struct
 }
 
+/// A struct type whose fields haven't been set yet, created by
+/// Context::new_opaque_struct_type. Its type can be used, say to point to
+/// it, before set_fields gives it its fields. Since set_fields consumes
+/// it, the fields can't be set twice.
+pub struct OpaqueStruct<'ctx> {
+    st: Struct<'ctx>
+}
+
+impl<'ctx> OpaqueStruct<'ctx> {
+    pub fn as_type(&self) -> Type<'ctx> {
+        self.st.as_type()
+    }
+
+    /// Sets the fields of the struct and returns the completed struct.
+    pub fn set_fields(self,
+                      location: Option<Location<'ctx>>,
+                      fields: &[Field<'ctx>]) -> Struct<'ctx> {
+        self.st.set_fields(location, fields);
+        self.st
+    }
+}
+
+impl<'ctx> ToObject<'ctx> for OpaqueStruct<'ctx> {
+    fn to_object(&self) -> Object<'ctx> {
+        self.st.to_object()
+    }
+}
+
+impl<'ctx> fmt::Debug for OpaqueStruct<'ctx> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.st.fmt(fmt)
+    }
+}
+
 impl<'ctx> Struct<'ctx> {
     pub fn as_type(&self) -> Type<'ctx> {
         unsafe {
@@ -29,9 +65,9 @@ impl<'ctx> Struct<'ctx> {
         }
     }
 
-    pub fn set_fields(&self,
-                      location: Option<Location<'ctx>>,
-                      fields: &[Field<'ctx>]) {
+    fn set_fields(&self,
+                  location: Option<Location<'ctx>>,
+                  fields: &[Field<'ctx>]) {
         let loc_ptr = match location {
                 Some(loc) => unsafe { location::get_ptr(&loc) },
                 None => ptr::null_mut()
@@ -46,6 +82,35 @@ impl<'ctx> Struct<'ctx> {
                                                   num_fields,
                                                   fields_ptrs.as_mut_ptr());
         }
+        let ty = self.as_type();
+        let ctx = tracking::context_of(unsafe { object::get_ptr(&ty.to_object()) });
+        tracking::record_fields(ctx, unsafe { types::get_ptr(&ty) }, &fields_ptrs, false);
+    }
+
+    /// Gets the number of fields of the struct, which is 0 for a struct
+    /// whose fields haven't been set.
+    pub fn get_field_count(&self) -> usize {
+        unsafe { gccjit_sys::gcc_jit_struct_get_field_count(self.ptr) as usize }
+    }
+
+    /// Gets the field at the given position, or None if the struct has
+    /// fewer fields.
+    pub fn get_field(&self, index: usize) -> Option<Field<'ctx>> {
+        if index >= self.get_field_count() {
+            return None;
+        }
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_struct_get_field(self.ptr, index as _);
+            Some(field::from_ptr(ptr))
+        }
+    }
+
+    /// Gets the field with the given name, or None if the struct has no
+    /// such field.
+    pub fn get_field_by_name(&self, name: &str) -> Option<Field<'ctx>> {
+        (0..self.get_field_count())
+            .filter_map(|idx| self.get_field(idx))
+            .find(|field| field.name() == name)
     }
 }
 
@@ -63,6 +128,12 @@ impl<'ctx> fmt::Debug for Struct<'ctx> {
     }
 }
 
+pub unsafe fn opaque_from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_struct) -> OpaqueStruct<'ctx> {
+    OpaqueStruct {
+        st: from_ptr(ptr)
+    }
+}
+
 pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_struct) -> Struct<'ctx> {
     Struct {
         marker: PhantomData,
@@ -70,6 +141,6 @@ pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_struct) -> Struct<'ct
     }
 }
 
-
-
-
+pub unsafe fn get_ptr<'ctx>(st: &Struct<'ctx>) -> *mut gccjit_sys::gcc_jit_struct {
+    st.ptr
+}
@@ -0,0 +1,179 @@
+//! Support for snapshot tests of the assembly that gccjit generates.
+//!
+//! assert_assembly_snapshot compiles a context to assembler, normalizes
+//! away details that change between otherwise identical compilations
+//! (local label numbers, file names, compiler identification, and call
+//! frame directives), and compares the result against a golden file.
+//! Golden files are kept per target, so a snapshot recorded on one target
+//! is never compared against the output for another. Setting the
+//! GCCJIT_BLESS environment variable writes the current output to the
+//! golden file instead of comparing against it.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use context::{Context, OutputKind};
+
+/// Compiles a context to assembler and returns the generated assembly.
+pub fn assembly(ctx: &Context) -> io::Result<String> {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!("gccjit-snapshot-{}-{}.s",
+                                            process::id(),
+                                            NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    ctx.compile_to_file(OutputKind::Assembler, path.to_string_lossy());
+    let asm = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    asm
+}
+
+/// Normalizes assembly so that it can be compared between compilations:
+/// comments, blank lines, and directives that only identify the compiler,
+/// source file, or call frame layout are removed, whitespace is collapsed,
+/// and local labels are renumbered in order of first appearance.
+pub fn normalize_assembly(asm: &str) -> String {
+    const IGNORED_DIRECTIVES: &[&str] = &[".file ", ".ident ", ".cfi_", ".loc ",
+                                          ".section .note.GNU-stack"];
+    let mut labels = HashMap::new();
+    let mut out = String::new();
+    for line in asm.lines() {
+        let line = strip_comment(line).split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() || IGNORED_DIRECTIVES.iter().any(|d| line.starts_with(d)) {
+            continue;
+        }
+        out.push_str(&renumber_labels(&line, &mut labels));
+        out.push('\n');
+    }
+    out
+}
+
+/// Compares the normalized assembly of a context against the golden file
+/// for the current target, named <name>.<arch>-<os>.s in the given
+/// directory. If GCCJIT_BLESS is set, the golden file is written instead.
+/// If there is no golden file for the current target, the comparison is
+/// skipped.
+///
+/// # Panics
+/// Panics if the assembly doesn't match the golden file, or if compiling
+/// or reading and writing files fails.
+pub fn assert_assembly_snapshot<P: AsRef<Path>>(ctx: &Context, dir: P, name: &str) {
+    let actual = normalize_assembly(&assembly(ctx).expect("failed to compile to assembler"));
+    let path = golden_path(dir.as_ref(), name);
+    if env::var_os("GCCJIT_BLESS").is_some() {
+        fs::create_dir_all(dir.as_ref()).expect("failed to create snapshot directory");
+        fs::write(&path, &actual).expect("failed to write golden file");
+        return;
+    }
+    let expected = match fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => panic!("failed to read {}: {}", path.display(), err)
+    };
+    if normalize_assembly(&expected) != actual {
+        panic!("assembly doesn't match {} (rerun with GCCJIT_BLESS=1 to update it):\n{}",
+               path.display(), diff(&expected, &actual));
+    }
+}
+
+fn golden_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.{}-{}.s", name, env::consts::ARCH, env::consts::OS))
+}
+
+fn strip_comment(line: &str) -> &str {
+    // '#' starts a comment on x86, and "//" on AArch64. Neither can appear
+    // outside of string directives, which gccjit emits with quotes.
+    let mut in_string = false;
+    let bytes = line.as_bytes();
+    for (idx, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'"' => in_string = !in_string,
+            b'#' if !in_string && cfg!(any(target_arch = "x86", target_arch = "x86_64")) => return &line[..idx],
+            b'/' if !in_string && bytes.get(idx + 1) == Some(&b'/') => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Replaces every local label such as .L42 or .LFB7 with a label that
+/// only depends on the order in which labels first appear.
+fn renumber_labels(line: &str, labels: &mut HashMap<String, usize>) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(".L") {
+        out.push_str(&rest[..start]);
+        let label = &rest[start + 2..];
+        let prefix_len = label.find(|c: char| !c.is_ascii_uppercase()).unwrap_or(label.len());
+        let digits_len = label[prefix_len..].find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(label.len() - prefix_len);
+        if digits_len == 0 {
+            out.push_str(".L");
+            rest = label;
+            continue;
+        }
+        let full = &label[..prefix_len + digits_len];
+        let next = labels.len();
+        let id = *labels.entry(full.to_owned()).or_insert(next);
+        out.push_str(&format!(".L{}{}", &label[..prefix_len], id));
+        rest = &label[prefix_len + digits_len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A line-based diff that marks lines only in the expected output with -
+/// and lines only in the actual output with +.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected : Vec<_> = expected.lines().collect();
+    let actual : Vec<_> = actual.lines().collect();
+    // longest common subsequence table, filled from the end.
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            out.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j < actual.len() && (i == expected.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        } else {
+            out.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_renumbers_labels() {
+        let asm = "\t.file\t\"fake.c\"\n\
+                   square:\n\
+                   .LFB5:\n\
+                   \t.cfi_startproc\n\
+                   \tjmp\t.L7   # loop\n\
+                   .L7:\n\
+                   \tret\n\
+                   \t.ident\t\"GCC\"\n";
+        assert_eq!(normalize_assembly(asm),
+                   "square:\n.LFB0:\njmp .L1\n.L1:\nret\n");
+    }
+}
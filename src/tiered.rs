@@ -0,0 +1,107 @@
+//! Tiered compilation: a function is compiled without optimizations so
+//! that it can be called right away, and compiled again with aggressive
+//! optimizations on a background thread, replacing the first version once
+//! that is done.
+//!
+//! Contexts can't be sent between threads, so rather than taking a
+//! context, the code is built by a closure that is run once for each
+//! tier, on a context of its own.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+use context::{Context, OptimizationLevel};
+use hotswap::{HotSwap, HotSwapGuard};
+
+/// A function compiled in two tiers, created by tiered::compile.
+pub struct Tiered<F: Copy> {
+    swap: Arc<HotSwap<F>>,
+    optimized: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>
+}
+
+/// Builds the code with the given closure and compiles it at
+/// OptimizationLevel::None, returning the function with the given name.
+/// Meanwhile, the closure is run again on a background thread and compiled
+/// at OptimizationLevel::Aggressive, and the function is replaced by the
+/// optimized version when it is ready. Returns None if the unoptimized
+/// code has no function with the given name.
+///
+/// # Safety
+/// This function is unsafe because the caller must ensure that F is an
+/// extern "C" function pointer type whose signature matches the one the
+/// function is declared with.
+///
+/// # Panics
+/// Panics if F isn't the size of a pointer, or if the closure imports
+/// closures with Context::import_closure.
+pub unsafe fn compile<F, B>(name: &str, build: B) -> Option<Tiered<F>>
+    where F: Copy + 'static,
+          B: Fn(&Context) + Send + 'static {
+    let quick = Context::default();
+    quick.set_optimization_level(OptimizationLevel::None);
+    build(&quick);
+    let swap = Arc::new(HotSwap::new(quick.compile(), name)?);
+    let optimized = Arc::new(AtomicBool::new(false));
+
+    let worker = {
+        let swap = swap.clone();
+        let optimized = optimized.clone();
+        thread::spawn(move || {
+            let ctx = Context::default();
+            ctx.set_optimization_level(OptimizationLevel::Aggressive);
+            build(&ctx);
+            if swap.replace(ctx.compile()) {
+                optimized.store(true, Ordering::SeqCst);
+            }
+        })
+    };
+    Some(Tiered { swap, optimized, worker: Some(worker) })
+}
+
+impl<F: Copy> Tiered<F> {
+    /// Gets the current version of the function.
+    pub fn get(&self) -> HotSwapGuard<'_, F> {
+        self.swap.get()
+    }
+
+    /// Returns true once the optimized version has replaced the
+    /// unoptimized one.
+    pub fn is_optimized(&self) -> bool {
+        self.optimized.load(Ordering::SeqCst)
+    }
+
+    /// Waits for the optimized compilation to finish, and returns whether
+    /// it replaced the unoptimized version. It doesn't if the optimized
+    /// code has no function with the name, or if building it panicked.
+    pub fn wait(&mut self) -> bool {
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+        self.is_optimized()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use block::BinaryOp;
+    use function::FunctionType;
+
+    #[test]
+    fn optimized_tier_replaces_quick_tier() {
+        let mut tiered = unsafe {
+            compile::<extern "C" fn(i32) -> i32, _>("triple", |ctx| {
+                let int_ty = ctx.new_type::<i32>();
+                let x = ctx.new_parameter(None, int_ty, "x");
+                let fun = ctx.new_function(None, FunctionType::Exported, int_ty, &[x], "triple", false);
+                let three = ctx.new_rvalue_from_int(int_ty, 3);
+                fun.new_block("entry").end_with_return(None, ctx.new_binary_op(None, BinaryOp::Mult, int_ty, x, three));
+            })
+        }.unwrap();
+        assert_eq!((*tiered.get())(4), 12);
+        assert!(tiered.wait());
+        assert_eq!((*tiered.get())(5), 15);
+    }
+}
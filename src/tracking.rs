@@ -0,0 +1,523 @@
+//! Rust-side bookkeeping of the entities that have been created through
+//! this crate. libgccjit offers almost no introspection of the objects it
+//! hands out, so anything that needs to reason about a context after the
+//! fact (such as Context::verify) consults the records kept here.
+//!
+//! Records are grouped by the gcc_jit_context that owns them and are
+//! discarded when that context is released. A context and everything
+//! created from it stay on the thread that created the context, so each
+//! thread keeps the records of its own contexts, and contexts that are
+//! built on different threads never wait on each other for them.
+//!
+//! What every context records is its configuration and the types,
+//! fields, functions, and globals created through it, which the
+//! type-directed helpers rely on. The statements added to blocks, calls,
+//! field accesses, and the values of integer constants are only recorded
+//! for contexts that opted in with Context::set_tracking, since they are
+//! only needed by Context::verify and the listings of
+//! Context::pretty_print and Function::to_dot.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types;
+
+use block::BinaryOp;
+use config::ContextConfig;
+use emit;
+use context::GlobalKind;
+use function::{FunctionType, InlineMode};
+
+/// How a tracked type was constructed.
+pub enum TypeKind {
+    /// A type obtained from gcc_jit_context_get_type.
+    Basic(gcc_jit_types),
+    /// A pointer to the given type.
+    Pointer(*mut gccjit_sys::gcc_jit_type),
+    /// A const-qualified version of the given type.
+    Const(*mut gccjit_sys::gcc_jit_type),
+    /// A volatile-qualified version of the given type.
+    Volatile(*mut gccjit_sys::gcc_jit_type),
+    /// An array of the given number of elements of the given type.
+    Array(*mut gccjit_sys::gcc_jit_type, i32),
+    /// A vector of the given number of elements of the given type, for
+    /// SIMD operations.
+    Vector(*mut gccjit_sys::gcc_jit_type, usize),
+    /// A struct type with the given fields, in order.
+    Struct(Vec<*mut gccjit_sys::gcc_jit_field>),
+    /// A union type with the given fields.
+    Union(Vec<*mut gccjit_sys::gcc_jit_field>),
+    /// A struct type whose fields haven't been set yet.
+    Opaque
+}
+
+pub struct FunctionInfo {
+    pub ptr: *mut gccjit_sys::gcc_jit_function,
+    pub name: String,
+    pub kind: FunctionType,
+    pub return_type: *mut gccjit_sys::gcc_jit_type,
+    pub params: Vec<*mut gccjit_sys::gcc_jit_param>,
+    pub is_variadic: bool,
+    pub blocks: Vec<*mut gccjit_sys::gcc_jit_block>,
+    /// The types of the function's locals, in the order they were created.
+    pub locals: Vec<*mut gccjit_sys::gcc_jit_type>,
+    /// The mode set with Function::set_inline_mode, if any.
+    pub inline_mode: Option<InlineMode>
+}
+
+pub struct GlobalInfo {
+    pub name: String,
+    pub kind: GlobalKind,
+    pub ty: *mut gccjit_sys::gcc_jit_type
+}
+
+pub enum Statement {
+    Eval(*mut gccjit_sys::gcc_jit_rvalue),
+    Assignment(*mut gccjit_sys::gcc_jit_lvalue, *mut gccjit_sys::gcc_jit_rvalue),
+    AssignmentOp(*mut gccjit_sys::gcc_jit_lvalue, BinaryOp, *mut gccjit_sys::gcc_jit_rvalue),
+    Comment(String),
+    Jump(*mut gccjit_sys::gcc_jit_block),
+    Conditional(*mut gccjit_sys::gcc_jit_rvalue, *mut gccjit_sys::gcc_jit_block, *mut gccjit_sys::gcc_jit_block),
+    Return(*mut gccjit_sys::gcc_jit_rvalue),
+    VoidReturn,
+    /// Jumps to the block of the case that contains the value, or to the
+    /// default block if none does.
+    Switch(*mut gccjit_sys::gcc_jit_rvalue, *mut gccjit_sys::gcc_jit_block, Vec<CaseInfo>),
+    /// Runs the first block, and the second if the first throws.
+    #[cfg(feature = "master")]
+    TryCatch(*mut gccjit_sys::gcc_jit_block, *mut gccjit_sys::gcc_jit_block),
+    /// Runs the first block, then the second however the first is left.
+    #[cfg(feature = "master")]
+    TryFinally(*mut gccjit_sys::gcc_jit_block, *mut gccjit_sys::gcc_jit_block)
+}
+
+impl Statement {
+    /// Returns true if this statement terminates the block containing it.
+    pub fn is_terminator(&self) -> bool {
+        matches!(*self,
+                 Statement::Jump(..) |
+                 Statement::Conditional(..) |
+                 Statement::Return(..) |
+                 Statement::VoidReturn |
+                 Statement::Switch(..))
+    }
+}
+
+pub struct StatementInfo {
+    pub loc: *mut gccjit_sys::gcc_jit_location,
+    pub kind: Statement
+}
+
+pub struct BlockInfo {
+    pub name: String,
+    pub function: *mut gccjit_sys::gcc_jit_function,
+    pub statements: Vec<StatementInfo>,
+    /// The location set with Block::set_default_location, or null.
+    pub default_location: *mut gccjit_sys::gcc_jit_location
+}
+
+pub struct CallInfo {
+    pub loc: *mut gccjit_sys::gcc_jit_location,
+    pub function: *mut gccjit_sys::gcc_jit_function,
+    pub args: Vec<*mut gccjit_sys::gcc_jit_rvalue>
+}
+
+/// The parts of a case of a switch. gccjit rejects cases whose minimum is
+/// greater than their maximum, so these are kept by the Case itself
+/// rather than looked up by the address of the gccjit case.
+#[derive(Copy, Clone)]
+pub struct CaseInfo {
+    pub min: *mut gccjit_sys::gcc_jit_rvalue,
+    pub max: *mut gccjit_sys::gcc_jit_rvalue,
+    pub dest: *mut gccjit_sys::gcc_jit_block
+}
+
+/// A host symbol registered with Context::register_symbol.
+pub enum SymbolInfo {
+    Function(*mut gccjit_sys::gcc_jit_rvalue),
+    Global(*mut gccjit_sys::gcc_jit_lvalue)
+}
+
+pub struct FieldAccessInfo {
+    pub loc: *mut gccjit_sys::gcc_jit_location,
+    pub value_type: *mut gccjit_sys::gcc_jit_type,
+    pub field: *mut gccjit_sys::gcc_jit_field,
+    pub through_pointer: bool
+}
+
+/// Everything that has been recorded about a single context.
+pub struct ContextInfo {
+    /// A number that is unique among all contexts created by the process,
+    /// assigned in the order the contexts were created.
+    pub id: u64,
+    /// The context this one was created from with new_child_context.
+    pub parent: Option<*mut gccjit_sys::gcc_jit_context>,
+    pub types: HashMap<*mut gccjit_sys::gcc_jit_type, TypeKind>,
+    pub field_types: HashMap<*mut gccjit_sys::gcc_jit_field, *mut gccjit_sys::gcc_jit_type>,
+    pub field_widths: HashMap<*mut gccjit_sys::gcc_jit_field, i32>,
+    pub field_names: HashMap<*mut gccjit_sys::gcc_jit_field, String>,
+    pub functions: Vec<FunctionInfo>,
+    /// The index in functions of each function, by its address.
+    function_index: HashMap<*mut gccjit_sys::gcc_jit_function, usize>,
+    pub globals: Vec<GlobalInfo>,
+    pub blocks: HashMap<*mut gccjit_sys::gcc_jit_block, BlockInfo>,
+    pub field_owners: HashMap<*mut gccjit_sys::gcc_jit_field, *mut gccjit_sys::gcc_jit_type>,
+    pub calls: Vec<CallInfo>,
+    pub field_accesses: Vec<FieldAccessInfo>,
+    /// The values of the integer constants created through the context,
+    /// which gccjit has no way of reading back.
+    pub int_constants: HashMap<*mut gccjit_sys::gcc_jit_rvalue, i64>,
+    pub symbols: HashMap<String, SymbolInfo>,
+    pub config: ContextConfig,
+    /// The struct types created for Rust tuples, by the types of their
+    /// elements, so that every use of a tuple type gets the same struct.
+    pub tuple_types: HashMap<Vec<*mut gccjit_sys::gcc_jit_type>, *mut gccjit_sys::gcc_jit_type>,
+    /// The errors that Context::drain_new_errors has already returned.
+    pub seen_errors: SeenErrors,
+    /// The number of globals that this crate has created in the context
+    /// for its own use.
+    internal_globals: usize
+}
+
+/// The first and last errors of a context as of the last call to
+/// Context::drain_new_errors. The last error is kept along with the
+/// address of its message, since gccjit replaces the message every time
+/// an error is recorded.
+#[derive(Default)]
+pub struct SeenErrors {
+    pub first: bool,
+    pub last: Option<(*const c_char, String)>
+}
+
+impl ContextInfo {
+    fn new() -> ContextInfo {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        ContextInfo {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            parent: None,
+            types: HashMap::new(),
+            field_types: HashMap::new(),
+            field_widths: HashMap::new(),
+            field_names: HashMap::new(),
+            functions: Vec::new(),
+            function_index: HashMap::new(),
+            globals: Vec::new(),
+            blocks: HashMap::new(),
+            field_owners: HashMap::new(),
+            calls: Vec::new(),
+            field_accesses: Vec::new(),
+            int_constants: HashMap::new(),
+            symbols: HashMap::new(),
+            config: ContextConfig::default(),
+            tuple_types: HashMap::new(),
+            seen_errors: SeenErrors::default(),
+            internal_globals: 0
+        }
+    }
+
+    /// Numbers a global that this crate creates in the context for its own
+    /// use, counting from zero in the order they are created.
+    pub fn next_internal_global(&mut self) -> usize {
+        self.internal_globals += 1;
+        self.internal_globals - 1
+    }
+
+    /// Records a function that was created through the context.
+    pub fn add_function(&mut self, info: FunctionInfo) {
+        self.function_index.insert(info.ptr, self.functions.len());
+        self.functions.push(info);
+    }
+}
+
+/// The records for all live contexts of a thread. The records of an
+/// object are usually kept with the context that owns it, so lookups of
+/// individual objects try that context first. Objects can be shared
+/// between a parent context and its children, though, so lookups fall back
+/// to searching every context.
+pub struct Registry {
+    contexts: HashMap<*mut gccjit_sys::gcc_jit_context, ContextInfo>
+}
+
+impl Registry {
+    pub fn context(&mut self, ctx: *mut gccjit_sys::gcc_jit_context) -> &mut ContextInfo {
+        self.contexts.entry(ctx).or_insert_with(ContextInfo::new)
+    }
+
+    pub fn get_context(&self, ctx: *mut gccjit_sys::gcc_jit_context) -> Option<&ContextInfo> {
+        self.contexts.get(&ctx)
+    }
+
+    /// The number of parents that a context has.
+    pub fn depth(&self, ctx: *mut gccjit_sys::gcc_jit_context) -> usize {
+        let mut parent = self.contexts.get(&ctx).and_then(|info| info.parent);
+        let mut depth = 0;
+        while let Some(ctx) = parent {
+            parent = self.contexts.get(&ctx).and_then(|info| info.parent);
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Finds a symbol registered with a context or one of its ancestors.
+    pub fn symbol(&self, mut ctx: *mut gccjit_sys::gcc_jit_context, name: &str) -> Option<&SymbolInfo> {
+        loop {
+            let info = self.contexts.get(&ctx)?;
+            if let Some(symbol) = info.symbols.get(name) {
+                return Some(symbol);
+            }
+            ctx = info.parent?;
+        }
+    }
+
+    /// Finds the context whose records hold an object owned by the given
+    /// context, trying the owner before the others.
+    fn holder<F>(&self, owner: *mut gccjit_sys::gcc_jit_context, holds: F) -> Option<&ContextInfo>
+        where F: Fn(&ContextInfo) -> bool {
+        match self.contexts.get(&owner) {
+            Some(info) if holds(info) => Some(info),
+            _ => self.contexts.values().find(|info| holds(info))
+        }
+    }
+
+    /// Like holder, but for changing the records.
+    fn holder_mut<F>(&mut self, owner: *mut gccjit_sys::gcc_jit_context, holds: F) -> Option<&mut ContextInfo>
+        where F: Fn(&ContextInfo) -> bool {
+        let ctx = match self.contexts.get(&owner) {
+            Some(info) if holds(info) => owner,
+            _ => *self.contexts.iter().find(|&(_, info)| holds(info))?.0
+        };
+        self.contexts.get_mut(&ctx)
+    }
+
+    pub fn type_kind(&self, ty: *mut gccjit_sys::gcc_jit_type) -> Option<&TypeKind> {
+        let owner = owner(ty, gccjit_sys::gcc_jit_type_as_object);
+        self.holder(owner, |c| c.types.contains_key(&ty)).map(|c| &c.types[&ty])
+    }
+
+    /// Strips any const or volatile qualifiers from a type.
+    pub fn unqualified(&self, mut ty: *mut gccjit_sys::gcc_jit_type) -> *mut gccjit_sys::gcc_jit_type {
+        loop {
+            match self.type_kind(ty) {
+                Some(&TypeKind::Const(base)) | Some(&TypeKind::Volatile(base)) => ty = base,
+                _ => return ty
+            }
+        }
+    }
+
+    pub fn function(&self, func: *mut gccjit_sys::gcc_jit_function) -> Option<&FunctionInfo> {
+        let owner = owner(func, gccjit_sys::gcc_jit_function_as_object);
+        self.holder(owner, |c| c.function_index.contains_key(&func))
+            .map(|c| &c.functions[c.function_index[&func]])
+    }
+
+    pub fn function_mut(&mut self, func: *mut gccjit_sys::gcc_jit_function) -> Option<&mut FunctionInfo> {
+        let owner = owner(func, gccjit_sys::gcc_jit_function_as_object);
+        self.holder_mut(owner, |c| c.function_index.contains_key(&func))
+            .map(|c| &mut c.functions[c.function_index[&func]])
+    }
+
+    pub fn block(&self, block: *mut gccjit_sys::gcc_jit_block) -> Option<&BlockInfo> {
+        let owner = owner(block, gccjit_sys::gcc_jit_block_as_object);
+        self.holder(owner, |c| c.blocks.contains_key(&block)).map(|c| &c.blocks[&block])
+    }
+
+    pub fn block_mut(&mut self, block: *mut gccjit_sys::gcc_jit_block) -> Option<&mut BlockInfo> {
+        let owner = owner(block, gccjit_sys::gcc_jit_block_as_object);
+        self.holder_mut(owner, |c| c.blocks.contains_key(&block)).and_then(|c| c.blocks.get_mut(&block))
+    }
+
+    /// The value of an integer constant.
+    pub fn int_constant(&self, rvalue: *mut gccjit_sys::gcc_jit_rvalue) -> Option<i64> {
+        let owner = owner(rvalue, gccjit_sys::gcc_jit_rvalue_as_object);
+        self.holder(owner, |c| c.int_constants.contains_key(&rvalue)).map(|c| c.int_constants[&rvalue])
+    }
+
+    pub fn field_type(&self, field: *mut gccjit_sys::gcc_jit_field) -> Option<*mut gccjit_sys::gcc_jit_type> {
+        let owner = owner(field, gccjit_sys::gcc_jit_field_as_object);
+        self.holder(owner, |c| c.field_types.contains_key(&field)).map(|c| c.field_types[&field])
+    }
+
+    /// The width in bits of a field, if it is a bitfield.
+    pub fn field_width(&self, field: *mut gccjit_sys::gcc_jit_field) -> Option<i32> {
+        let owner = owner(field, gccjit_sys::gcc_jit_field_as_object);
+        self.holder(owner, |c| c.field_widths.contains_key(&field)).map(|c| c.field_widths[&field])
+    }
+
+    pub fn field_name(&self, field: *mut gccjit_sys::gcc_jit_field) -> Option<&str> {
+        let owner = owner(field, gccjit_sys::gcc_jit_field_as_object);
+        self.holder(owner, |c| c.field_names.contains_key(&field)).map(|c| &c.field_names[&field][..])
+    }
+
+    pub fn field_owner(&self, field: *mut gccjit_sys::gcc_jit_field) -> Option<*mut gccjit_sys::gcc_jit_type> {
+        let owner = owner(field, gccjit_sys::gcc_jit_field_as_object);
+        self.holder(owner, |c| c.field_owners.contains_key(&field)).map(|c| c.field_owners[&field])
+    }
+}
+
+/// Gets the context that owns a gccjit object, given the function that
+/// converts it to a gcc_jit_object, or null for a null object.
+fn owner<T>(ptr: *mut T,
+            as_object: unsafe extern "C" fn(*mut T) -> *mut gccjit_sys::gcc_jit_object)
+            -> *mut gccjit_sys::gcc_jit_context {
+    if ptr.is_null() {
+        ptr::null_mut()
+    } else {
+        context_of(unsafe { as_object(ptr) })
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry { contexts: HashMap::new() });
+}
+
+/// Runs the given closure with exclusive access to the registry of the
+/// current thread.
+pub fn with_registry<F, R>(f: F) -> R
+    where F: FnOnce(&mut Registry) -> R {
+    REGISTRY.with(|registry| f(&mut registry.borrow_mut()))
+}
+
+/// Runs the given closure with the records of a single context.
+pub fn with_context<F, R>(ctx: *mut gccjit_sys::gcc_jit_context, f: F) -> R
+    where F: FnOnce(&mut ContextInfo) -> R {
+    with_registry(|reg| f(reg.context(ctx)))
+}
+
+/// Discards everything recorded about a context that is being released.
+pub fn release(ctx: *mut gccjit_sys::gcc_jit_context) {
+    // contexts that are dropped while their thread exits may outlive the
+    // registry, which then has nothing left to discard.
+    let _ = REGISTRY.try_with(|registry| registry.borrow_mut().contexts.remove(&ctx));
+}
+
+/// Counts of what has been recorded about a context, from
+/// Context::stats.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ContextStats {
+    pub types: usize,
+    pub fields: usize,
+    pub functions: usize,
+    pub globals: usize,
+    pub blocks: usize,
+    pub statements: usize,
+    /// The child contexts of the context that haven't been dropped yet.
+    pub live_children: usize
+}
+
+impl Registry {
+    pub fn stats(&self, ctx: *mut gccjit_sys::gcc_jit_context) -> ContextStats {
+        let live_children = self.contexts.values().filter(|info| info.parent == Some(ctx)).count();
+        match self.contexts.get(&ctx) {
+            Some(info) => ContextStats {
+                types: info.types.len(),
+                fields: info.field_types.len(),
+                functions: info.functions.len(),
+                globals: info.globals.len(),
+                blocks: info.blocks.len(),
+                statements: info.blocks.values().map(|block| block.statements.len()).sum(),
+                live_children
+            },
+            None => ContextStats { live_children, ..ContextStats::default() }
+        }
+    }
+}
+
+/// Starts the records of a newly acquired context, so that its id
+/// reflects the order in which contexts were created.
+pub fn register(ctx: *mut gccjit_sys::gcc_jit_context,
+                parent: Option<*mut gccjit_sys::gcc_jit_context>) {
+    with_context(ctx, |info| info.parent = parent)
+}
+
+/// Panics if a gccjit object belongs to a context that objects of ctx
+/// can't refer to, that is, one other than ctx or one of its parents.
+/// Only checked in debug builds.
+pub fn check_provenance(ctx: *mut gccjit_sys::gcc_jit_context,
+                        obj: *mut gccjit_sys::gcc_jit_object) {
+    if !cfg!(debug_assertions) || obj.is_null() {
+        return;
+    }
+    let owner = context_of(obj);
+    let mismatch = with_registry(|reg| {
+        let mut current = Some(ctx);
+        while let Some(c) = current {
+            if c == owner {
+                return None;
+            }
+            current = reg.contexts.get(&c).and_then(|info| info.parent);
+        }
+        let id = |c| reg.contexts.get(&c).map_or(0, |info: &ContextInfo| info.id);
+        Some((id(owner), id(ctx)))
+    });
+    if let Some((owner_id, ctx_id)) = mismatch {
+        let name = unsafe { emit::debug_string(obj) };
+        panic!("{} belongs to context #{} and can't be used with context #{}, \
+                which isn't that context or one of its children",
+               name, owner_id, ctx_id);
+    }
+}
+
+/// Gets the context that owns a gccjit object.
+pub fn context_of(obj: *mut gccjit_sys::gcc_jit_object) -> *mut gccjit_sys::gcc_jit_context {
+    unsafe { gccjit_sys::gcc_jit_object_get_context(obj) }
+}
+
+/// Records that the given fields now make up a struct or union type.
+pub fn record_fields(ctx: *mut gccjit_sys::gcc_jit_context,
+                     owner: *mut gccjit_sys::gcc_jit_type,
+                     fields: &[*mut gccjit_sys::gcc_jit_field],
+                     is_union: bool) {
+    with_context(ctx, |info| {
+        for &field in fields {
+            info.field_owners.insert(field, owner);
+        }
+        let kind = if is_union {
+            TypeKind::Union(fields.to_vec())
+        } else {
+            TypeKind::Struct(fields.to_vec())
+        };
+        info.types.insert(owner, kind);
+    })
+}
+
+/// Records a field access performed on a value of the given type.
+pub fn record_field_access(value: *mut gccjit_sys::gcc_jit_object,
+                           access: FieldAccessInfo) {
+    let ctx = context_of(value);
+    with_context(ctx, |info| {
+        if info.config.tracking {
+            info.field_accesses.push(access);
+        }
+    })
+}
+
+/// Records the value of an integer constant.
+pub fn record_int_constant(ctx: *mut gccjit_sys::gcc_jit_context,
+                           rvalue: *mut gccjit_sys::gcc_jit_rvalue,
+                           value: i64) {
+    with_context(ctx, |info| {
+        if info.config.tracking {
+            info.int_constants.insert(rvalue, value);
+        }
+    })
+}
+
+/// Records a statement that was added to a block.
+pub fn record_statement(block: *mut gccjit_sys::gcc_jit_block,
+                        loc: *mut gccjit_sys::gcc_jit_location,
+                        kind: Statement) {
+    with_registry(|reg| {
+        let owner = owner(block, gccjit_sys::gcc_jit_block_as_object);
+        let info = reg.holder_mut(owner, |c| c.blocks.contains_key(&block));
+        if let Some(info) = info.filter(|c| c.config.tracking) {
+            info.blocks.get_mut(&block).unwrap().statements.push(StatementInfo {
+                loc,
+                kind
+            });
+        }
+    })
+}
@@ -1,15 +1,34 @@
+use std::error::Error;
 use std::marker::PhantomData;
 use std::fmt;
+use std::ptr::NonNull;
 
 use gccjit_sys;
 
+use abi;
 use context::Context;
 use context;
+use field::{self, Field};
 use object;
 use object::{Object, ToObject};
+use tracking::{self, TypeKind};
 
 use gccjit_sys::gcc_jit_types::*;
 
+/// The error returned when the layout of a type can't be determined, such
+/// as for an opaque struct or, without libgccjit's own layout queries, a
+/// type that wasn't created through this library.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct UnknownLayout;
+
+impl fmt::Display for UnknownLayout {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "the layout of the type isn't known")
+    }
+}
+
+impl Error for UnknownLayout {}
+
 /// A representation of a type, as it is known to the JIT compiler.
 /// Types can be created through the Typeable trait or they can
 /// be created dynamically by composing Field types.
@@ -39,14 +58,16 @@ impl<'ctx> Type<'ctx> {
     /// Given a type T, creates a type to *T, a pointer to T.
     pub fn make_pointer(self) -> Type<'ctx> {
         unsafe {
-            from_ptr(gccjit_sys::gcc_jit_type_get_pointer(self.ptr))
+            let ptr = gccjit_sys::gcc_jit_type_get_pointer(self.ptr);
+            record(from_ptr(ptr), TypeKind::Pointer(self.ptr))
         }
     }
 
     /// Given a type T, creates a type of const T.
     pub fn make_const(self) -> Type<'ctx> {
         unsafe {
-            from_ptr(gccjit_sys::gcc_jit_type_get_const(self.ptr))
+            let ptr = gccjit_sys::gcc_jit_type_get_const(self.ptr);
+            record(from_ptr(ptr), TypeKind::Const(self.ptr))
         }
     }
 
@@ -54,9 +75,91 @@ impl<'ctx> Type<'ctx> {
     /// has the semantics of C's volatile.
     pub fn make_volatile(self) -> Type<'ctx> {
         unsafe {
-            from_ptr(gccjit_sys::gcc_jit_type_get_volatile(self.ptr))
+            let ptr = gccjit_sys::gcc_jit_type_get_volatile(self.ptr);
+            record(from_ptr(ptr), TypeKind::Volatile(self.ptr))
         }
     }
+
+    /// Gets the field at the given position of a struct type, such as _1
+    /// for the second element of a tuple type. Returns None if the type
+    /// isn't a struct with that many fields.
+    pub fn field(self, index: usize) -> Option<Field<'ctx>> {
+        tracking::with_registry(|reg| {
+            match reg.type_kind(reg.unqualified(self.ptr)) {
+                Some(TypeKind::Struct(fields)) => fields.get(index).map(|&f| unsafe { field::from_ptr(f) }),
+                _ => None
+            }
+        })
+    }
+
+    /// Given an integer or floating point type T, creates a vector type of
+    /// num_units elements of T, which arithmetic, bitwise, and comparison
+    /// operations apply to element-wise. num_units must be a power of two.
+    pub fn make_vector(self, num_units: usize) -> Type<'ctx> {
+        unsafe {
+            let ptr = gccjit_sys::gcc_jit_type_get_vector(self.ptr, num_units as _);
+            record(from_ptr(ptr), TypeKind::Vector(self.ptr, num_units))
+        }
+    }
+
+    /// Returns true if this type, ignoring qualifiers, is float, double,
+    /// or long double.
+    pub fn is_floating_point(self) -> bool {
+        #[cfg(feature = "master")]
+        unsafe {
+            gccjit_sys::gcc_jit_type_is_floating_point(self.ptr) != 0
+        }
+        #[cfg(not(feature = "master"))]
+        tracking::with_registry(|reg| {
+            matches!(reg.type_kind(reg.unqualified(self.ptr)),
+                     Some(&TypeKind::Basic(GCC_JIT_TYPE_FLOAT)) |
+                     Some(&TypeKind::Basic(GCC_JIT_TYPE_DOUBLE)) |
+                     Some(&TypeKind::Basic(GCC_JIT_TYPE_LONG_DOUBLE)))
+        })
+    }
+
+    /// Returns true if this type, ignoring qualifiers, is a signed integer
+    /// type. Plain char is signed if it is signed on the host.
+    pub fn is_signed(self) -> bool {
+        tracking::with_registry(|reg| {
+            match reg.type_kind(reg.unqualified(self.ptr)) {
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_CHAR)) => ::std::os::raw::c_char::MIN != 0,
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_SIGNED_CHAR)) |
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_SHORT)) |
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_INT)) |
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_LONG)) |
+                Some(&TypeKind::Basic(GCC_JIT_TYPE_LONG_LONG)) => true,
+                _ => false
+            }
+        })
+    }
+
+    /// Returns true if this type, ignoring qualifiers, is a pointer type,
+    /// including void * and const char *.
+    pub fn is_pointer(self) -> bool {
+        unsafe {
+            let unqualified = gccjit_sys::gcc_jit_type_unqualified(self.ptr);
+            !gccjit_sys::gcc_jit_type_is_pointer(unqualified).is_null()
+        }
+    }
+
+    /// Gets the alignment of this type in bytes.
+    pub fn get_alignment(self) -> Result<usize, UnknownLayout> {
+        #[cfg(feature = "master")]
+        unsafe {
+            let align = gccjit_sys::gcc_jit_type_get_alignment(self.ptr);
+            if align > 0 { Ok(align as usize) } else { Err(UnknownLayout) }
+        }
+        #[cfg(not(feature = "master"))]
+        tracking::with_registry(|reg| abi::size_and_align(reg, self.ptr))
+            .map(|(_, align)| align)
+            .ok_or(UnknownLayout)
+    }
+
+    /// Returns true if this type, ignoring qualifiers, is void.
+    pub fn is_void(self) -> bool {
+        tracking::with_registry(|reg| abi::is_void(reg, self.ptr))
+    }
 }
 
 /// Typeable is a trait for types that have a corresponding type within
@@ -74,7 +177,7 @@ macro_rules! typeable_def {
                 unsafe {
                     let ctx_ptr = context::get_ptr(ctx);
                     let ptr = gccjit_sys::gcc_jit_context_get_type(ctx_ptr, $expr);
-                    from_ptr(ptr)
+                    record(from_ptr(ptr), TypeKind::Basic($expr))
                 }
             }
         }
@@ -96,31 +199,139 @@ typeable_def!(f32, GCC_JIT_TYPE_FLOAT);
 typeable_def!(f64, GCC_JIT_TYPE_DOUBLE);
 typeable_def!(usize, GCC_JIT_TYPE_SIZE_T);
 
-/// Specific implementations of Typeable for *mut T and *const T that
-/// represent void* and const void*, respectively. These impls should
-/// only be used to expose opaque pointers to gccjit, not to create
-/// pointers that are not opaque to gcc. For that, the make_pointer
-/// function should be used.
-impl<T> Typeable for *mut T {
+/// Pointers are typed pointers to T, so *mut () is void* and *const u8
+/// is const unsigned char*. Pointers to types that gccjit doesn't know
+/// about can be made with *mut () or with make_pointer on a struct type.
+impl<T: Typeable> Typeable for *mut T {
     fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
-        unsafe {
-            let ctx_ptr = context::get_ptr(ctx);
-            let ptr = gccjit_sys::gcc_jit_context_get_type(ctx_ptr, GCC_JIT_TYPE_VOID_PTR);
-            from_ptr(ptr)
-        }
+        T::get_type(ctx).make_pointer()
     }
 }
 
-impl<T> Typeable for *const T {
+impl<T: Typeable> Typeable for *const T {
     fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
-        unsafe {
-            let ctx_ptr = context::get_ptr(ctx);
-            let ptr = gccjit_sys::gcc_jit_context_get_type(ctx_ptr, GCC_JIT_TYPE_VOID_PTR);
-            from_ptr(ptr).make_const()
+        T::get_type(ctx).make_const().make_pointer()
+    }
+}
+
+impl<T: Typeable> Typeable for NonNull<T> {
+    fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+        <*mut T as Typeable>::get_type(ctx)
+    }
+}
+
+/// Option<NonNull<T>> has the same representation as *mut T, with None
+/// as the null pointer.
+impl<T: Typeable> Typeable for Option<NonNull<T>> {
+    fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+        <*mut T as Typeable>::get_type(ctx)
+    }
+}
+
+/// Fixed-size arrays are arrays of the same number of elements in gccjit,
+/// so that a field or local holding a buffer can be declared with, say,
+/// new_type::<[u8; 64]>().
+impl<T: Typeable, const N: usize> Typeable for [T; N] {
+    fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+        ctx.new_array_type(None, T::get_type(ctx), N as i32)
+    }
+}
+
+/// Tuples are repr(C) structs with a field for each element, named _0,
+/// _1, and so on, which is how Rust lays out #[repr(C)] tuple structs.
+/// Note that Rust doesn't guarantee this layout for tuples themselves, so
+/// values should be passed to and from jitted code as #[repr(C)] structs.
+macro_rules! typeable_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Typeable),+> Typeable for ($($name,)+) {
+            fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                tuple_type(ctx, &[$($name::get_type(ctx)),+])
+            }
         }
     }
 }
 
+typeable_tuple!(A);
+typeable_tuple!(A, B);
+typeable_tuple!(A, B, C);
+typeable_tuple!(A, B, C, D);
+typeable_tuple!(A, B, C, D, E);
+typeable_tuple!(A, B, C, D, E, F);
+typeable_tuple!(A, B, C, D, E, F, G);
+typeable_tuple!(A, B, C, D, E, F, G, H);
+
+/// C function pointers are function pointer types in gccjit, as from
+/// Context::new_function_pointer_type.
+macro_rules! typeable_fn {
+    ($($name:ident),*) => {
+        impl<R: Typeable, $($name: Typeable),*> Typeable for extern "C" fn($($name),*) -> R {
+            fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                ctx.new_function_pointer_type(None, R::get_type(ctx), &[$($name::get_type(ctx)),*], false)
+            }
+        }
+
+        impl<R: Typeable, $($name: Typeable),*> Typeable for unsafe extern "C" fn($($name),*) -> R {
+            fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                <extern "C" fn($($name),*) -> R as Typeable>::get_type(ctx)
+            }
+        }
+
+        // None is the null function pointer.
+        impl<R: Typeable, $($name: Typeable),*> Typeable for Option<extern "C" fn($($name),*) -> R> {
+            fn get_type<'a, 'ctx>(ctx: &'a Context<'ctx>) -> Type<'a> {
+                <extern "C" fn($($name),*) -> R as Typeable>::get_type(ctx)
+            }
+        }
+    }
+}
+
+typeable_fn!();
+typeable_fn!(A);
+typeable_fn!(A, B);
+typeable_fn!(A, B, C);
+typeable_fn!(A, B, C, D);
+typeable_fn!(A, B, C, D, E);
+typeable_fn!(A, B, C, D, E, F);
+typeable_fn!(A, B, C, D, E, F, G);
+typeable_fn!(A, B, C, D, E, F, G, H);
+
+fn tuple_type<'a, 'ctx>(ctx: &'a Context<'ctx>, elements: &[Type<'a>]) -> Type<'a> {
+    let key : Vec<_> = elements.iter().map(|ty| ty.ptr).collect();
+    let ctx_ptr = unsafe { context::get_ptr(ctx) };
+    let (existing, count) = tracking::with_context(ctx_ptr, |info| {
+        (info.tuple_types.get(&key).cloned(), info.tuple_types.len())
+    });
+    if let Some(ptr) = existing {
+        return unsafe { from_ptr(ptr) };
+    }
+    let fields : Vec<_> = elements.iter().enumerate()
+        .map(|(idx, &ty)| ctx.new_field(None, ty, format!("_{}", idx)))
+        .collect();
+    let ty = ctx.new_struct_type(None, format!("tuple{}", count), &fields).as_type();
+    tracking::with_context(ctx_ptr, |info| info.tuple_types.insert(key, ty.ptr));
+    ty
+}
+
+/// Gets the element type of a vector type created with make_vector.
+pub fn vector_element_type<'ctx>(ty: Type<'ctx>) -> Option<Type<'ctx>> {
+    tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(ty.ptr)) {
+            Some(&TypeKind::Vector(elem, _)) => Some(unsafe { from_ptr(elem) }),
+            _ => None
+        }
+    })
+}
+
+/// Remembers how a type was constructed, so that wrapper-side checks can
+/// see through qualifiers and pointers.
+pub fn record<'ctx>(ty: Type<'ctx>, kind: TypeKind) -> Type<'ctx> {
+    let ctx = tracking::context_of(unsafe { object::get_ptr(&ty.to_object()) });
+    tracking::with_context(ctx, |info| {
+        info.types.insert(ty.ptr, kind);
+    });
+    ty
+}
+
 pub unsafe fn from_ptr<'ctx>(ptr: *mut gccjit_sys::gcc_jit_type) -> Type<'ctx> {
     Type {
         marker: PhantomData,
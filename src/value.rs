@@ -0,0 +1,179 @@
+use std::mem;
+use std::os::raw::{c_char, c_int, c_long, c_longlong, c_short};
+use std::ptr;
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types::*;
+
+use context::Context;
+use rvalue::RValue;
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// JitValue is a dynamically typed scalar, for hosts such as interpreters
+/// whose values only get a gccjit type when they cross into jitted code.
+/// A JitValue can be turned into a constant of any scalar type and read
+/// from or written to memory laid out according to a type.
+///
+/// Only the basic integer, floating point, and bool types, along with
+/// pointers, are supported. Every conversion returns None (or false) for
+/// other types or for types that weren't constructed through this library.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JitValue {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Ptr(*mut ()),
+    Bool(bool)
+}
+
+/// How values of a scalar type are represented in memory.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Repr {
+    Signed(usize),
+    Unsigned(usize),
+    Float(usize),
+    Bool,
+    Pointer
+}
+
+impl JitValue {
+    /// Creates a constant of the given type from this value, converting
+    /// it the way a C cast would. Pointers can only be created from
+    /// pointers and integers.
+    pub fn to_constant<'a, 'ctx>(&self,
+                                 ctx: &'a Context<'ctx>,
+                                 ty: Type<'a>) -> Option<RValue<'a>> {
+        match repr(ty)? {
+            Repr::Signed(_) | Repr::Unsigned(_) => Some(ctx.new_rvalue_from_long(ty, self.as_i64())),
+            Repr::Float(_) => Some(ctx.new_rvalue_from_double(ty, self.as_f64())),
+            Repr::Bool => {
+                if self.as_f64() != 0.0 || self.as_i64() != 0 {
+                    Some(ctx.new_rvalue_one(ty))
+                } else {
+                    Some(ctx.new_rvalue_zero(ty))
+                }
+            }
+            Repr::Pointer => match *self {
+                JitValue::Ptr(ptr) => Some(ctx.new_rvalue_from_ptr(ty, ptr)),
+                JitValue::Int(_) | JitValue::UInt(_) => {
+                    Some(ctx.new_rvalue_from_ptr(ty, self.as_i64() as usize as *mut ()))
+                }
+                _ => None
+            }
+        }
+    }
+
+    /// Reads a value of the given type from memory.
+    ///
+    /// # Safety
+    ///
+    /// ptr must be valid for reads of a value of the given type, although
+    /// it doesn't need to be aligned.
+    pub unsafe fn read(ty: Type, ptr: *const u8) -> Option<JitValue> {
+        let value = match repr(ty)? {
+            Repr::Signed(1) => JitValue::Int(ptr::read_unaligned(ptr as *const i8) as i64),
+            Repr::Signed(2) => JitValue::Int(ptr::read_unaligned(ptr as *const i16) as i64),
+            Repr::Signed(4) => JitValue::Int(ptr::read_unaligned(ptr as *const i32) as i64),
+            Repr::Signed(8) => JitValue::Int(ptr::read_unaligned(ptr as *const i64)),
+            Repr::Unsigned(1) => JitValue::UInt(ptr::read_unaligned(ptr) as u64),
+            Repr::Unsigned(2) => JitValue::UInt(ptr::read_unaligned(ptr as *const u16) as u64),
+            Repr::Unsigned(4) => JitValue::UInt(ptr::read_unaligned(ptr as *const u32) as u64),
+            Repr::Unsigned(8) => JitValue::UInt(ptr::read_unaligned(ptr as *const u64)),
+            Repr::Float(4) => JitValue::Float(ptr::read_unaligned(ptr as *const f32) as f64),
+            Repr::Float(8) => JitValue::Float(ptr::read_unaligned(ptr as *const f64)),
+            Repr::Bool => JitValue::Bool(ptr::read_unaligned(ptr) != 0),
+            Repr::Pointer => JitValue::Ptr(ptr::read_unaligned(ptr as *const *mut ())),
+            _ => return None
+        };
+        Some(value)
+    }
+
+    /// Writes this value to memory as a value of the given type, converting
+    /// it like to_constant does. Returns false if nothing was written
+    /// because the type isn't supported.
+    ///
+    /// # Safety
+    ///
+    /// ptr must be valid for writes of a value of the given type, although
+    /// it doesn't need to be aligned.
+    pub unsafe fn write(&self, ty: Type, ptr: *mut u8) -> bool {
+        let (int, float) = (self.as_i64(), self.as_f64());
+        match repr(ty) {
+            Some(Repr::Signed(1)) | Some(Repr::Unsigned(1)) => ptr::write_unaligned(ptr, int as u8),
+            Some(Repr::Signed(2)) | Some(Repr::Unsigned(2)) => ptr::write_unaligned(ptr as *mut u16, int as u16),
+            Some(Repr::Signed(4)) | Some(Repr::Unsigned(4)) => ptr::write_unaligned(ptr as *mut u32, int as u32),
+            Some(Repr::Signed(8)) | Some(Repr::Unsigned(8)) => ptr::write_unaligned(ptr as *mut u64, int as u64),
+            Some(Repr::Float(4)) => ptr::write_unaligned(ptr as *mut f32, float as f32),
+            Some(Repr::Float(8)) => ptr::write_unaligned(ptr as *mut f64, float),
+            Some(Repr::Bool) => ptr::write_unaligned(ptr, (int != 0 || float != 0.0) as u8),
+            Some(Repr::Pointer) => match *self {
+                JitValue::Ptr(value) => ptr::write_unaligned(ptr as *mut *mut (), value),
+                JitValue::Int(_) | JitValue::UInt(_) => {
+                    ptr::write_unaligned(ptr as *mut *mut (), int as usize as *mut ())
+                }
+                _ => return false
+            },
+            _ => return false
+        }
+        true
+    }
+
+    pub(crate) fn as_i64(&self) -> i64 {
+        match *self {
+            JitValue::Int(value) => value,
+            JitValue::UInt(value) => value as i64,
+            JitValue::Float(value) => value as i64,
+            JitValue::Ptr(value) => value as usize as i64,
+            JitValue::Bool(value) => value as i64
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> f64 {
+        match *self {
+            JitValue::Int(value) => value as f64,
+            JitValue::UInt(value) => value as f64,
+            JitValue::Float(value) => value,
+            JitValue::Ptr(value) => value as usize as f64,
+            JitValue::Bool(value) => value as i64 as f64
+        }
+    }
+}
+
+fn repr(ty: Type) -> Option<Repr> {
+    let ty = unsafe { types::get_ptr(&ty) };
+    tracking::with_registry(|reg| {
+        match *reg.type_kind(reg.unqualified(ty))? {
+            TypeKind::Pointer(_) => Some(Repr::Pointer),
+            TypeKind::Basic(ref kind) => basic_repr(kind),
+            _ => None
+        }
+    })
+}
+
+fn basic_repr(kind: &gccjit_sys::gcc_jit_types) -> Option<Repr> {
+    let char_is_signed = c_char::MIN != 0;
+    let repr = match *kind {
+        GCC_JIT_TYPE_BOOL => Repr::Bool,
+        GCC_JIT_TYPE_CHAR if char_is_signed => Repr::Signed(1),
+        GCC_JIT_TYPE_CHAR => Repr::Unsigned(1),
+        GCC_JIT_TYPE_SIGNED_CHAR => Repr::Signed(1),
+        GCC_JIT_TYPE_UNSIGNED_CHAR => Repr::Unsigned(1),
+        GCC_JIT_TYPE_SHORT => Repr::Signed(mem::size_of::<c_short>()),
+        GCC_JIT_TYPE_UNSIGNED_SHORT => Repr::Unsigned(mem::size_of::<c_short>()),
+        GCC_JIT_TYPE_INT => Repr::Signed(mem::size_of::<c_int>()),
+        GCC_JIT_TYPE_UNSIGNED_INT => Repr::Unsigned(mem::size_of::<c_int>()),
+        GCC_JIT_TYPE_LONG => Repr::Signed(mem::size_of::<c_long>()),
+        GCC_JIT_TYPE_UNSIGNED_LONG => Repr::Unsigned(mem::size_of::<c_long>()),
+        GCC_JIT_TYPE_LONG_LONG => Repr::Signed(mem::size_of::<c_longlong>()),
+        GCC_JIT_TYPE_UNSIGNED_LONG_LONG => Repr::Unsigned(mem::size_of::<c_longlong>()),
+        GCC_JIT_TYPE_SIZE_T => Repr::Unsigned(mem::size_of::<usize>()),
+        GCC_JIT_TYPE_FLOAT => Repr::Float(4),
+        GCC_JIT_TYPE_DOUBLE => Repr::Float(8),
+        GCC_JIT_TYPE_VOID_PTR |
+        GCC_JIT_TYPE_CONST_CHAR_PTR |
+        GCC_JIT_TYPE_FILE_PTR => Repr::Pointer,
+        _ => return None
+    };
+    Some(repr)
+}
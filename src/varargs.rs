@@ -0,0 +1,252 @@
+//! Access to the variadic arguments of jitted functions.
+//!
+//! gccjit exposes va_start and va_end as builtins, but va_arg is a
+//! language construct in C rather than a function, so there is no builtin
+//! for it. Instead, this module knows the layout of va_list on the
+//! supported hosts and reads arguments out of it with ordinary gccjit IR,
+//! the same way that GCC expands va_arg for those targets.
+
+use std::mem;
+use std::os::raw::c_int;
+
+use gccjit_sys;
+
+use abi::{self, Convention, Scalar};
+use block::{BinaryOp, Block, ComparisonOp};
+use context::Context;
+use field::Field;
+use function::{self, Function};
+use location::Location;
+use lvalue::LValue;
+use rvalue::{RValue, ToRValue};
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// The host representations of va_list that this module understands.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Layout {
+    /// A pointer into an argument area where every argument takes up one
+    /// eight byte slot, as on Windows x64 and Apple's AArch64.
+    Pointer,
+    /// The SysV x86-64 register save area descriptor.
+    SysV64,
+    /// The AAPCS64 register save area descriptor.
+    Aapcs64
+}
+
+impl Layout {
+    fn host() -> Option<Layout> {
+        match Convention::host()? {
+            Convention::SysV64 => Some(Layout::SysV64),
+            Convention::Win64 => Some(Layout::Pointer),
+            Convention::Aapcs64 if cfg!(target_vendor = "apple") => Some(Layout::Pointer),
+            Convention::Aapcs64 => Some(Layout::Aapcs64)
+        }
+    }
+}
+
+/// VaList is a started va_list local of a variadic function, created by
+/// Context::va_start. Arguments are read from it with Context::va_arg and
+/// it must be finished with Context::va_end before the function returns.
+#[derive(Copy, Clone)]
+pub struct VaList<'ctx> {
+    layout: Layout,
+    local: LValue<'ctx>,
+    fields: [Option<Field<'ctx>>; 5]
+}
+
+impl<'ctx> VaList<'ctx> {
+    /// Gets the va_list local itself, so that it can be passed to
+    /// functions such as vprintf. On targets where va_list is an array
+    /// type, the address of the first element should be passed instead,
+    /// which is what C does implicitly.
+    pub fn as_lvalue(&self) -> LValue<'ctx> {
+        self.local
+    }
+
+    fn field(&self, idx: usize) -> Field<'ctx> {
+        self.fields[idx].unwrap()
+    }
+}
+
+pub fn start<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                       loc: Option<Location<'a>>,
+                       block: Block<'a>) -> Option<VaList<'a>> {
+    let layout = Layout::host()?;
+    let func = block.get_function();
+    let last_param = tracking::with_registry(|reg| {
+        reg.function(unsafe { function::get_ptr(&func) })
+            .filter(|info| info.is_variadic)
+            .and_then(|info| info.params.len().checked_sub(1))
+    })?;
+    let builtin = ctx.get_builtin_function("__builtin_va_start");
+    if unsafe { function::get_ptr(&builtin) }.is_null() {
+        return None;
+    }
+    let uint_ty = ctx.new_type::<u32>();
+    let int_ty = ctx.new_type::<c_int>();
+    let void_ptr = ctx.new_type::<*mut ()>();
+    let list = match layout {
+        Layout::Pointer => {
+            let ty = ctx.new_type::<u8>().make_pointer();
+            VaList {
+                layout,
+                local: func.new_local(loc, ty, "va_list"),
+                fields: [None; 5]
+            }
+        }
+        Layout::SysV64 => {
+            let fields = [ctx.new_field(loc, uint_ty, "gp_offset"),
+                          ctx.new_field(loc, uint_ty, "fp_offset"),
+                          ctx.new_field(loc, void_ptr, "overflow_arg_area"),
+                          ctx.new_field(loc, void_ptr, "reg_save_area")];
+            let tag = ctx.new_struct_type(loc, "__va_list_tag", &fields).as_type();
+            let ty = ctx.new_array_type(loc, tag, 1);
+            VaList {
+                layout,
+                local: func.new_local(loc, ty, "va_list"),
+                fields: [Some(fields[0]), Some(fields[1]), Some(fields[2]), Some(fields[3]), None]
+            }
+        }
+        Layout::Aapcs64 => {
+            let fields = [ctx.new_field(loc, void_ptr, "__stack"),
+                          ctx.new_field(loc, void_ptr, "__gr_top"),
+                          ctx.new_field(loc, void_ptr, "__vr_top"),
+                          ctx.new_field(loc, int_ty, "__gr_offs"),
+                          ctx.new_field(loc, int_ty, "__vr_offs")];
+            let ty = ctx.new_struct_type(loc, "__va_list", &fields).as_type();
+            VaList {
+                layout,
+                local: func.new_local(loc, ty, "va_list"),
+                fields: [Some(fields[0]), Some(fields[1]), Some(fields[2]), Some(fields[3]), Some(fields[4])]
+            }
+        }
+    };
+    // gcc checks that the second argument is the last named parameter,
+    // even though it doesn't use it for anything.
+    let last = func.get_param(last_param as i32).to_rvalue();
+    call_builtin(ctx, loc, block, builtin, &list, Some(last));
+    Some(list)
+}
+
+pub fn end<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                     loc: Option<Location<'a>>,
+                     block: Block<'a>,
+                     list: &VaList<'a>) {
+    let builtin = ctx.get_builtin_function("__builtin_va_end");
+    call_builtin(ctx, loc, block, builtin, list, None);
+}
+
+pub fn arg<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                     loc: Option<Location<'a>>,
+                     block: Block<'a>,
+                     list: &VaList<'a>,
+                     ty: Type<'a>) -> Option<(Block<'a>, RValue<'a>)> {
+    let scalar = tracking::with_registry(|reg| {
+        let ty = reg.unqualified(unsafe { types::get_ptr(&ty) });
+        match *reg.type_kind(ty)? {
+            TypeKind::Pointer(_) => Some(Scalar::Integer),
+            TypeKind::Basic(ref kind) => match abi::basic_layout(kind)? {
+                (0, _, _) => None,
+                (size, _, scalar) if size <= mem::size_of::<u64>() => Some(scalar),
+                _ => None
+            },
+            _ => None
+        }
+    })?;
+    // floats are promoted to double when passed through an ellipsis.
+    let slot_ty = match scalar {
+        Scalar::Integer | Scalar::Double => ty,
+        Scalar::Float => ctx.new_type::<f64>(),
+        Scalar::Other => return None
+    };
+    let func = block.get_function();
+    let byte_ptr = ctx.new_type::<u8>().make_pointer();
+    let void_ptr = ctx.new_type::<*mut ()>();
+    let addr = func.new_local(loc, byte_ptr, "va_arg_addr");
+    let next_slot = |area: RValue<'a>| {
+        let eight = ctx.new_rvalue_from_int(ctx.new_type::<c_int>(), 8);
+        ctx.new_array_access(loc, ctx.new_cast(loc, area, byte_ptr), eight).get_address(loc)
+    };
+    let done = match list.layout {
+        Layout::Pointer => {
+            block.add_assignment(loc, addr, list.local);
+            block.add_assignment(loc, list.local, next_slot(addr.to_rvalue()));
+            block
+        }
+        Layout::SysV64 | Layout::Aapcs64 => {
+            let is_sysv = list.layout == Layout::SysV64;
+            let record = if is_sysv {
+                let zero = ctx.new_rvalue_zero(ctx.new_type::<c_int>());
+                ctx.new_array_access(loc, list.local, zero)
+            } else {
+                list.local
+            };
+            let is_int = scalar == Scalar::Integer;
+            // (offset field, register area field, overflow area field,
+            //  size of a register's slot in the save area)
+            let (offset, area, overflow, step) = match (is_sysv, is_int) {
+                (true, true) => (list.field(0), list.field(3), list.field(2), 8),
+                (true, false) => (list.field(1), list.field(3), list.field(2), 16),
+                (false, true) => (list.field(3), list.field(1), list.field(0), 8),
+                (false, false) => (list.field(4), list.field(2), list.field(0), 16)
+            };
+            let offset = record.access_field(loc, offset);
+            let offset_ty = offset.to_rvalue().get_type();
+            // SysV counts up to the end of the register save area, which
+            // holds six general purpose and eight SSE registers. AAPCS64
+            // counts negative offsets up to zero.
+            let in_registers = if is_sysv {
+                let limit = if is_int { 6 * 8 } else { 6 * 8 + 8 * 16 };
+                ctx.new_comparison(loc, ComparisonOp::LessThan, offset,
+                                   ctx.new_rvalue_from_int(offset_ty, limit))
+            } else {
+                ctx.new_comparison(loc, ComparisonOp::LessThan, offset,
+                                   ctx.new_rvalue_zero(offset_ty))
+            };
+            let reg_block = func.new_block("va_arg_reg");
+            let mem_block = func.new_block("va_arg_mem");
+            let done = func.new_block("va_arg_done");
+            block.end_with_conditional(loc, in_registers, reg_block, mem_block);
+
+            let area = ctx.new_cast(loc, record.access_field(loc, area), byte_ptr);
+            reg_block.add_assignment(loc, addr, ctx.new_array_access(loc, area, offset).get_address(loc));
+            reg_block.add_assignment_op(loc, offset, BinaryOp::Plus,
+                                        ctx.new_rvalue_from_int(offset_ty, step));
+            reg_block.end_with_jump(loc, done);
+
+            let overflow = record.access_field(loc, overflow);
+            mem_block.add_assignment(loc, addr, ctx.new_cast(loc, overflow, byte_ptr));
+            mem_block.add_assignment(loc, overflow, ctx.new_cast(loc, next_slot(addr.to_rvalue()), void_ptr));
+            mem_block.end_with_jump(loc, done);
+            done
+        }
+    };
+    let value = func.new_local(loc, ty, "va_arg_value");
+    let slot = ctx.new_cast(loc, addr, slot_ty.make_pointer()).dereference(loc).to_rvalue();
+    let slot = if scalar == Scalar::Float {
+        ctx.new_cast(loc, slot, ty)
+    } else {
+        slot
+    };
+    done.add_assignment(loc, value, slot);
+    Some((done, value.to_rvalue()))
+}
+
+/// Calls va_start or va_end on a va_list, converting the address of the
+/// list to whatever type gcc uses for the builtin's va_list parameter.
+fn call_builtin<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                          loc: Option<Location<'a>>,
+                          block: Block<'a>,
+                          builtin: Function<'a>,
+                          list: &VaList<'a>,
+                          extra: Option<RValue<'a>>) {
+    let param_ty = unsafe {
+        let param = gccjit_sys::gcc_jit_function_get_param(function::get_ptr(&builtin), 0);
+        let rvalue = gccjit_sys::gcc_jit_param_as_rvalue(param);
+        types::from_ptr(gccjit_sys::gcc_jit_rvalue_get_type(rvalue))
+    };
+    let mut args = vec![ctx.new_cast(loc, list.local.get_address(loc), param_ty)];
+    args.extend(extra);
+    block.add_eval(loc, ctx.new_call(loc, builtin, &args));
+}
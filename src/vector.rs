@@ -0,0 +1,77 @@
+//! Masks for vector values: comparing vectors gives a mask vector whose
+//! elements are all ones where the comparison holds and all zeros where
+//! it doesn't, which can then pick elements from two vectors without
+//! branching.
+
+use abi;
+#[cfg(feature = "master")]
+use block::ComparisonOp;
+use block::{BinaryOp, UnaryOp};
+use context::Context;
+use location::Location;
+use rvalue::RValue;
+use tracking::{self, TypeKind};
+use types::{self, Type};
+
+/// Gets the vector type of signed integers of the same size as the
+/// elements of the given vector type.
+pub fn mask_type<'a, 'ctx>(ctx: &'a Context<'ctx>, vec_ty: Type<'a>) -> Option<Type<'a>> {
+    let (elem_size, num_units) = tracking::with_registry(|reg| {
+        match reg.type_kind(reg.unqualified(unsafe { types::get_ptr(&vec_ty) })) {
+            Some(&TypeKind::Vector(elem, num_units)) => Some((abi::size_and_align(reg, elem)?.0, num_units)),
+            _ => None
+        }
+    })?;
+    let elem_ty = match elem_size {
+        1 => ctx.new_type::<i8>(),
+        2 => ctx.new_type::<i16>(),
+        4 => ctx.new_type::<i32>(),
+        8 => ctx.new_type::<i64>(),
+        _ => return None
+    };
+    Some(elem_ty.make_vector(num_units))
+}
+
+#[cfg(feature = "master")]
+pub fn comparison<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                            loc: Option<Location<'a>>,
+                            op: ComparisonOp,
+                            lhs: RValue<'a>,
+                            rhs: RValue<'a>) -> RValue<'a> {
+    let ty = lhs.get_type();
+    if mask_type(ctx, ty).is_none() {
+        panic!("new_vector_comparison needs vectors, not values of type {:?}", ty);
+    }
+    ctx.new_comparison(loc, op, lhs, rhs)
+}
+
+pub fn select<'a, 'ctx>(ctx: &'a Context<'ctx>,
+                        loc: Option<Location<'a>>,
+                        mask: RValue<'a>,
+                        on_true: RValue<'a>,
+                        on_false: RValue<'a>) -> RValue<'a> {
+    let ty = on_true.get_type();
+    let mask_ty = match mask_type(ctx, ty) {
+        Some(mask_ty) => mask_ty,
+        None => panic!("new_vector_select needs vectors, not values of type {:?}", ty)
+    };
+    // the values are blended as integers, since bitwise operations can't
+    // be done on vectors of floating point values.
+    let as_mask = |value: RValue<'a>| {
+        if unsafe { types::get_ptr(&value.get_type()) == types::get_ptr(&mask_ty) } {
+            value
+        } else {
+            ctx.new_bitcast(loc, value, mask_ty)
+        }
+    };
+    let mask = as_mask(mask);
+    let not_mask = ctx.new_unary_op(loc, UnaryOp::BitwiseNegate, mask_ty, mask);
+    let picked_true = ctx.new_binary_op(loc, BinaryOp::BitwiseAnd, mask_ty, as_mask(on_true), mask);
+    let picked_false = ctx.new_binary_op(loc, BinaryOp::BitwiseAnd, mask_ty, as_mask(on_false), not_mask);
+    let blended = ctx.new_binary_op(loc, BinaryOp::BitwiseOr, mask_ty, picked_true, picked_false);
+    if unsafe { types::get_ptr(&ty) == types::get_ptr(&mask_ty) } {
+        blended
+    } else {
+        ctx.new_bitcast(loc, blended, ty)
+    }
+}
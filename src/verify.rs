@@ -0,0 +1,484 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ptr;
+
+use gccjit_sys;
+use gccjit_sys::gcc_jit_types::*;
+
+use block;
+use dot;
+use field;
+use function::{self, FunctionType};
+use location;
+use lvalue;
+use rvalue;
+use types;
+use tracking::{self, Registry, BlockInfo, CaseInfo, FunctionInfo, TypeKind, Statement};
+
+/// The kinds of problems that Context::verify is able to detect.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ViolationKind {
+    /// A block of a defined function doesn't end with a terminator.
+    UnterminatedBlock,
+    /// A statement was added to a block after it was terminated.
+    StatementAfterTerminator,
+    /// A block can't be reached from the entry block of its function.
+    /// Not reported if Context::set_allow_unreachable_blocks is set.
+    UnreachableBlock,
+    /// A block jumps to a block that belongs to a different function.
+    ForeignBlock,
+    /// A value is assigned to an lvalue of an incompatible type.
+    IncompatibleAssignment,
+    /// A returned value doesn't match the return type of its function.
+    IncompatibleReturn,
+    /// A function is called with the wrong number of arguments.
+    ArgumentCount,
+    /// A function is called with an argument of an incompatible type.
+    IncompatibleArgument,
+    /// A field is accessed on a value whose type doesn't contain it.
+    ForeignField,
+    /// A case of a switch has a minimum greater than its maximum, or
+    /// overlaps another case of the same switch.
+    CaseRange,
+    /// The locals of a function are estimated to take more stack than the
+    /// threshold set with Context::set_stack_warning_threshold.
+    LargeStackFrame,
+    /// Tracking wasn't enabled with Context::set_tracking, so the code of
+    /// the context wasn't recorded and couldn't be checked.
+    TrackingDisabled
+}
+
+/// A single problem found by Context::verify. The message describes the
+/// offending objects using their gccjit debug strings.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub message: String
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt.write_str(&self.message)
+    }
+}
+
+pub fn verify(ctx: *mut gccjit_sys::gcc_jit_context) -> Result<(), Vec<Violation>> {
+    let violations = tracking::with_registry(|reg| {
+        let mut violations = vec![];
+        if let Some(info) = reg.get_context(ctx) {
+            if !info.config.tracking {
+                violation(&mut violations, ViolationKind::TrackingDisabled, ptr::null_mut(),
+                          "the context can't be verified, since tracking isn't enabled".to_owned());
+                return violations;
+            }
+            for func in info.functions.iter() {
+                check_function(reg, func, &mut violations);
+                if !info.config.allow_unreachable_blocks {
+                    check_reachability(reg, func, &mut violations);
+                }
+                if let Some(threshold) = info.config.stack_warning_threshold {
+                    check_stack_frame(reg, func, threshold, &mut violations);
+                }
+            }
+            for call in info.calls.iter() {
+                check_call(reg, call, &mut violations);
+            }
+            for access in info.field_accesses.iter() {
+                check_field_access(reg, access, &mut violations);
+            }
+        }
+        violations
+    });
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+fn violation(violations: &mut Vec<Violation>,
+             kind: ViolationKind,
+             loc: *mut gccjit_sys::gcc_jit_location,
+             message: String) {
+    let message = if loc.is_null() {
+        message
+    } else {
+        format!("{:?}: {}", unsafe { location::from_ptr(loc) }, message)
+    };
+    violations.push(Violation {
+        kind,
+        message
+    });
+}
+
+fn check_function(reg: &Registry,
+                  func: &FunctionInfo,
+                  violations: &mut Vec<Violation>) {
+    if func.kind == FunctionType::Extern {
+        return;
+    }
+    let return_type = reg.unqualified(func.return_type);
+    let returns_void = match reg.type_kind(return_type) {
+        Some(&TypeKind::Basic(GCC_JIT_TYPE_VOID)) => Some(true),
+        Some(_) => Some(false),
+        None => None
+    };
+    for &block_ptr in func.blocks.iter() {
+        let block = match reg.block(block_ptr) {
+            Some(block) => block,
+            None => continue
+        };
+        let terminator = block.statements.iter().position(|s| s.kind.is_terminator());
+        match terminator {
+            None => violation(violations,
+                              ViolationKind::UnterminatedBlock,
+                              ptr::null_mut(),
+                              format!("block {} of function {} is not terminated",
+                                      block.name, func.name)),
+            Some(idx) if idx + 1 < block.statements.len() => {
+                let stmt = &block.statements[idx + 1];
+                violation(violations,
+                          ViolationKind::StatementAfterTerminator,
+                          stmt.loc,
+                          format!("block {} of function {} has statements after its terminator",
+                                  block.name, func.name));
+            }
+            _ => {}
+        }
+        for stmt in block.statements.iter() {
+            match stmt.kind {
+                Statement::Assignment(lvalue, rvalue) |
+                Statement::AssignmentOp(lvalue, _, rvalue) => {
+                    let ltype = lvalue_type(lvalue);
+                    let rtype = rvalue_type(rvalue);
+                    if accepts_writes_from(reg, ltype, rtype) == Some(false) {
+                        violation(violations,
+                                  ViolationKind::IncompatibleAssignment,
+                                  stmt.loc,
+                                  format!("cannot assign {:?} of type {:?} to {:?} of type {:?}",
+                                          unsafe { rvalue::from_ptr(rvalue) },
+                                          unsafe { types::from_ptr(rtype) },
+                                          unsafe { lvalue::from_ptr(lvalue) },
+                                          unsafe { types::from_ptr(ltype) }));
+                    }
+                }
+                Statement::Return(rvalue) => {
+                    let rtype = rvalue_type(rvalue);
+                    if returns_void == Some(true) {
+                        violation(violations,
+                                  ViolationKind::IncompatibleReturn,
+                                  stmt.loc,
+                                  format!("function {} returns void but block {} returns {:?}",
+                                          func.name, block.name,
+                                          unsafe { rvalue::from_ptr(rvalue) }));
+                    } else if accepts_writes_from(reg, func.return_type, rtype) == Some(false) {
+                        violation(violations,
+                                  ViolationKind::IncompatibleReturn,
+                                  stmt.loc,
+                                  format!("function {} returns {:?} but block {} returns {:?} of type {:?}",
+                                          func.name,
+                                          unsafe { types::from_ptr(func.return_type) },
+                                          block.name,
+                                          unsafe { rvalue::from_ptr(rvalue) },
+                                          unsafe { types::from_ptr(rtype) }));
+                    }
+                }
+                Statement::VoidReturn if returns_void == Some(false) => {
+                    violation(violations,
+                              ViolationKind::IncompatibleReturn,
+                              stmt.loc,
+                              format!("function {} returns {:?} but block {} returns void",
+                                      func.name,
+                                      unsafe { types::from_ptr(func.return_type) },
+                                      block.name));
+                }
+                Statement::Jump(target) => {
+                    check_target(reg, func, block_ptr, target, stmt.loc, violations);
+                }
+                Statement::Conditional(_, on_true, on_false) => {
+                    check_target(reg, func, block_ptr, on_true, stmt.loc, violations);
+                    check_target(reg, func, block_ptr, on_false, stmt.loc, violations);
+                }
+                Statement::Switch(_, default_block, ref cases) => {
+                    check_target(reg, func, block_ptr, default_block, stmt.loc, violations);
+                    for case in cases.iter() {
+                        check_target(reg, func, block_ptr, case.dest, stmt.loc, violations);
+                    }
+                    check_cases(reg, func, block, cases, stmt.loc, violations);
+                }
+                #[cfg(feature = "master")]
+                Statement::TryCatch(try_block, catch_block) |
+                Statement::TryFinally(try_block, catch_block) => {
+                    check_target(reg, func, block_ptr, try_block, stmt.loc, violations);
+                    check_target(reg, func, block_ptr, catch_block, stmt.loc, violations);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn check_stack_frame(reg: &Registry,
+                     func: &FunctionInfo,
+                     threshold: usize,
+                     violations: &mut Vec<Violation>) {
+    let bytes = function::stack_bytes(reg, func);
+    if bytes > threshold {
+        violation(violations,
+                  ViolationKind::LargeStackFrame,
+                  ptr::null_mut(),
+                  format!("the locals of function `{}` take an estimated {} bytes of stack, more than {}",
+                          func.name, bytes, threshold));
+    }
+}
+
+fn check_target(reg: &Registry,
+                func: &FunctionInfo,
+                from: *mut gccjit_sys::gcc_jit_block,
+                target: *mut gccjit_sys::gcc_jit_block,
+                loc: *mut gccjit_sys::gcc_jit_location,
+                violations: &mut Vec<Violation>) {
+    let target_info = match reg.block(target) {
+        Some(info) => info,
+        None => return
+    };
+    if target_info.function != func.ptr {
+        violation(violations,
+                  ViolationKind::ForeignBlock,
+                  loc,
+                  format!("block {:?} of function {} jumps to block {:?} of function {:?}",
+                          unsafe { block::from_ptr(from) },
+                          func.name,
+                          unsafe { block::from_ptr(target) },
+                          unsafe { function::from_ptr(target_info.function) }));
+    }
+}
+
+/// Checks that the cases of a switch are ordered ranges that don't
+/// overlap. Cases whose bounds aren't known constants are skipped.
+fn check_cases(reg: &Registry,
+               func: &FunctionInfo,
+               block: &BlockInfo,
+               cases: &[CaseInfo],
+               loc: *mut gccjit_sys::gcc_jit_location,
+               violations: &mut Vec<Violation>) {
+    let mut ranges = vec![];
+    for case in cases.iter() {
+        let (min, max) = match (reg.int_constant(case.min), reg.int_constant(case.max)) {
+            (Some(min), Some(max)) => (min, max),
+            _ => continue
+        };
+        if min > max {
+            violation(violations,
+                      ViolationKind::CaseRange,
+                      loc,
+                      format!("`{}` in block {} of function {} has a minimum greater than its maximum",
+                              dot::case_text(case), block.name, func.name));
+        } else {
+            ranges.push((min, max, case));
+        }
+    }
+    ranges.sort_by_key(|&(min, max, _)| (min, max));
+    // the case that reaches the furthest so far, which every later case
+    // that overlaps an earlier one also overlaps.
+    let mut furthest : Option<(i64, &CaseInfo)> = None;
+    for &(min, max, case) in ranges.iter() {
+        if let Some((furthest_max, furthest_case)) = furthest {
+            if min <= furthest_max {
+                violation(violations,
+                          ViolationKind::CaseRange,
+                          loc,
+                          format!("`{}` in block {} of function {} overlaps `{}`",
+                                  dot::case_text(case), block.name, func.name,
+                                  dot::case_text(furthest_case)));
+            }
+            if max <= furthest_max {
+                continue;
+            }
+        }
+        furthest = Some((max, case));
+    }
+}
+
+fn check_reachability(reg: &Registry,
+                      func: &FunctionInfo,
+                      violations: &mut Vec<Violation>) {
+    let entry = match func.blocks.first() {
+        Some(&entry) => entry,
+        None => return
+    };
+    let mut reachable = HashSet::new();
+    let mut worklist = vec![entry];
+    while let Some(block_ptr) = worklist.pop() {
+        if !reachable.insert(block_ptr) {
+            continue;
+        }
+        let block = match reg.block(block_ptr) {
+            Some(block) => block,
+            None => continue
+        };
+        for stmt in block.statements.iter() {
+            match stmt.kind {
+                Statement::Jump(target) => worklist.push(target),
+                Statement::Conditional(_, on_true, on_false) => {
+                    worklist.push(on_true);
+                    worklist.push(on_false);
+                }
+                Statement::Switch(_, default_block, ref cases) => {
+                    worklist.push(default_block);
+                    worklist.extend(cases.iter().map(|case| case.dest));
+                }
+                #[cfg(feature = "master")]
+                Statement::TryCatch(try_block, catch_block) |
+                Statement::TryFinally(try_block, catch_block) => {
+                    worklist.push(try_block);
+                    worklist.push(catch_block);
+                }
+                _ => {}
+            }
+        }
+    }
+    for &block_ptr in func.blocks.iter() {
+        if !reachable.contains(&block_ptr) {
+            let name = reg.block(block_ptr).map(|b| &b.name[..]).unwrap_or("<unknown>");
+            violation(violations,
+                      ViolationKind::UnreachableBlock,
+                      ptr::null_mut(),
+                      format!("block {} of function {} is unreachable", name, func.name));
+        }
+    }
+}
+
+fn check_call(reg: &Registry,
+              call: &tracking::CallInfo,
+              violations: &mut Vec<Violation>) {
+    let func = match reg.function(call.function) {
+        Some(func) => func,
+        None => return
+    };
+    let arity_ok = if func.is_variadic {
+        call.args.len() >= func.params.len()
+    } else {
+        call.args.len() == func.params.len()
+    };
+    if !arity_ok {
+        violation(violations,
+                  ViolationKind::ArgumentCount,
+                  call.loc,
+                  format!("function {} takes {}{} arguments but was called with {}",
+                          func.name,
+                          if func.is_variadic { "at least " } else { "" },
+                          func.params.len(),
+                          call.args.len()));
+    }
+    for (idx, (&arg, &param)) in call.args.iter().zip(func.params.iter()).enumerate() {
+        let param_type = rvalue_type(unsafe { gccjit_sys::gcc_jit_param_as_rvalue(param) });
+        let arg_type = rvalue_type(arg);
+        if accepts_writes_from(reg, param_type, arg_type) == Some(false) {
+            violation(violations,
+                      ViolationKind::IncompatibleArgument,
+                      call.loc,
+                      format!("argument {} of call to {} is {:?} of type {:?}, expected {:?}",
+                              idx,
+                              func.name,
+                              unsafe { rvalue::from_ptr(arg) },
+                              unsafe { types::from_ptr(arg_type) },
+                              unsafe { types::from_ptr(param_type) }));
+        }
+    }
+}
+
+fn check_field_access(reg: &Registry,
+                      access: &tracking::FieldAccessInfo,
+                      violations: &mut Vec<Violation>) {
+    let field = unsafe { field::from_ptr(access.field) };
+    let owner = match reg.field_owner(access.field) {
+        Some(owner) => owner,
+        None => {
+            violation(violations,
+                      ViolationKind::ForeignField,
+                      access.loc,
+                      format!("field {:?} does not belong to any struct or union", field));
+            return;
+        }
+    };
+    let container = if access.through_pointer {
+        match reg.type_kind(reg.unqualified(access.value_type)) {
+            Some(&TypeKind::Pointer(pointee)) => pointee,
+            _ => return
+        }
+    } else {
+        access.value_type
+    };
+    if reg.unqualified(container) != owner {
+        violation(violations,
+                  ViolationKind::ForeignField,
+                  access.loc,
+                  format!("field {:?} of {:?} is accessed on a value of type {:?}",
+                          field,
+                          unsafe { types::from_ptr(owner) },
+                          unsafe { types::from_ptr(access.value_type) }));
+    }
+}
+
+fn rvalue_type(rvalue: *mut gccjit_sys::gcc_jit_rvalue) -> *mut gccjit_sys::gcc_jit_type {
+    unsafe { gccjit_sys::gcc_jit_rvalue_get_type(rvalue) }
+}
+
+fn lvalue_type(lvalue: *mut gccjit_sys::gcc_jit_lvalue) -> *mut gccjit_sys::gcc_jit_type {
+    unsafe { rvalue_type(gccjit_sys::gcc_jit_lvalue_as_rvalue(lvalue)) }
+}
+
+fn is_basic_pointer(kind: &TypeKind) -> bool {
+    matches!(*kind,
+             TypeKind::Basic(GCC_JIT_TYPE_VOID_PTR) |
+             TypeKind::Basic(GCC_JIT_TYPE_CONST_CHAR_PTR) |
+             TypeKind::Basic(GCC_JIT_TYPE_FILE_PTR))
+}
+
+fn is_basic_float(kind: &TypeKind) -> bool {
+    matches!(*kind,
+             TypeKind::Basic(GCC_JIT_TYPE_FLOAT) |
+             TypeKind::Basic(GCC_JIT_TYPE_DOUBLE) |
+             TypeKind::Basic(GCC_JIT_TYPE_LONG_DOUBLE))
+}
+
+/// Mirrors gccjit's rule for whether a value of type rtype can be written
+/// to a location of type ltype. Returns None if not enough is known about
+/// either type to decide, or if gccjit's answer depends on the target.
+fn accepts_writes_from(reg: &Registry,
+                       ltype: *mut gccjit_sys::gcc_jit_type,
+                       rtype: *mut gccjit_sys::gcc_jit_type) -> Option<bool> {
+    let ltype = reg.unqualified(ltype);
+    let rtype = reg.unqualified(rtype);
+    if ltype == rtype {
+        return Some(true);
+    }
+    match (reg.type_kind(ltype), reg.type_kind(rtype)) {
+        (Some(&TypeKind::Basic(GCC_JIT_TYPE_VOID_PTR)), Some(other)) => {
+            match *other {
+                TypeKind::Pointer(_) => Some(true),
+                ref kind => Some(is_basic_pointer(kind))
+            }
+        }
+        (Some(&TypeKind::Pointer(lpointee)), Some(&TypeKind::Pointer(rpointee))) => {
+            accepts_writes_from(reg, lpointee, rpointee)
+        }
+        (Some(&TypeKind::Pointer(_)), Some(kind)) if is_basic_pointer(kind) => None,
+        // distinct integer types, such as long and long long, are accepted
+        // by newer versions of gccjit if they have the same size.
+        (Some(lkind @ &TypeKind::Basic(_)), Some(rkind @ &TypeKind::Basic(_))) => {
+            if is_basic_float(lkind) || is_basic_float(rkind) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        // array and vector types aren't unique, so two types of the same
+        // shape may be the same type to gccjit.
+        (Some(&TypeKind::Array(..)), Some(&TypeKind::Array(..))) |
+        (Some(&TypeKind::Vector(..)), Some(&TypeKind::Vector(..))) => None,
+        (Some(_), Some(_)) => Some(false),
+        _ => None
+    }
+}